@@ -0,0 +1,32 @@
+//! With the `ffi` feature, writes `include/yagrep.h` from the declarations in
+//! `src/ffi.rs`, so the header always matches the functions it declares.
+
+fn main() {
+    #[cfg(feature = "ffi")]
+    header();
+}
+
+#[cfg(feature = "ffi")]
+fn header() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    let config = cbindgen::Config {
+        language: cbindgen::Language::C,
+        header: Some(
+            "/*\n * The C interface of the yagrep library; see src/ffi.rs for how to build it.\n \
+             * Generated from src/ffi.rs by cbindgen; don't edit it by hand.\n */"
+                .to_string(),
+        ),
+        include_guard: Some("YAGREP_H".to_string()),
+        cpp_compat: true,
+        usize_is_size_t: true,
+        style: cbindgen::Style::Both,
+        documentation_style: cbindgen::DocumentationStyle::Doxy,
+        ..Default::default()
+    };
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src("src/ffi.rs")
+        .generate()
+        .expect("src/ffi.rs should be declarable in C")
+        .write_to_file("include/yagrep.h");
+}
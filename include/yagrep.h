@@ -0,0 +1,135 @@
+/*
+ * The C interface of the yagrep library; see src/ffi.rs for how to build it.
+ * Generated from src/ffi.rs by cbindgen; don't edit it by hand.
+ */
+
+#ifndef YAGREP_H
+#define YAGREP_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Something selected a line.
+ */
+#define YAGREP_OK 0
+
+/**
+ * Nothing was selected.
+ */
+#define YAGREP_NO_MATCH 1
+
+/**
+ * A required pointer was null, or a string wasn't UTF-8.
+ */
+#define YAGREP_ERROR_ARGUMENT -1
+
+/**
+ * The pattern didn't compile.
+ */
+#define YAGREP_ERROR_PATTERN -2
+
+/**
+ * The path doesn't exist, or an option was rejected.
+ */
+#define YAGREP_ERROR_CONFIG -3
+
+/**
+ * The search failed unexpectedly: a bug in yagrep, which the message describes.
+ */
+#define YAGREP_ERROR_INTERNAL -4
+
+/**
+ * Search options; a null pointer means all of them off.
+ */
+typedef struct YagrepOptions {
+  bool case_insensitive;
+  bool fixed_strings;
+  bool word_regexp;
+  bool line_regexp;
+  bool invert_match;
+  bool hidden;
+  /**
+   * Search files that ignore files exclude.
+   */
+  bool no_ignore;
+  bool follow;
+  /**
+   * Lines selected per file at most, 0 for no limit.
+   */
+  size_t max_count;
+  /**
+   * How deep to walk directories, 0 for no limit.
+   */
+  size_t max_depth;
+  /**
+   * `--glob` patterns, `glob_count` of them.
+   */
+  const char *const *globs;
+  size_t glob_count;
+} YagrepOptions;
+
+/**
+ * One selected line, valid only for the duration of the callback.
+ */
+typedef struct YagrepMatch {
+  /**
+   * The file's path, or null for standard input; not NUL-terminated.
+   */
+  const uint8_t *path;
+  size_t path_len;
+  size_t line_number;
+  size_t offset;
+  /**
+   * The line without its terminator; not NUL-terminated.
+   */
+  const uint8_t *line;
+  size_t line_len;
+  /**
+   * `span_count` pairs of start and end offsets within `line`.
+   */
+  const size_t *spans;
+  size_t span_count;
+} YagrepMatch;
+
+/**
+ * Receives each match with the caller's `user_data`; returning nonzero stops the
+ * search.
+ */
+typedef int (*YagrepCallback)(const struct YagrepMatch *found, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Searches `path` (standard input when null) for `pattern`, calling `callback` once for
+ * every selected line in walk order. Returns `YAGREP_OK` or `YAGREP_NO_MATCH`, or a
+ * negative error code with the message left for `yagrep_last_error`.
+ *
+ * # Safety
+ *
+ * `pattern` and a non-null `path` must be NUL-terminated strings, and a non-null
+ * `options` must point to a valid `YagrepOptions` whose `globs` holds `glob_count`
+ * NUL-terminated strings.
+ */
+int yagrep_search(const char *pattern,
+                  const char *path,
+                  const struct YagrepOptions *options,
+                  YagrepCallback callback,
+                  void *user_data);
+
+/**
+ * The message of the last error `yagrep_search` returned on this thread, or an empty
+ * string. Valid until the next call on the same thread.
+ */
+const char *yagrep_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* YAGREP_H */
@@ -0,0 +1,143 @@
+//! The `yagrep` Python module: `search(pattern, path=None, **options)` runs the library's
+//! search and returns the selected lines as `Match` objects.
+//!
+//! Built with maturin (`maturin develop` in this directory); it is a crate of its own so
+//! the main build never needs Python.
+
+// The wrappers pyo3 0.22 generates convert each `PyResult` into itself.
+#![allow(clippy::useless_conversion)]
+
+use pyo3::exceptions::{PyFileNotFoundError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// A selected line.
+#[pyclass(frozen, get_all, module = "yagrep")]
+struct Match {
+    /// The file the line is in, or `None` for standard input.
+    path: Option<String>,
+    line_number: usize,
+    offset: usize,
+    /// The line without its terminator; bytes that aren't UTF-8 become U+FFFD.
+    line: String,
+    /// `(start, end)` byte offsets of every match within the line.
+    spans: Vec<(usize, usize)>,
+}
+
+#[pymethods]
+impl Match {
+    fn __repr__(&self) -> String {
+        format!(
+            "Match(path={:?}, line_number={}, line={:?})",
+            self.path, self.line_number, self.line
+        )
+    }
+}
+
+/// Searches `path` (standard input when `None`) for `pattern`.
+///
+/// Options mirror the command line: `ignore_case`, `fixed_strings`, `word_regexp`,
+/// `line_regexp`, `invert_match`, `hidden`, `no_ignore`, `follow` (booleans),
+/// `max_count` and `max_depth` (integers), `glob` and `type` (lists of strings).
+///
+/// The GIL is released while the search runs, so other Python threads carry on.
+#[pyfunction]
+#[pyo3(signature = (pattern, path = None, **options))]
+fn search(
+    py: Python<'_>,
+    pattern: &str,
+    path: Option<&str>,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<Vec<Match>> {
+    let mut config = yagrep::SearchConfig::new();
+    config.pattern(pattern);
+    if let Some(path) = path {
+        if !std::path::Path::new(path).exists() {
+            return Err(PyFileNotFoundError::new_err(path.to_string()));
+        }
+        config.path(path);
+    }
+    for (key, value) in options.into_iter().flatten() {
+        let key = key.extract::<String>()?;
+        match key.as_str() {
+            "ignore_case" => {
+                config.case_insensitive(value.extract()?);
+            }
+            "fixed_strings" => {
+                config.fixed_strings(value.extract()?);
+            }
+            "word_regexp" => {
+                config.word_regexp(value.extract()?);
+            }
+            "line_regexp" => {
+                config.line_regexp(value.extract()?);
+            }
+            "invert_match" => {
+                config.invert_match(value.extract()?);
+            }
+            "hidden" => {
+                config.hidden(value.extract()?);
+            }
+            "no_ignore" => {
+                config.respect_ignore(!value.extract::<bool>()?);
+            }
+            "follow" => {
+                config.follow(value.extract()?);
+            }
+            "max_count" => {
+                config.max_count(value.extract()?);
+            }
+            "max_depth" => {
+                config.max_depth(value.extract()?);
+            }
+            "glob" => {
+                for glob in value.extract::<Vec<String>>()? {
+                    config.glob(&glob).map_err(value_error)?;
+                }
+            }
+            "type" => {
+                for name in value.extract::<Vec<String>>()? {
+                    config.select_type(&name).map_err(value_error)?;
+                }
+            }
+            _ => {
+                let message = format!("search() got an unexpected keyword argument '{}'", key);
+                return Err(PyTypeError::new_err(message));
+            }
+        }
+    }
+
+    py.allow_threads(|| {
+        let searcher = yagrep::Searcher::new(config)
+            .map_err(|err| PyValueError::new_err(format!("Failed to compile regex: {}", err)))?;
+        let matches = searcher
+            .search()
+            .map(|found| Match {
+                path: found.path.map(|path| path.display().to_string()),
+                line_number: found.line_number,
+                offset: found.offset,
+                line: String::from_utf8_lossy(&found.line).into_owned(),
+                spans: found
+                    .spans
+                    .iter()
+                    .map(|span| (span.start, span.end))
+                    .collect(),
+            })
+            .collect();
+        Ok(matches)
+    })
+}
+
+/// The library's errors carry the command line's `Error: ` prefix, which Python's
+/// traceback already says.
+fn value_error(message: &'static str) -> PyErr {
+    PyValueError::new_err(message.trim_start_matches("Error: "))
+}
+
+#[pymodule]
+#[pyo3(name = "yagrep")]
+fn yagrep_python(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Match>()?;
+    module.add_function(wrap_pyfunction!(search, module)?)?;
+    Ok(())
+}
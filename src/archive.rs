@@ -0,0 +1,353 @@
+//! Reading the members of zip and tar archives for `--archive`. Zip archives are read
+//! whole and inflated natively one member at a time; tarballs are streamed, with
+//! `.tar.gz` decompressed through the same `gzip` pipe as `-z`.
+
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+#[derive(Clone, Copy)]
+pub enum Kind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+/// The kind of archive `path` is, judging by its name.
+pub fn kind(path: &Path) -> Option<Kind> {
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+    if name.ends_with(".zip") {
+        Some(Kind::Zip)
+    } else if name.ends_with(".tar") {
+        Some(Kind::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(Kind::TarGz)
+    } else {
+        None
+    }
+}
+
+/// What `visit` is called with: a member's contents, or `None` for a member larger
+/// than the size limit, which isn't extracted.
+type Visit<'a> = dyn FnMut(&str, Option<&mut dyn BufRead>) + 'a;
+
+/// Calls `visit` with the path and contents of every regular file in the archive, in
+/// archive order. Members that can't be decoded, such as encrypted zip entries, are
+/// skipped, and those over `max_size` bytes (`--max-filesize`) are passed without
+/// their contents.
+pub fn members(
+    path: &Path,
+    kind: Kind,
+    max_size: Option<u64>,
+    mut visit: impl FnMut(&str, Option<&mut dyn BufRead>),
+) -> std::io::Result<()> {
+    let max_size = max_size.unwrap_or(u64::MAX);
+    match kind {
+        Kind::Zip => zip_members(&std::fs::read(path)?, max_size, &mut visit),
+        Kind::Tar => tar_members(std::fs::File::open(path)?, max_size, &mut visit),
+        Kind::TarGz => {
+            let gzip = crate::input::decompressor(path).unwrap_or(&["gzip", "-dc"]);
+            let reader = crate::input::Piped::decompress(gzip, path)?;
+            tar_members(reader, max_size, &mut visit)
+        }
+    }
+}
+
+fn invalid(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+fn u16_at(data: &[u8], offset: usize) -> std::io::Result<usize> {
+    let bytes = data
+        .get(offset..offset + 2)
+        .ok_or_else(|| invalid("truncated zip archive"))?;
+    Ok(usize::from(u16::from_le_bytes([bytes[0], bytes[1]])))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> std::io::Result<usize> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .ok_or_else(|| invalid("truncated zip archive"))?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
+
+/// Walks the central directory, the authoritative list of a zip's members.
+fn zip_members(data: &[u8], max_size: u64, visit: &mut Visit) -> std::io::Result<()> {
+    const END_SIGNATURE: &[u8] = b"PK\x05\x06";
+    const ENTRY_SIGNATURE: &[u8] = b"PK\x01\x02";
+    const LOCAL_SIGNATURE: &[u8] = b"PK\x03\x04";
+
+    // The end record is the last 22 bytes, unless an archive comment follows it.
+    let end = (0..data.len().saturating_sub(21))
+        .rev()
+        .take(u16::MAX as usize + 1)
+        .find(|&offset| data[offset..].starts_with(END_SIGNATURE))
+        .ok_or_else(|| invalid("not a zip archive"))?;
+    let entries = u16_at(data, end + 10)?;
+    let mut offset = u32_at(data, end + 16)?;
+
+    for _ in 0..entries {
+        if !data[offset.min(data.len())..].starts_with(ENTRY_SIGNATURE) {
+            return Err(invalid("corrupt zip central directory"));
+        }
+        let flags = u16_at(data, offset + 8)?;
+        let method = u16_at(data, offset + 10)?;
+        let compressed_size = u32_at(data, offset + 20)?;
+        let size = u32_at(data, offset + 24)?;
+        let name_length = u16_at(data, offset + 28)?;
+        let extra_length = u16_at(data, offset + 30)?;
+        let comment_length = u16_at(data, offset + 32)?;
+        let local = u32_at(data, offset + 42)?;
+        let name = data
+            .get(offset + 46..offset + 46 + name_length)
+            .ok_or_else(|| invalid("truncated zip archive"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        offset += 46 + name_length + extra_length + comment_length;
+
+        let encrypted = flags & 1 == 1;
+        if name.ends_with('/') || encrypted {
+            continue;
+        }
+        if size as u64 > max_size {
+            visit(&name, None);
+            continue;
+        }
+        if !data[local.min(data.len())..].starts_with(LOCAL_SIGNATURE) {
+            return Err(invalid("corrupt zip member header"));
+        }
+        let start = local + 30 + u16_at(data, local + 26)? + u16_at(data, local + 28)?;
+        let compressed = data
+            .get(start..start + compressed_size)
+            .ok_or_else(|| invalid("truncated zip archive"))?;
+        let contents = match method {
+            0 => compressed.to_vec(),
+            // The declared size bounds the output, which may lie about it.
+            8 => crate::inflate::inflate(compressed, size)?,
+            // Other methods (bzip2, LZMA, ...) are rare enough to skip.
+            _ => continue,
+        };
+        visit(&name, Some(&mut &contents[..]));
+    }
+    Ok(())
+}
+
+/// Reads ustar and GNU tar streams, including GNU and pax long names.
+fn tar_members(reader: impl Read, max_size: u64, visit: &mut Visit) -> std::io::Result<()> {
+    let mut reader = BufReader::new(reader);
+    let mut long_name = None;
+    loop {
+        let mut header = [0; 512];
+        if reader.read_exact(&mut header).is_err() || header.iter().all(|&byte| byte == 0) {
+            return Ok(());
+        }
+        let size = tar_size(&header[124..136])?;
+        let padded = size
+            .div_ceil(512)
+            .checked_mul(512)
+            .ok_or_else(|| invalid("corrupt tar header"))?;
+        let mut member = (&mut reader).take(size);
+
+        match header[156] {
+            b'0' | b'\0' | b'7' => {
+                let name = long_name.take().unwrap_or_else(|| tar_name(&header));
+                match size > max_size {
+                    true => visit(&name, None),
+                    false => visit(&name, Some(&mut BufReader::new(&mut member))),
+                }
+            }
+            // GNU: the member's contents are the next member's name.
+            b'L' => {
+                let mut name = Vec::new();
+                member.read_to_end(&mut name)?;
+                let name = name.split(|&byte| byte == 0).next().unwrap_or_default();
+                long_name = Some(String::from_utf8_lossy(name).into_owned());
+            }
+            // pax: `length key=value` records, of which only `path` matters here.
+            b'x' => {
+                let mut records = Vec::new();
+                member.read_to_end(&mut records)?;
+                let records = String::from_utf8_lossy(&records);
+                long_name = records
+                    .lines()
+                    .filter_map(|record| record.split_once(' ')?.1.strip_prefix("path="))
+                    .next_back()
+                    .map(str::to_string);
+            }
+            _ => {}
+        }
+        // Skip whatever `visit` didn't read, then the padding to the next header.
+        std::io::copy(&mut member, &mut std::io::sink())?;
+        std::io::copy(&mut (&mut reader).take(padded - size), &mut std::io::sink())?;
+    }
+}
+
+fn tar_name(header: &[u8; 512]) -> String {
+    let field = |range: std::ops::Range<usize>| {
+        let bytes = &header[range];
+        let end = bytes
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(bytes.len());
+        String::from_utf8_lossy(&bytes[..end]).into_owned()
+    };
+    let name = field(0..100);
+    let prefix = match &header[257..262] == b"ustar" {
+        true => field(345..500),
+        false => String::new(),
+    };
+    match prefix.is_empty() {
+        true => name,
+        false => format!("{}/{}", prefix, name),
+    }
+}
+
+/// Parses a size field: octal digits, or big-endian binary when the top bit is set.
+fn tar_size(field: &[u8]) -> std::io::Result<u64> {
+    if field[0] & 0x80 != 0 {
+        let value = field
+            .iter()
+            .skip(1)
+            .try_fold(u64::from(field[0] & 0x7f), |value, &byte| {
+                value.checked_mul(256)?.checked_add(u64::from(byte))
+            });
+        return value.ok_or_else(|| invalid("corrupt tar header"));
+    }
+    let digits = String::from_utf8_lossy(field);
+    let digits = digits.trim_matches(|c: char| c == '\0' || c == ' ');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8).map_err(|_| invalid("corrupt tar header"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tar member header for `name` with the raw `size` field.
+    fn header(name: &str, size: &[u8]) -> Vec<u8> {
+        let mut header = vec![0; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..124 + size.len()].copy_from_slice(size);
+        header[156] = b'0';
+        header
+    }
+
+    /// The members `tar_members` visits in `tar`, with their contents.
+    fn visited(tar: &[u8], max_size: u64) -> std::io::Result<Vec<(String, Option<String>)>> {
+        let mut visited = Vec::new();
+        tar_members(tar, max_size, &mut |name, contents| {
+            let contents = contents.map(|contents| {
+                let mut text = String::new();
+                contents.read_to_string(&mut text).unwrap();
+                text
+            });
+            visited.push((name.to_string(), contents));
+        })?;
+        Ok(visited)
+    }
+
+    #[test]
+    fn reads_tar_members() {
+        let mut tar = header("a.txt", b"00000000003\0");
+        tar.extend(b"foo");
+        tar.resize(1024, 0);
+        tar.extend(header("b.txt", &[0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]));
+        tar.extend(b"hi");
+        tar.resize(2560, 0);
+        let expected = [
+            ("a.txt".to_string(), Some("foo".to_string())),
+            ("b.txt".to_string(), Some("hi".to_string())),
+        ];
+        assert_eq!(visited(&tar, u64::MAX).unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_a_binary_size_past_u64() {
+        let mut size = [0xff; 12];
+        size[0] = 0x80;
+        let mut tar = header("x.txt", &size);
+        tar.resize(2048, 0);
+        let err = visited(&tar, u64::MAX).unwrap_err();
+        assert_eq!(err.to_string(), "corrupt tar header");
+    }
+
+    #[test]
+    fn rejects_a_size_whose_padding_overflows() {
+        let mut size = [0xff; 12];
+        // Base-256 for `u64::MAX`, which doesn't round up to a whole block.
+        size[..4].copy_from_slice(&[0x80, 0, 0, 0]);
+        let mut tar = header("x.txt", &size);
+        tar.resize(2048, 0);
+        let err = visited(&tar, u64::MAX).unwrap_err();
+        assert_eq!(err.to_string(), "corrupt tar header");
+    }
+
+    #[test]
+    fn passes_members_over_the_limit_without_contents() {
+        let mut tar = header("big.txt", b"00000000010\0");
+        tar.extend(b"12345678");
+        tar.resize(1024, 0);
+        tar.extend(header("small.txt", b"00000000001\0"));
+        tar.extend(b"x");
+        tar.resize(2560, 0);
+        let expected = [
+            ("big.txt".to_string(), None),
+            ("small.txt".to_string(), Some("x".to_string())),
+        ];
+        assert_eq!(visited(&tar, 4).unwrap(), expected);
+    }
+
+    #[test]
+    fn zip_members_inflate_no_further_than_their_declared_size() {
+        // `hello hello hello\n`, deflated, in a zip whose directory claims 5 bytes.
+        let deflated = [203, 72, 205, 201, 201, 87, 200, 64, 144, 92, 0];
+        let zip = |size: u32| {
+            let mut zip = b"PK\x03\x04".to_vec();
+            zip.extend([0; 22]);
+            zip.extend(5u16.to_le_bytes());
+            zip.extend([0; 2]);
+            zip.extend(b"x.txt");
+            zip.extend(deflated);
+            let directory = zip.len() as u32;
+            zip.extend(b"PK\x01\x02");
+            zip.extend([0; 4]);
+            zip.extend(0u16.to_le_bytes());
+            zip.extend(8u16.to_le_bytes());
+            zip.extend([0; 8]);
+            zip.extend((deflated.len() as u32).to_le_bytes());
+            zip.extend(size.to_le_bytes());
+            zip.extend(5u16.to_le_bytes());
+            zip.extend([0; 12]);
+            zip.extend(0u32.to_le_bytes());
+            zip.extend(b"x.txt");
+            zip.extend(b"PK\x05\x06");
+            zip.extend([0; 6]);
+            zip.extend(1u16.to_le_bytes());
+            zip.extend([0; 4]);
+            zip.extend(directory.to_le_bytes());
+            zip.extend([0; 2]);
+            zip
+        };
+        let mut found = Vec::new();
+        zip_members(&zip(18), u64::MAX, &mut |name, contents| {
+            let mut text = String::new();
+            contents.unwrap().read_to_string(&mut text).unwrap();
+            found.push((name.to_string(), text));
+        })
+        .unwrap();
+        assert_eq!(
+            found,
+            [("x.txt".to_string(), "hello hello hello\n".to_string())]
+        );
+
+        let err = zip_members(&zip(5), u64::MAX, &mut |_, _| {}).unwrap_err();
+        assert_eq!(err.to_string(), "output exceeds its limit");
+
+        let mut skipped = Vec::new();
+        zip_members(&zip(18), 10, &mut |name, contents| {
+            skipped.push((name.to_string(), contents.is_none()));
+        })
+        .unwrap();
+        assert_eq!(skipped, [("x.txt".to_string(), true)]);
+    }
+}
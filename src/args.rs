@@ -0,0 +1,312 @@
+//! The options `yagrep` accepts, as one table that the parser, the `--help` text and
+//! the man page are built from, and the tokenizer that matches arguments against it: long
+//! flags with `--name value` or `--name=value`, and short flags that combine (`-iw`)
+//! and take their value attached (`-A3`) or separately (`-A 3`).
+
+/// One option.
+pub(crate) struct Arg {
+    pub(crate) long: &'static str,
+    pub(crate) short: Option<char>,
+    /// The name of the value the option takes, shown in help, or `None` for a flag.
+    pub(crate) value: Option<&'static str>,
+    pub(crate) help: &'static str,
+}
+
+const fn flag(long: &'static str, short: Option<char>, help: &'static str) -> Arg {
+    Arg {
+        long,
+        short,
+        value: None,
+        help,
+    }
+}
+
+const fn option(
+    long: &'static str,
+    short: Option<char>,
+    value: &'static str,
+    help: &'static str,
+) -> Arg {
+    Arg {
+        long,
+        short,
+        value: Some(value),
+        help,
+    }
+}
+
+/// Every option, in the order `--help` lists them.
+#[rustfmt::skip]
+pub(crate) const ARGS: &[Arg] = &[
+    option("regexp", Some('e'), "PATTERN", "Search for PATTERN; may be repeated"),
+    option("file", Some('f'), "FILE", "Read patterns from FILE, one per line"),
+    flag("all-match", None, "Select lines that match every pattern, not just one"),
+    option("not", None, "PATTERN", "Don't select lines matching PATTERN; may be repeated"),
+    flag("pattern-labels", None, "Start each line with the patterns it matched"),
+    option("files-matching", None, "PATTERN", "Search only files that PATTERN matches somewhere"),
+    flag("ignore-case", Some('i'), "Match case-insensitively"),
+    flag("smart-case", Some('S'), "Match case-insensitively unless a pattern has uppercase"),
+    flag("case-sensitive", Some('s'), "Match case-sensitively, undoing -i and -S"),
+    flag("fixed-strings", Some('F'), "Treat patterns as literal strings"),
+    flag("word-regexp", Some('w'), "Only match whole words"),
+    flag("line-regexp", Some('x'), "Only match whole lines"),
+    flag("multiline", Some('U'), "Let matches span lines"),
+    flag("multiline-dotall", None, "With -U, let . match newlines"),
+    flag("crlf", None, "Treat CRLF as a line ending, so $ matches before it"),
+    option("engine", None, "ENGINE", "Regex engine: default, pcre2 or auto (PCRE2 if needed)"),
+    flag("fuzzy", None, "Match patterns literally, allowing one typo"),
+    option("fuzzy-distance", None, "NUM", "With --fuzzy, allow NUM typos (implies --fuzzy)"),
+    flag("invert-match", Some('v'), "Select lines that don't match"),
+    option("max-count", Some('m'), "NUM", "Stop after NUM selected lines per file"),
+    option("after-context", Some('A'), "NUM", "Print NUM lines after each match"),
+    option("before-context", Some('B'), "NUM", "Print NUM lines before each match"),
+    option("context", Some('C'), "NUM", "Print NUM lines before and after each match"),
+    flag("passthru", None, "Print every line, highlighting the matches"),
+    flag("no-passthru", None, "Print only selected lines, undoing --passthru"),
+    flag("count", Some('c'), "Print the number of selected lines per file"),
+    flag("count-matches", None, "Print the number of matches per file"),
+    flag("files-with-matches", Some('l'), "Print only the paths of files with a match"),
+    flag("files-without-match", Some('L'), "Print only the paths of files without a match"),
+    flag("only-matching", Some('o'), "Print only the matched parts of lines"),
+    option("group", None, "NAME", "Print only the named group NAME of each match"),
+    flag("quiet", Some('q'), "Print nothing; exit 0 at the first match"),
+    flag("with-filename", None, "Print the path of every matching line"),
+    flag("no-filename", Some('I'), "Never print paths before lines or counts"),
+    flag("line-number", Some('n'), "Print line numbers (the default on a terminal)"),
+    flag("no-line-number", Some('N'), "Don't print line numbers"),
+    option("max-columns", Some('M'), "NUM", "Cut printed lines off after NUM columns"),
+    flag("max-columns-preview", None, "With --max-columns, show the first match of a cut line"),
+    flag("column", None, "Print the column of the first match, and the line number"),
+    flag("byte-offset", Some('b'), "Print the byte offset of each line"),
+    flag("heading", None, "Print each file's path above its matches"),
+    flag("no-heading", None, "Print the path on every matching line"),
+    option("color", None, "WHEN", "Color output: auto, always or never"),
+    option("output", None, "FILE", "Write results to FILE rather than stdout"),
+    flag("no-pager", None, "Don't page results on a terminal through $PAGER"),
+    option("colors", None, "SPEC", "Set colors, e.g. match:fg:red"),
+    flag("json", None, "Print results as JSON Lines"),
+    option("format", None, "FORMAT", "Print results as sarif, csv, tsv, github, patch or a template"),
+    flag("vimgrep", None, "Print path:line:column:text for every match"),
+    flag("null", Some('0'), "Follow paths with a NUL byte"),
+    option("replace", Some('r'), "TEXT", "Print TEXT in place of each match"),
+    flag("write", None, "Write replacements back to the files"),
+    flag("diff", None, "Preview replacements as a unified diff"),
+    flag("interactive", None, "Confirm each replacement (with --write)"),
+    option("backup", None, "SUFFIX", "Keep the original of rewritten files with SUFFIX"),
+    flag("null-data", None, "Read and print records ended by NUL instead of lines"),
+    option("record-separator", None, "BYTE", "Read and print records ended by BYTE, e.g. \\0"),
+    flag("text", Some('a'), "Search binary files as text"),
+    flag("binary", None, "Search binary files, escaping their control bytes"),
+    flag("search-zip", Some('z'), "Search compressed files"),
+    flag("archive", None, "Search the members of zip and tar archives"),
+    option("pre", None, "COMMAND", "Search the output of COMMAND run on each file"),
+    option("pre-glob", None, "GLOB", "Only run --pre on files matching GLOB"),
+    option("encoding", Some('E'), "ENCODING", "Decode files from ENCODING"),
+    flag("mmap", None, "Memory-map large files"),
+    flag("no-mmap", None, "Never memory-map files"),
+    flag("no-index", None, "Read every file, even those the index rules out"),
+    flag("hidden", Some('H'), "Search hidden files and directories"),
+    flag("no-hidden", None, "Skip hidden files and directories, undoing -H"),
+    flag("no-ignore", None, "Don't respect ignore files"),
+    flag("ignore", None, "Respect ignore files, undoing --no-ignore"),
+    flag("no-ignore-vcs", None, "Don't respect .gitignore files"),
+    flag("unrestricted", Some('u'), "Once, --no-ignore; twice, also -H; three times, also -a"),
+    flag("follow", None, "Follow symlinked directories"),
+    flag("no-follow", None, "Don't follow symlinked directories, undoing --follow"),
+    flag("one-file-system", None, "Don't descend into directories on other file systems"),
+    option("devices", Some('D'), "ACTION", "Skip FIFOs, sockets and devices, or read them: skip or read"),
+    flag("git-tracked", None, "Only search files tracked by git"),
+    option("rev", None, "REV", "Search the files of git revision REV, not the working tree"),
+    flag("git-log", None, "Show the first and last commit each file matched in"),
+    option("glob", Some('g'), "GLOB", "Include or, with !, exclude files by GLOB"),
+    option("type", Some('t'), "TYPE", "Only search files of TYPE"),
+    option("type-not", Some('T'), "TYPE", "Don't search files of TYPE"),
+    option("type-add", None, "NAME:GLOB", "Add GLOB to file type NAME"),
+    flag("type-list", None, "List the file types and exit"),
+    option("max-depth", None, "NUM", "Descend at most NUM directories"),
+    option("max-filesize", None, "SIZE", "Skip files and archive members larger than SIZE (K, M or G suffix)"),
+    option("sort", None, "ORDER", "Sort files by ORDER: path, modified, size or none (finish order)"),
+    option("sortr", None, "ORDER", "Like --sort, in reverse"),
+    option("threads", Some('j'), "NUM", "Search with NUM threads"),
+    flag("no-messages", None, "Don't report files that can't be read"),
+    flag("stats", None, "Print totals for the search after its results"),
+    flag("summary", None, "Print the matching files ranked by their number of matches"),
+    flag("summary-chart", None, "Like --summary, with a bar chart"),
+    flag("tally", None, "Print how many times each distinct match (or --group) was found"),
+    flag("watch", None, "Search again whenever a file it searches changes"),
+    flag("no-config", None, "Don't read options from the config file"),
+    flag("help", Some('h'), "Print this help and exit"),
+    flag("generate-man", None, "Print a man page in roff and exit"),
+    flag("version", Some('V'), "Print the version and exit"),
+];
+
+/// One argument of a command line, matched against [`ARGS`].
+pub(crate) enum Token {
+    /// An option; the value is present exactly when it takes one.
+    Option(&'static Arg, Option<String>),
+    Positional(String),
+}
+
+/// Splits `args` into tokens, or returns the message for the first one that isn't
+/// valid. After `--`, every argument is positional.
+pub(crate) fn tokenize(args: impl IntoIterator<Item = String>) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            tokens.extend(args.by_ref().map(Token::Positional));
+            break;
+        }
+        if arg == "-" || !arg.starts_with('-') {
+            tokens.push(Token::Positional(arg));
+            continue;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline_value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            let Some(known) = ARGS.iter().find(|known| known.long == name) else {
+                return Err(format!("Error: Unknown option --{}", name));
+            };
+            let value = match (known.value, inline_value) {
+                (None, None) => None,
+                (None, Some(_)) => return Err(format!("Error: --{} takes no value", name)),
+                (Some(_), Some(value)) => Some(value),
+                (Some(_), None) => Some(args.next().ok_or_else(|| missing(known))?),
+            };
+            tokens.push(Token::Option(known, value));
+            continue;
+        }
+
+        let mut chars = arg[1..].chars();
+        while let Some(c) = chars.next() {
+            let Some(known) = ARGS.iter().find(|known| known.short == Some(c)) else {
+                return Err(format!("Error: Unknown option -{}", c));
+            };
+            if known.value.is_none() {
+                tokens.push(Token::Option(known, None));
+                continue;
+            }
+            // The value is either the rest of this flag group (`-A3`) or the next argument.
+            let rest = chars.as_str();
+            let value = match rest.is_empty() {
+                true => args.next().ok_or_else(|| missing(known))?,
+                false => rest.to_string(),
+            };
+            tokens.push(Token::Option(known, Some(value)));
+            break;
+        }
+    }
+    Ok(tokens)
+}
+
+fn missing(arg: &Arg) -> String {
+    format!("Error: --{} requires a value", arg.long)
+}
+
+/// The `--help` text: usage, then every option with its description.
+pub(crate) fn help(usage: &str) -> String {
+    let names = ARGS
+        .iter()
+        .map(|arg| {
+            let short = arg
+                .short
+                .map_or("    ".to_string(), |c| format!("-{}, ", c));
+            let value = arg
+                .value
+                .map_or(String::new(), |value| format!(" {}", value));
+            format!("{}--{}{}", short, arg.long, value)
+        })
+        .collect::<Vec<_>>();
+    let width = names.iter().map(String::len).max().unwrap_or(0);
+
+    let mut help = format!("{}\n\nOptions:\n", usage);
+    for (name, arg) in names.iter().zip(ARGS) {
+        help.push_str(&format!("  {:width$}  {}\n", name, arg.help, width = width));
+    }
+    help
+}
+
+/// The man page (`--generate-man`), for `man 1 yagrep`.
+pub(crate) fn man(version: &str) -> String {
+    let mut man = format!(
+        ".TH YAGREP 1 \"\" \"{}\" \"User Commands\"\n",
+        roff(version.trim_end())
+    );
+    man.push_str(concat!(
+        ".SH NAME\n",
+        "yagrep \\- search files for lines matching a regular expression\n",
+        ".SH SYNOPSIS\n",
+        ".B yagrep\n",
+        "[\\fIoptions\\fR] \\fIpattern\\fR [\\fIfile\\fR]\n",
+        ".br\n",
+        ".B yagrep\n",
+        "[\\fIoptions\\fR] \\fB\\-e\\fR \\fIpattern\\fR... [\\fIfile\\fR]\n",
+        ".br\n",
+        ".B yagrep index build\\fR|\\fBwatch\n",
+        "[\\fIdirectory\\fR]\n",
+        ".br\n",
+        ".B yagrep serve\n",
+        "[\\fB\\-\\-socket\\fR \\fIpath\\fR]\n",
+        ".SH DESCRIPTION\n",
+        "Prints the lines of \\fIfile\\fR that match \\fIpattern\\fR. A directory is searched\n",
+        "recursively, skipping hidden and ignored files; without a file, or with\n",
+        "\\fB\\-\\fR, standard input is searched.\n",
+        ".PP\n",
+        "With \\fB\\-\\-replace\\fR and no file, standard input is filtered like\n",
+        "\\fBsed s/\\fR\\fIpattern\\fR\\fB/\\fR\\fItext\\fR\\fB/g\\fR: every line is printed, with its matches\n",
+        "replaced.\n",
+        ".PP\n",
+        "\\fByagrep serve\\fR answers JSON\\-RPC 2.0 requests, one per line, on standard input\n",
+        "or on each connection to a Unix socket: \\fBsearch\\fR with \\fBargs\\fR, the arguments\n",
+        "of a command line, and \\fBshutdown\\fR.\n",
+        ".SH OPTIONS\n",
+    ));
+    for arg in ARGS {
+        man.push_str(".TP\n");
+        if let Some(short) = arg.short {
+            man.push_str(&format!("\\fB\\-{}\\fR, ", short));
+        }
+        man.push_str(&format!("\\fB\\-\\-{}\\fR", roff(arg.long)));
+        if let Some(value) = arg.value {
+            man.push_str(&format!(" \\fI{}\\fR", roff(value)));
+        }
+        man.push_str(&format!("\n{}\n", roff(arg.help)));
+    }
+    man.push_str(concat!(
+        ".SH FILES\n",
+        ".TP\n",
+        "\\fI~/.config/yagrep/config\\fR\n",
+        "Default options, one argument per line, or an option and its value separated by a\n",
+        "space, overridden by the command line: a later \\fB\\-s\\fR, \\fB\\-\\-no\\-hidden\\fR and\n",
+        "the like undo what the file turns on. Set\n",
+        "\\fBYAGREP_CONFIG_PATH\\fR to read another file, or to an empty value to read none.\n",
+        ".TP\n",
+        "\\fI.yagrep\\-index\\fR\n",
+        "The trigram index \\fByagrep index build\\fR writes, and \\fByagrep index watch\\fR\n",
+        "keeps up to date. Searches of the directory, or below it, skip reading the files\n",
+        "it shows can't match, unless they have changed since; \\fB\\-\\-no\\-index\\fR reads\n",
+        "them all.\n",
+        ".SH ENVIRONMENT\n",
+        ".TP\n",
+        "\\fBPAGER\\fR\n",
+        "The pager results shown on a terminal are piped into, \\fBless \\-R\\fR when unset.\n",
+        "Set it to an empty value or \\fBcat\\fR, or pass \\fB\\-\\-no\\-pager\\fR, not to page.\n",
+        ".TP\n",
+        "\\fBLESS\\fR\n",
+        "Options for less, \\fBFRX\\fR when unset: results that fit on one screen are\n",
+        "printed without waiting for a key.\n",
+        ".SH EXIT STATUS\n",
+        "0 if a line was selected, 1 if none was, and 2 if an error occurred, including a\n",
+        "file or directory that couldn't be read. A search stopped by Ctrl\\-C prints what it\n",
+        "found and its totals, then exits with 130; a second Ctrl\\-C exits at once.\n",
+    ));
+    man
+}
+
+/// Escapes text for roff: backslashes, and hyphens so they aren't typeset as dashes.
+fn roff(text: &str) -> String {
+    text.replace('\\', "\\e").replace('-', "\\-")
+}
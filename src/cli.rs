@@ -0,0 +1,694 @@
+//! Command-line parsing: turns `yagrep`'s arguments into the [`SearchConfig`] to search
+//! with.
+
+use crate::args::{self, Token};
+use crate::config::{ColorChoice, EngineChoice, FormatChoice, SearchConfig, SortChoice};
+use crate::terminal;
+
+const USAGE: &str = concat!(
+    "Usage: yagrep [options] <pattern> [file]\n",
+    "       yagrep [options] -e <pattern>... [file]\n",
+    "       yagrep index build|watch [directory]\n",
+    "       yagrep serve [--socket <path>]"
+);
+
+const INDEX_USAGE: &str = "Usage: yagrep index build|watch [directory]";
+
+const SERVE_USAGE: &str = "Usage: yagrep serve [--socket <path>]";
+
+const VERSION: &str = concat!("yagrep ", env!("CARGO_PKG_VERSION"), "\n");
+
+const INVALID_CONTEXT: &str = "Error: Invalid context length argument";
+
+const INVALID_MAX_COUNT: &str = "Error: Invalid max count";
+
+const INVALID_THREADS: &str = "Error: Invalid thread count";
+
+const INVALID_MAX_DEPTH: &str = "Error: Invalid max depth";
+
+const INVALID_FUZZY_DISTANCE: &str = "Error: Invalid fuzzy distance";
+
+const INVALID_MAX_COLUMNS: &str = "Error: Invalid max columns";
+
+const INVALID_RECORD_SEPARATOR: &str =
+    "Error: --record-separator must be one character, or \\0, \\t, \\n, \\r or \\xNN";
+
+const INVALID_MAX_FILESIZE: &str = "Error: Invalid max file size";
+
+const INVALID_PATTERN_FILE: &str = "Error: Failed to read pattern file";
+
+const INVALID_COLOR: &str = "Error: --color must be one of auto, always, never";
+
+const INVALID_SORT: &str = "Error: --sort must be one of path, modified, size, none";
+
+const INVALID_DEVICES: &str = "Error: --devices must be one of skip, read";
+
+const INVALID_FORMAT: &str =
+    "Error: --format must be one of sarif, csv, tsv, github, patch or a {placeholder} template";
+
+const JSON_WITH_FORMAT: &str = "Error: --json can't be combined with --format";
+
+const JSON_WITH_OUTPUT_MODE: &str =
+    "Error: --json can't be combined with -c, --count-matches, -l, -L or -o";
+
+const INVALID_ENGINE: &str = "Error: --engine must be one of default, pcre2, auto";
+
+const NO_PCRE2: &str = "Error: --engine pcre2 needs yagrep built with the pcre2 feature";
+
+const WRITE_WITHOUT_REPLACE: &str = "Error: --write requires --replace";
+
+const DIFF_WITHOUT_REPLACE: &str = "Error: --diff requires --replace";
+
+const PATCH_WITHOUT_REPLACE: &str = "Error: --format patch requires --replace";
+
+const WRITE_WITH_REV: &str = "Error: --write can't rewrite the files of --rev";
+
+const WRITE_WITH_GIT_LOG: &str = "Error: --write can't be combined with --git-log";
+
+const INTERACTIVE_WITHOUT_WRITE: &str = "Error: --interactive requires --write";
+
+const WATCH_WITHOUT_PATH: &str = "Error: --watch requires a file or directory";
+
+#[cfg(not(target_arch = "wasm32"))]
+const INVALID_PATH: &str = "Error: Failed to resolve the path against the working directory";
+
+const INVALID_TERMINAL: &str = "Error: Failed to open the terminal for --interactive";
+
+/// What the command line asks for.
+pub enum Command {
+    /// Print the text and exit: the file type table (`--type-list`), `--help`, the man
+    /// page or `--version`.
+    Print(String),
+    /// Write the trigram index of a directory (`yagrep index build`).
+    BuildIndex(std::path::PathBuf),
+    /// Write the trigram index of a directory and keep it up to date (`yagrep index
+    /// watch`).
+    WatchIndex(std::path::PathBuf),
+    /// Answer JSON-RPC searches on stdio, or on the Unix socket given (`yagrep serve`).
+    Serve(Option<std::path::PathBuf>),
+    /// Run the search, and with `--watch` again on every change to `watch`.
+    Search {
+        config: Box<SearchConfig>,
+        watch: Option<std::path::PathBuf>,
+    },
+}
+
+/// Parses `yagrep`'s arguments, `args[0]` being the program name.
+pub fn parse(args: Vec<String>) -> Result<Command, String> {
+    let mut config = SearchConfig::new();
+    let mut positionals = Vec::new();
+    let mut before_context = None;
+    let mut after_context = None;
+    let mut context = 0;
+    let mut pattern_flag = false;
+    // `(name, negated)`, resolved once every `--type-add` is known.
+    let mut selected_types = Vec::new();
+    let mut interactive = false;
+    let mut watch = false;
+    let mut type_list = false;
+    // How many times `-u` was given; each one lifts one more restriction.
+    let mut unrestricted = 0;
+
+    let args = args.into_iter().skip(1).collect::<Vec<_>>();
+    if let [command, rest @ ..] = args.as_slice() {
+        if command == "serve" {
+            return match rest {
+                [] => Ok(Command::Serve(None)),
+                [option, socket] if option == "--socket" => Ok(Command::Serve(Some(socket.into()))),
+                _ => Err(SERVE_USAGE.into()),
+            };
+        }
+    }
+    if let [command, action, rest @ ..] = args.as_slice() {
+        if command == "index" && (action == "build" || action == "watch") {
+            let directory = match rest {
+                [] => ".".into(),
+                [directory] => directory.into(),
+                _ => return Err(INDEX_USAGE.into()),
+            };
+            return Ok(match action.as_str() {
+                "build" => Command::BuildIndex(directory),
+                _ => Command::WatchIndex(directory),
+            });
+        }
+    }
+    // Options from the config file come first, so the command line overrides them.
+    let no_config = args
+        .iter()
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == "--no-config");
+    let mut tokens = match no_config {
+        true => Vec::new(),
+        false => config_file_tokens()?,
+    };
+    tokens.extend(args::tokenize(args)?);
+
+    for token in tokens {
+        let (arg, value) = match token {
+            Token::Positional(positional) => {
+                positionals.push(positional);
+                continue;
+            }
+            Token::Option(arg, value) => (arg, value.unwrap_or_default()),
+        };
+        match arg.long {
+            "after-context" => after_context = Some(parse_number(&value, INVALID_CONTEXT)?),
+            "before-context" => before_context = Some(parse_number(&value, INVALID_CONTEXT)?),
+            "context" => context = parse_number(&value, INVALID_CONTEXT)?,
+            "regexp" => {
+                pattern_flag = true;
+                config.pattern(&value);
+            }
+            "all-match" => {
+                config.all_match(true);
+            }
+            "not" => {
+                config.not(&value);
+            }
+            "pattern-labels" => {
+                config.pattern_labels(true);
+            }
+            "files-matching" => {
+                config.files_matching(&value);
+            }
+            "file" => {
+                pattern_flag = true;
+                for pattern in read_pattern_file(&value)? {
+                    config.pattern(&pattern);
+                }
+            }
+            "output" => {
+                config.output(std::path::Path::new(&value))?;
+            }
+            "color" => {
+                config.color(match value.as_str() {
+                    "auto" => ColorChoice::Auto,
+                    "always" => ColorChoice::Always,
+                    "never" => ColorChoice::Never,
+                    _ => return Err(INVALID_COLOR.into()),
+                });
+            }
+            "glob" => {
+                config.glob(&value)?;
+            }
+            "type" => selected_types.push((value, false)),
+            "type-not" => selected_types.push((value, true)),
+            "type-add" => {
+                config.type_add(&value)?;
+            }
+            "type-list" => type_list = true,
+            "pre" => {
+                config.preprocessor(Some(&value));
+            }
+            "pre-glob" => {
+                config.pre_glob(&value)?;
+            }
+            "encoding" => {
+                config.encoding(&value)?;
+            }
+            "devices" => {
+                config.read_devices(match value.as_str() {
+                    "skip" => false,
+                    "read" => true,
+                    _ => return Err(INVALID_DEVICES.into()),
+                });
+            }
+            "sort" | "sortr" => {
+                config.sort(match value.as_str() {
+                    "path" => SortChoice::Path,
+                    "modified" => SortChoice::Modified,
+                    "size" => SortChoice::Size,
+                    "none" => SortChoice::Unordered,
+                    _ => return Err(INVALID_SORT.into()),
+                });
+                config.sort_reverse(arg.long == "sortr");
+            }
+            "replace" => {
+                config.replacement(Some(&value));
+            }
+            "colors" => {
+                config.colors(&value)?;
+            }
+            "threads" => {
+                config.threads(parse_number(&value, INVALID_THREADS)?);
+            }
+            "max-count" => {
+                config.max_count(Some(parse_number(&value, INVALID_MAX_COUNT)?));
+            }
+            "max-depth" => {
+                config.max_depth(Some(parse_number(&value, INVALID_MAX_DEPTH)?));
+            }
+            "null-data" => {
+                config.record_separator(0);
+            }
+            "record-separator" => {
+                config.record_separator(parse_separator(&value)?);
+            }
+            "max-columns" => {
+                config.max_columns(Some(parse_number(&value, INVALID_MAX_COLUMNS)?));
+            }
+            "max-columns-preview" => {
+                config.max_columns_preview(true);
+            }
+            "max-filesize" => {
+                config.max_filesize(Some(parse_size(&value)?));
+            }
+            "backup" => {
+                config.backup(Some(&value));
+            }
+            // Whichever of `-i` and `-S` comes last wins.
+            "ignore-case" => {
+                config.case_insensitive(true).smart_case(false);
+            }
+            "smart-case" => {
+                config.smart_case(true).case_insensitive(false);
+            }
+            "case-sensitive" => {
+                config.case_insensitive(false).smart_case(false);
+            }
+            "multiline" => {
+                config.multiline(true);
+            }
+            "multiline-dotall" => {
+                config.multiline_dotall(true);
+            }
+            "crlf" => {
+                config.crlf(true);
+            }
+            "engine" => {
+                config.engine(match value.as_str() {
+                    "default" => EngineChoice::Default,
+                    "pcre2" if cfg!(feature = "pcre2") => EngineChoice::Pcre2,
+                    "pcre2" => return Err(NO_PCRE2.into()),
+                    "auto" => EngineChoice::Auto,
+                    _ => return Err(INVALID_ENGINE.into()),
+                });
+            }
+            "invert-match" => {
+                config.invert_match(true);
+            }
+            "passthru" => {
+                config.passthru(true);
+            }
+            "no-passthru" => {
+                config.passthru(false);
+            }
+            "count" => {
+                config.count(true);
+            }
+            "count-matches" => {
+                config.count_matches(true);
+            }
+            "files-with-matches" => {
+                config.files_with_matches(true);
+            }
+            "files-without-match" => {
+                config.files_without_match(true);
+            }
+            "only-matching" => {
+                config.only_matching(true);
+            }
+            "group" => {
+                config.group(&value);
+            }
+            "quiet" => {
+                config.quiet(true);
+            }
+            "fuzzy" => {
+                // One edit unless `--fuzzy-distance` already said how many.
+                config.fuzzy(Some(config.fuzzy.unwrap_or(1)));
+            }
+            "fuzzy-distance" => {
+                config.fuzzy(Some(parse_number(&value, INVALID_FUZZY_DISTANCE)?));
+            }
+            "word-regexp" => {
+                config.word_regexp(true);
+            }
+            "line-regexp" => {
+                config.line_regexp(true);
+            }
+            "fixed-strings" => {
+                config.fixed_strings(true);
+            }
+            "column" => {
+                config.column(true);
+            }
+            "byte-offset" => {
+                config.byte_offset(true);
+            }
+            "json" => {
+                config.json(true);
+            }
+            "format" => match value.as_str() {
+                "sarif" => {
+                    config.format(Some(FormatChoice::Sarif));
+                }
+                "csv" => {
+                    config.format(Some(FormatChoice::Csv));
+                }
+                "tsv" => {
+                    config.format(Some(FormatChoice::Tsv));
+                }
+                "github" => {
+                    config.format(Some(FormatChoice::Github));
+                }
+                "patch" => {
+                    config.format(Some(FormatChoice::Patch));
+                }
+                template if template.contains('{') => {
+                    config.template(template)?;
+                }
+                _ => return Err(INVALID_FORMAT.into()),
+            },
+            "vimgrep" => {
+                config.vimgrep(true);
+            }
+            "null" => {
+                config.null(true);
+            }
+            "hidden" => {
+                config.hidden(true);
+            }
+            "no-hidden" => {
+                config.hidden(false);
+            }
+            "no-ignore" => {
+                config.respect_ignore(false);
+            }
+            "ignore" => {
+                config.respect_ignore(true);
+            }
+            "no-ignore-vcs" => {
+                config.respect_gitignore(false);
+            }
+            "unrestricted" => {
+                unrestricted += 1;
+                match unrestricted {
+                    1 => config.respect_ignore(false),
+                    2 => config.hidden(true),
+                    _ => config.text(true),
+                };
+            }
+            "write" => {
+                config.write(true);
+            }
+            "diff" => {
+                config.diff(true);
+            }
+            "interactive" => interactive = true,
+            "watch" => watch = true,
+            "stats" => {
+                config.stats(true);
+            }
+            "summary" => {
+                config.summary(true);
+            }
+            "summary-chart" => {
+                config.summary(true).summary_chart(true);
+            }
+            "tally" => {
+                config.tally(true);
+            }
+            "no-messages" => {
+                config.messages(false);
+            }
+            "heading" => {
+                config.heading(Some(true));
+            }
+            "no-heading" => {
+                config.heading(Some(false));
+            }
+            "with-filename" => {
+                config.filename(Some(true));
+            }
+            "no-filename" => {
+                config.filename(Some(false));
+            }
+            "line-number" => {
+                config.line_number(Some(true));
+            }
+            "no-line-number" => {
+                config.line_number(Some(false));
+            }
+            "mmap" => {
+                config.mmap(Some(true));
+            }
+            "no-mmap" => {
+                config.mmap(Some(false));
+            }
+            "no-index" => {
+                config.index(false);
+            }
+            "text" => {
+                config.text(true);
+            }
+            "binary" => {
+                config.binary(true);
+            }
+            "search-zip" => {
+                config.search_zip(true);
+            }
+            "no-pager" => {
+                config.pager(false);
+            }
+            "follow" => {
+                config.follow(true);
+            }
+            "no-follow" => {
+                config.follow(false);
+            }
+            "one-file-system" => {
+                config.one_file_system(true);
+            }
+            "git-tracked" => {
+                config.git_tracked(true);
+            }
+            "rev" => {
+                config.revision(Some(&value));
+            }
+            "git-log" => {
+                config.git_log(true);
+            }
+            "archive" => {
+                config.archive(true);
+            }
+            "no-config" => {}
+            "help" => return Ok(Command::Print(args::help(USAGE))),
+            "generate-man" => return Ok(Command::Print(args::man(VERSION))),
+            "version" => return Ok(Command::Print(VERSION.to_string())),
+            _ => unreachable!("--{} is in the table but not handled", arg.long),
+        }
+    }
+
+    // Listed once every `--type-add`, before or after it, has been applied.
+    if type_list {
+        return Ok(Command::Print(config.types.list()));
+    }
+    for (name, negated) in &selected_types {
+        match negated {
+            true => config.negate_type(name)?,
+            false => config.select_type(name)?,
+        };
+    }
+    config
+        .before_context(before_context.unwrap_or(context))
+        .after_context(after_context.unwrap_or(context));
+
+    let mut positionals = positionals.into_iter();
+    // Without `-e` or `-f`, the first positional argument is the pattern.
+    if !pattern_flag {
+        config.pattern(&positionals.next().ok_or(USAGE)?);
+    }
+    if let Some(path) = positionals.next().filter(|path| path != "-") {
+        config.path(get_full_path(&path)?);
+    }
+    if config.json && config.format.is_some() {
+        return Err(JSON_WITH_FORMAT.into());
+    }
+    let other_output = config.count
+        || config.count_matches
+        || config.files_with_matches
+        || config.files_without_match
+        || config.only_matching;
+    if config.json && other_output {
+        return Err(JSON_WITH_OUTPUT_MODE.into());
+    }
+    if config.write && config.replacement.is_none() {
+        return Err(WRITE_WITHOUT_REPLACE.into());
+    }
+    if config.write && config.git_log {
+        return Err(WRITE_WITH_GIT_LOG.into());
+    }
+    if config.write && config.revision.is_some() {
+        return Err(WRITE_WITH_REV.into());
+    }
+    if config.diff && config.replacement.is_none() {
+        return Err(DIFF_WITHOUT_REPLACE.into());
+    }
+    if config.format == Some(FormatChoice::Patch) && config.replacement.is_none() {
+        return Err(PATCH_WITHOUT_REPLACE.into());
+    }
+    // Replacing in standard input filters it like `sed s/PATTERN/TEXT/`: every line is
+    // printed, replaced where it matches, unless only some output was asked for.
+    let whole_lines = !(config.only_matching
+        || config.invert_match
+        || config.count
+        || config.count_matches
+        || config.files_with_matches
+        || config.files_without_match
+        || config.quiet
+        || config.json
+        || config.vimgrep
+        || config.format.is_some()
+        || config.diff
+        || config.summary
+        || config.tally);
+    let stdin = config.path.is_none() && config.revision.is_none() && !config.git_log;
+    if config.replacement.is_some() && stdin && whole_lines {
+        config.passthru(true);
+    }
+    let watch = match watch {
+        true => Some(config.path.clone().ok_or(WATCH_WITHOUT_PATH)?),
+        false => None,
+    };
+    if interactive {
+        if !config.write {
+            return Err(INTERACTIVE_WITHOUT_WRITE.into());
+        }
+        let prompter = terminal::Prompter::open().map_err(|_| INVALID_TERMINAL)?;
+        config.prompter = std::sync::Mutex::new(Some(prompter));
+        // Prompts must come one file at a time.
+        config.threads(1);
+    }
+
+    Ok(Command::Search {
+        config: Box::new(config),
+        watch,
+    })
+}
+
+/// Reads the options in the config file: `$YAGREP_CONFIG_PATH`, or else
+/// `$XDG_CONFIG_HOME/yagrep/config` (`~/.config/yagrep/config`) if it exists. The file
+/// holds one argument per line, written as on the command line, or an option and its
+/// value separated by a space (`--max-columns 150`); blank lines and lines starting with
+/// `#` are skipped.
+fn config_file_tokens() -> Result<Vec<Token>, String> {
+    let explicit = std::env::var_os("YAGREP_CONFIG_PATH");
+    let path = match &explicit {
+        // An empty override turns the config file off.
+        Some(path) if path.is_empty() => return Ok(Vec::new()),
+        Some(path) => std::path::PathBuf::from(path),
+        None => {
+            let config = std::env::var_os("XDG_CONFIG_HOME")
+                .map(std::path::PathBuf::from)
+                .filter(|path| path.is_absolute())
+                .or_else(|| {
+                    std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".config"))
+                });
+            match config {
+                Some(config) => config.join("yagrep").join("config"),
+                None => return Ok(Vec::new()),
+            }
+        }
+    };
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_) if explicit.is_none() => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(format!(
+                "Error: Failed to read config file {}: {}",
+                path.display(),
+                err
+            ));
+        }
+    };
+
+    let lines = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .flat_map(split_option_line);
+    let in_file = |err: String| format!("{} in config file {}", err, path.display());
+    let tokens = args::tokenize(lines).map_err(in_file)?;
+    if let Some(Token::Positional(positional)) = tokens
+        .iter()
+        .find(|token| matches!(token, Token::Positional(_)))
+    {
+        return Err(in_file(format!(
+            "Error: Unexpected argument {}",
+            positional
+        )));
+    }
+    Ok(tokens)
+}
+
+/// A config file line as the arguments it stands for: `--name value` and `-n value` are
+/// two, unless an `=` joins the value to the name already.
+fn split_option_line(line: &str) -> Vec<String> {
+    match line.split_once(' ') {
+        Some((name, value)) if name.starts_with('-') && !name.contains('=') => {
+            vec![name.to_string(), value.trim_start().to_string()]
+        }
+        _ => vec![line.to_string()],
+    }
+}
+
+fn parse_number(value: &str, error: &'static str) -> Result<usize, &'static str> {
+    value.parse().map_err(|_| error)
+}
+
+/// Parses a `--max-filesize` value: a byte count with an optional `K`, `M` or `G`
+/// suffix.
+fn parse_size(value: &str) -> Result<u64, &'static str> {
+    let (digits, unit) = match value.char_indices().last() {
+        Some((index, suffix)) if suffix.is_ascii_alphabetic() => {
+            let unit = match suffix.to_ascii_uppercase() {
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                _ => return Err(INVALID_MAX_FILESIZE),
+            };
+            (&value[..index], unit)
+        }
+        _ => (value, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|size| size.checked_mul(unit))
+        .ok_or(INVALID_MAX_FILESIZE)
+}
+
+/// Parses a `--record-separator`: a single ASCII character, or an escape for one.
+fn parse_separator(value: &str) -> Result<u8, &'static str> {
+    match value.as_bytes() {
+        [byte] if byte.is_ascii() => Ok(*byte),
+        b"\\0" => Ok(0),
+        b"\\t" => Ok(b'\t'),
+        b"\\n" => Ok(b'\n'),
+        b"\\r" => Ok(b'\r'),
+        [b'\\', b'x', digits @ ..] => std::str::from_utf8(digits)
+            .ok()
+            .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+            .ok_or(INVALID_RECORD_SEPARATOR),
+        _ => Err(INVALID_RECORD_SEPARATOR),
+    }
+}
+
+/// Reads newline-separated patterns for `-f`. As with grep, an empty line matches everything.
+fn read_pattern_file(path: &str) -> Result<Vec<String>, &'static str> {
+    let contents = std::fs::read_to_string(path).map_err(|_| INVALID_PATTERN_FILE)?;
+    Ok(contents.lines().map(str::to_string).collect())
+}
+
+/// `path` made absolute against the working directory, with `.` segments dropped. On
+/// Windows, a drive-relative path like `C:src` is resolved against that drive's working
+/// directory, one like `\src` against the current drive, and `/` separators become `\`;
+/// UNC paths are kept. On wasm32 there is no working directory, and the path is kept
+/// as given, to name a file of a `MemoryFs`.
+fn get_full_path(path: &str) -> Result<std::path::PathBuf, &'static str> {
+    #[cfg(target_arch = "wasm32")]
+    return Ok(std::path::PathBuf::from(path));
+    #[cfg(not(target_arch = "wasm32"))]
+    std::path::absolute(path).map_err(|_| INVALID_PATH)
+}
@@ -0,0 +1,1006 @@
+//! [`SearchConfig`], every option a search runs with. The command line builds one from
+//! its flags and library users build one directly; either way it is compiled into a
+//! [`Searcher`](crate::Searcher).
+
+use std::io::{BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    console, encoding, glob, interrupt, mmap, stats, summary, tally, template, terminal, theme,
+    types, walker,
+};
+
+pub(crate) const INVALID_ENCODING: &str =
+    "Error: --encoding must be one of auto, utf-8, utf-16le, utf-16be, latin1";
+
+const INVALID_GLOB: &str = "Error: Invalid glob";
+
+const INVALID_OUTPUT: &str = "Error: Failed to create the --output file";
+
+const INVALID_TEMPLATE: &str = "Error: Invalid --format template; placeholders are written {name}";
+
+const INVALID_TYPE: &str = "Error: Unknown file type; see --type-list";
+
+const INVALID_TYPE_DEFINITION: &str = "Error: --type-add must be name:glob[,glob...]";
+
+/// When to emit ANSI colors (`--color`).
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    /// Color only when stdout is a terminal and `NO_COLOR` is unset.
+    Auto,
+    Always,
+    Never,
+}
+
+/// The order in which a directory's results are printed (`--sort`).
+#[derive(Clone, Copy, PartialEq)]
+pub enum SortChoice {
+    /// The order a sequential walk would visit files in, whatever the thread count.
+    Walk,
+    /// Each file as soon as it has been searched (`--sort none`).
+    Unordered,
+    /// By file name within each directory, as `sort` orders bytes.
+    Path,
+    /// By modification time within each directory, oldest first.
+    Modified,
+    /// By size within each directory, smallest first.
+    Size,
+}
+
+/// The regex engine patterns are compiled with (`--engine`).
+#[derive(Clone, Copy, PartialEq)]
+pub enum EngineChoice {
+    /// The `regex` crate.
+    Default,
+    /// PCRE2, for look-around and backreferences; needs the `pcre2` feature.
+    Pcre2,
+    /// The default engine, or PCRE2 for patterns it can't compile.
+    Auto,
+}
+
+/// A structured output format (`--format`).
+#[derive(Clone, Copy, PartialEq)]
+pub enum FormatChoice {
+    /// One SARIF 2.1.0 document, for code scanning.
+    Sarif,
+    /// Comma-separated values with a header row: path, line, column, match and line.
+    Csv,
+    /// The same fields separated by tabs.
+    Tsv,
+    /// GitHub Actions `::warning` commands, shown as annotations on pull requests.
+    Github,
+    /// A line per match from the template given to `SearchConfig::template`.
+    Template,
+    /// One unified diff of what `--replace` would change in every file, with `git`'s
+    /// headers and paths relative to the working directory, for `git apply`.
+    Patch,
+}
+
+pub struct SearchConfig {
+    /// Every pattern to search for; a line is selected when any of them matches.
+    pub(crate) patterns: Vec<String>,
+    /// Select a line only when every pattern matches it (`--all-match`).
+    pub(crate) all_match: bool,
+    /// Start each selected line with the patterns it matched (`--pattern-labels`).
+    pub(crate) pattern_labels: bool,
+    /// Patterns that rule a line out, however the others match (`--not`).
+    pub(crate) not_patterns: Vec<String>,
+    /// Search only the files this pattern matches somewhere (`--files-matching`).
+    pub(crate) files_matching: Option<String>,
+    /// `None` when searching standard input.
+    pub(crate) path: Option<PathBuf>,
+    pub(crate) case_insensitive: bool,
+    /// Match case-insensitively unless a pattern has an uppercase letter (`-S`).
+    pub(crate) smart_case: bool,
+    pub(crate) fixed_strings: bool,
+    pub(crate) word_regexp: bool,
+    pub(crate) line_regexp: bool,
+    /// Let matches span lines (`-U`): the input is searched as a whole instead of a line
+    /// at a time.
+    pub(crate) multiline: bool,
+    /// With `multiline`, let `.` match newlines too.
+    pub(crate) multiline_dotall: bool,
+    /// Treat `\r\n` as a line ending (`--crlf`): `$` matches before it and the `\r` is
+    /// never printed.
+    pub(crate) crlf: bool,
+    pub(crate) engine: EngineChoice,
+    /// Match approximately, within this many edits (`--fuzzy`); the patterns are taken
+    /// literally and `engine` doesn't apply.
+    pub(crate) fuzzy: Option<usize>,
+    pub(crate) invert_match: bool,
+    /// Number of lines to print before each match (`-B`/`-C`).
+    pub(crate) before_context: usize,
+    /// Number of lines to print after each match (`-A`/`-C`).
+    pub(crate) after_context: usize,
+    /// Print every line, the unselected ones as context (`--passthru`).
+    pub(crate) passthru: bool,
+    /// Stop searching a file after this many selected lines (`-m`).
+    pub(crate) max_count: Option<usize>,
+    pub(crate) count: bool,
+    pub(crate) count_matches: bool,
+    pub(crate) files_with_matches: bool,
+    pub(crate) files_without_match: bool,
+    pub(crate) only_matching: bool,
+    /// The named group `-o` prints in place of the whole match (`--group`).
+    pub(crate) group: Option<String>,
+    pub(crate) quiet: bool,
+    pub(crate) column: bool,
+    pub(crate) byte_offset: bool,
+    pub(crate) json: bool,
+    pub(crate) vimgrep: bool,
+    pub(crate) format: Option<FormatChoice>,
+    /// The template of `FormatChoice::Template` (`--format TEMPLATE`).
+    pub(crate) template: Option<template::Template>,
+    /// Whether a result of `format` has been written yet, so the next is preceded by a
+    /// separator.
+    pub(crate) results_emitted: std::sync::atomic::AtomicBool,
+    pub(crate) null: bool,
+    /// `--heading`/`--no-heading`; `None` groups by file only on a terminal.
+    pub(crate) heading: Option<bool>,
+    /// `--with-filename`/`--no-filename`; `None` names files only when there can be
+    /// several.
+    pub(crate) filename: Option<bool>,
+    /// `-n`/`-N`; `None` numbers lines only on a terminal.
+    pub(crate) line_number: Option<bool>,
+    /// What ends a line of input and of the printed lines: `\n`, or NUL with
+    /// `--null-data` (`--record-separator`).
+    pub(crate) record_separator: u8,
+    /// Cut printed lines off after this many columns (`--max-columns`).
+    pub(crate) max_columns: Option<usize>,
+    /// Show the first match of a cut line rather than its start (`--max-columns-preview`).
+    pub(crate) max_columns_preview: bool,
+    pub(crate) color: ColorChoice,
+    pub(crate) theme: theme::Theme,
+    /// Template substituted for every match in printed lines (`-r`), with `$1`/`${name}`
+    /// capture references.
+    pub(crate) replacement: Option<String>,
+    /// Apply the replacement to the files themselves (`--write`).
+    pub(crate) write: bool,
+    /// Print the replacement as a unified diff instead (`--diff`).
+    pub(crate) diff: bool,
+    /// With `--write`, keep each original file next to it with this suffix appended.
+    pub(crate) backup: Option<String>,
+    /// Search binary files as text (`-a`).
+    pub(crate) text: bool,
+    /// Print the matching lines of binary files, escaped (`--binary`).
+    pub(crate) binary: bool,
+    pub(crate) search_zip: bool,
+    pub(crate) archive: bool,
+    /// Command that every file is piped through before searching (`--pre`).
+    pub(crate) preprocessor: Option<String>,
+    /// Limits `--pre` to files whose path matches one of these (`--pre-glob`).
+    pub(crate) pre_globs: Vec<regex::Regex>,
+    /// Decode input as this instead of sniffing a byte order mark (`-E`).
+    pub(crate) encoding: Option<encoding::Encoding>,
+    /// `--mmap`/`--no-mmap`; `None` maps only large files.
+    pub(crate) mmap: Option<bool>,
+    /// Consult the `.yagrep-index` of the searched directory (`--no-index` turns it off).
+    pub(crate) index: bool,
+    /// Search hidden files and directories (`-H`).
+    pub(crate) hidden: bool,
+    /// Honor `.ignore` and, unless `respect_gitignore` is off, `.gitignore` files.
+    pub(crate) respect_ignore: bool,
+    pub(crate) respect_gitignore: bool,
+    pub(crate) follow: bool,
+    /// Stay on the file system the walk started on (`--one-file-system`).
+    pub(crate) one_file_system: bool,
+    /// Search FIFOs, sockets and device nodes rather than skip them (`--devices read`).
+    pub(crate) read_devices: bool,
+    /// Search the files of this git revision instead of the working tree (`--rev`).
+    pub(crate) revision: Option<String>,
+    /// Report when the pattern entered and left each file in git history (`--git-log`).
+    pub(crate) git_log: bool,
+    /// Only walk files git tracks (`--git-tracked`).
+    pub(crate) git_tracked: bool,
+    /// Include and exclude filters for the directory walk (`-g`).
+    pub(crate) globs: glob::Globs,
+    /// File type selections for the directory walk (`-t`, `-T`).
+    pub(crate) types: types::Types,
+    /// Descend at most this many directories below the searched one (`--max-depth`).
+    pub(crate) max_depth: Option<usize>,
+    /// Skip files larger than this many bytes in a directory walk (`--max-filesize`).
+    pub(crate) max_filesize: Option<u64>,
+    pub(crate) sort: SortChoice,
+    /// Reverse the `sort` order (`--sortr`).
+    pub(crate) sort_reverse: bool,
+    /// Number of files searched concurrently in a directory walk; 0 picks one per CPU.
+    pub(crate) threads: usize,
+    /// Asks before each replacement with `--write --interactive`. Only the command line
+    /// opens one, since it needs the controlling terminal.
+    pub(crate) prompter: std::sync::Mutex<Option<terminal::Prompter>>,
+    /// Report unreadable files and other per-file problems on stderr; `--no-messages`
+    /// turns this off.
+    pub(crate) messages: bool,
+    /// Problems reported during the current run, which make it exit with an error.
+    pub(crate) errors: std::sync::atomic::AtomicUsize,
+    /// Whether the output was closed during the current run, as by `head` exiting, which
+    /// ends the search: nothing more could be printed.
+    pub(crate) closed: std::sync::atomic::AtomicBool,
+    /// Set while `--watch` walks the tree between runs; what it can't read is left for
+    /// the next run to report.
+    pub(crate) muted: std::sync::atomic::AtomicBool,
+    /// Print run totals at the end (`--stats`).
+    pub(crate) stats: bool,
+    /// Run totals, for `--stats` and the final `--json` summary event.
+    pub(crate) totals: std::sync::Mutex<stats::Stats>,
+    /// Print the files ranked by their matches instead of the results (`--summary`).
+    pub(crate) summary: bool,
+    /// Draw a bar for each file in the summary (`--summary-chart`).
+    pub(crate) summary_chart: bool,
+    /// The files matched so far, for `--summary`.
+    pub(crate) summaries: std::sync::Mutex<summary::Summary>,
+    /// Print how often each distinct match was found instead of the results (`--tally`).
+    pub(crate) tally: bool,
+    /// The matches counted so far, for `--tally`.
+    pub(crate) tallies: std::sync::Mutex<tally::Tally>,
+    /// Shared by every search thread; each file's output is written in one piece so
+    /// concurrent results never interleave.
+    pub(crate) writer: std::sync::Mutex<Box<dyn Write + Send>>,
+    /// The file `writer` writes to in place of stdout (`--output`).
+    pub(crate) output: Option<PathBuf>,
+    /// Its identity where the platform has one, so a walk never searches its own results.
+    pub(crate) output_id: Option<(u64, u64)>,
+    /// Page results shown on a terminal; `--no-pager` turns this off.
+    pub(crate) pager: bool,
+}
+
+impl Default for SearchConfig {
+    fn default() -> SearchConfig {
+        SearchConfig {
+            patterns: Vec::new(),
+            all_match: false,
+            pattern_labels: false,
+            not_patterns: Vec::new(),
+            files_matching: None,
+            path: None,
+            case_insensitive: false,
+            smart_case: false,
+            fixed_strings: false,
+            word_regexp: false,
+            line_regexp: false,
+            multiline: false,
+            multiline_dotall: false,
+            crlf: false,
+            engine: EngineChoice::Default,
+            fuzzy: None,
+            invert_match: false,
+            before_context: 0,
+            after_context: 0,
+            passthru: false,
+            max_count: None,
+            count: false,
+            count_matches: false,
+            files_with_matches: false,
+            files_without_match: false,
+            only_matching: false,
+            group: None,
+            quiet: false,
+            column: false,
+            byte_offset: false,
+            json: false,
+            vimgrep: false,
+            format: None,
+            template: None,
+            results_emitted: std::sync::atomic::AtomicBool::new(false),
+            null: false,
+            heading: None,
+            filename: None,
+            line_number: None,
+            record_separator: b'\n',
+            max_columns: None,
+            max_columns_preview: false,
+            color: ColorChoice::Auto,
+            theme: theme::Theme::default(),
+            replacement: None,
+            write: false,
+            diff: false,
+            backup: None,
+            text: false,
+            binary: false,
+            search_zip: false,
+            archive: false,
+            preprocessor: None,
+            pre_globs: Vec::new(),
+            encoding: None,
+            mmap: None,
+            index: true,
+            hidden: false,
+            respect_ignore: true,
+            respect_gitignore: true,
+            follow: false,
+            one_file_system: false,
+            read_devices: false,
+            revision: None,
+            git_log: false,
+            git_tracked: false,
+            globs: glob::Globs::default(),
+            types: types::Types::new(),
+            max_depth: None,
+            max_filesize: None,
+            sort: SortChoice::Walk,
+            sort_reverse: false,
+            threads: 0,
+            prompter: std::sync::Mutex::new(None),
+            messages: true,
+            errors: std::sync::atomic::AtomicUsize::new(0),
+            closed: std::sync::atomic::AtomicBool::new(false),
+            muted: std::sync::atomic::AtomicBool::new(false),
+            stats: false,
+            totals: std::sync::Mutex::new(stats::Stats::default()),
+            summary: false,
+            summary_chart: false,
+            summaries: std::sync::Mutex::new(summary::Summary::default()),
+            tally: false,
+            tallies: std::sync::Mutex::new(tally::Tally::default()),
+            writer: std::sync::Mutex::new(Box::new(BufWriter::new(std::io::stdout()))),
+            output: None,
+            output_id: None,
+            pager: true,
+        }
+    }
+}
+
+/// Builder methods. Each one sets the option of the same name and returns the config
+/// so calls can be chained; those taking text that may not parse return a `Result`.
+impl SearchConfig {
+    /// A config that searches standard input for nothing yet, with every option off.
+    pub fn new() -> SearchConfig {
+        SearchConfig::default()
+    }
+
+    /// Adds a pattern; a line is selected when any pattern matches it.
+    pub fn pattern(&mut self, pattern: &str) -> &mut SearchConfig {
+        self.patterns.push(pattern.to_string());
+        self
+    }
+
+    /// Selects a line only when every pattern matches it, rather than any.
+    pub fn all_match(&mut self, yes: bool) -> &mut SearchConfig {
+        self.all_match = yes;
+        self
+    }
+
+    /// Starts each selected line with `[PATTERN] `, naming the patterns it matched, to
+    /// tell apart the results of a search for several.
+    pub fn pattern_labels(&mut self, yes: bool) -> &mut SearchConfig {
+        self.pattern_labels = yes;
+        self
+    }
+
+    /// Adds a pattern that rules out every line it matches. Like the others, it's
+    /// affected by `-i`, `-F`, `-w` and `-x`, but it's never highlighted.
+    pub fn not(&mut self, pattern: &str) -> &mut SearchConfig {
+        self.not_patterns.push(pattern.to_string());
+        self
+    }
+
+    /// Searches only the files `pattern` matches somewhere, as if the files had been
+    /// listed with `-l` first. It's affected by `-i`, `-F`, `-w` and `-x` like the
+    /// patterns searched for.
+    pub fn files_matching(&mut self, pattern: &str) -> &mut SearchConfig {
+        self.files_matching = Some(pattern.to_string());
+        self
+    }
+
+    /// The file or directory to search; without one, standard input is searched.
+    pub fn path(&mut self, path: impl AsRef<Path>) -> &mut SearchConfig {
+        self.path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn case_insensitive(&mut self, yes: bool) -> &mut SearchConfig {
+        self.case_insensitive = yes;
+        self
+    }
+
+    /// Matches case-insensitively when no pattern contains an uppercase letter;
+    /// `case_insensitive` takes precedence.
+    pub fn smart_case(&mut self, yes: bool) -> &mut SearchConfig {
+        self.smart_case = yes;
+        self
+    }
+
+    /// Matches the patterns literally rather than as regexes.
+    pub fn fixed_strings(&mut self, yes: bool) -> &mut SearchConfig {
+        self.fixed_strings = yes;
+        self
+    }
+
+    pub fn word_regexp(&mut self, yes: bool) -> &mut SearchConfig {
+        self.word_regexp = yes;
+        self
+    }
+
+    pub fn line_regexp(&mut self, yes: bool) -> &mut SearchConfig {
+        self.line_regexp = yes;
+        self
+    }
+
+    pub fn multiline(&mut self, yes: bool) -> &mut SearchConfig {
+        self.multiline = yes;
+        self
+    }
+
+    pub fn multiline_dotall(&mut self, yes: bool) -> &mut SearchConfig {
+        self.multiline_dotall = yes;
+        self
+    }
+
+    /// Takes `\r\n` for a line ending, for files written on Windows. Without it a
+    /// carriage return is just another byte, which `$` or `-x` don't match through.
+    pub fn crlf(&mut self, yes: bool) -> &mut SearchConfig {
+        self.crlf = yes;
+        self
+    }
+
+    pub fn engine(&mut self, engine: EngineChoice) -> &mut SearchConfig {
+        self.engine = engine;
+        self
+    }
+
+    /// Matches text within `distance` edits of a pattern, such as `receive` for
+    /// `recieve`: a character inserted, deleted or substituted, or two adjacent ones
+    /// swapped. `None` matches exactly.
+    pub fn fuzzy(&mut self, distance: Option<usize>) -> &mut SearchConfig {
+        self.fuzzy = distance;
+        self
+    }
+
+    pub fn invert_match(&mut self, yes: bool) -> &mut SearchConfig {
+        self.invert_match = yes;
+        self
+    }
+
+    pub fn before_context(&mut self, lines: usize) -> &mut SearchConfig {
+        self.before_context = lines;
+        self
+    }
+
+    pub fn after_context(&mut self, lines: usize) -> &mut SearchConfig {
+        self.after_context = lines;
+        self
+    }
+
+    /// Prints every line of the input, with the selected ones highlighted and the rest
+    /// as context. Reading stdin, each line is written as soon as it's read, so the
+    /// output of a running program can be colorized as it arrives.
+    pub fn passthru(&mut self, yes: bool) -> &mut SearchConfig {
+        self.passthru = yes;
+        self
+    }
+
+    pub fn max_count(&mut self, max_count: Option<usize>) -> &mut SearchConfig {
+        self.max_count = max_count;
+        self
+    }
+
+    pub fn count(&mut self, yes: bool) -> &mut SearchConfig {
+        self.count = yes;
+        self
+    }
+
+    pub fn count_matches(&mut self, yes: bool) -> &mut SearchConfig {
+        self.count_matches = yes;
+        self
+    }
+
+    pub fn files_with_matches(&mut self, yes: bool) -> &mut SearchConfig {
+        self.files_with_matches = yes;
+        self
+    }
+
+    pub fn files_without_match(&mut self, yes: bool) -> &mut SearchConfig {
+        self.files_without_match = yes;
+        self
+    }
+
+    pub fn only_matching(&mut self, yes: bool) -> &mut SearchConfig {
+        self.only_matching = yes;
+        self
+    }
+
+    /// Prints only the text of the named group `name` of each match, as `-o` prints
+    /// whole matches; matches it took no part in are left out.
+    pub fn group(&mut self, name: &str) -> &mut SearchConfig {
+        self.group = Some(name.to_string());
+        self.only_matching = true;
+        self
+    }
+
+    pub fn quiet(&mut self, yes: bool) -> &mut SearchConfig {
+        self.quiet = yes;
+        self
+    }
+
+    pub fn column(&mut self, yes: bool) -> &mut SearchConfig {
+        self.column = yes;
+        self
+    }
+
+    pub fn byte_offset(&mut self, yes: bool) -> &mut SearchConfig {
+        self.byte_offset = yes;
+        self
+    }
+
+    pub fn json(&mut self, yes: bool) -> &mut SearchConfig {
+        self.json = yes;
+        self
+    }
+
+    /// Prints results in a structured format instead of lines. `--stats` isn't
+    /// reported, and binary files are skipped rather than reported.
+    pub fn format(&mut self, format: Option<FormatChoice>) -> &mut SearchConfig {
+        self.format = format;
+        self
+    }
+
+    pub fn vimgrep(&mut self, yes: bool) -> &mut SearchConfig {
+        self.vimgrep = yes;
+        self
+    }
+
+    /// Terminates printed paths with NUL (`--null`).
+    pub fn null(&mut self, yes: bool) -> &mut SearchConfig {
+        self.null = yes;
+        self
+    }
+
+    pub fn heading(&mut self, heading: Option<bool>) -> &mut SearchConfig {
+        self.heading = heading;
+        self
+    }
+
+    /// Whether printed lines and counts say which file they're from, under a heading or
+    /// as a prefix. Defaults to naming files when several can be searched: a directory,
+    /// or the members of an archive, but not a single file or stdin.
+    pub fn filename(&mut self, filename: Option<bool>) -> &mut SearchConfig {
+        self.filename = filename;
+        self
+    }
+
+    /// Whether printed lines start with their line number, which defaults to only on a
+    /// terminal. `--vimgrep` and `--json` always report it.
+    pub fn line_number(&mut self, line_number: Option<bool>) -> &mut SearchConfig {
+        self.line_number = line_number;
+        self
+    }
+
+    /// Reads input as records ended by `separator` rather than lines, and ends printed
+    /// records with it too: NUL handles `find -print0` output, whose NULs then don't make
+    /// it binary. A carriage return is only stripped before `\n`.
+    pub fn record_separator(&mut self, separator: u8) -> &mut SearchConfig {
+        self.record_separator = separator;
+        self
+    }
+
+    /// Cuts printed lines longer than `columns` characters down to that many, marking
+    /// what was left out with `[... omitted]`, so one minified line can't flood the
+    /// output.
+    pub fn max_columns(&mut self, columns: Option<usize>) -> &mut SearchConfig {
+        self.max_columns = columns;
+        self
+    }
+
+    /// With `max_columns`, shows the part of a cut line around its first match when the
+    /// match lies past the cut, instead of always the start of the line.
+    pub fn max_columns_preview(&mut self, yes: bool) -> &mut SearchConfig {
+        self.max_columns_preview = yes;
+        self
+    }
+
+    pub fn color(&mut self, color: ColorChoice) -> &mut SearchConfig {
+        self.color = color;
+        self
+    }
+
+    /// Writes results to a new file at `path` instead of stdout, replacing any there.
+    /// Like a redirected stdout, it's never colored unless `--color always` says so;
+    /// with `--watch`, every run's results are added to it.
+    pub fn output(&mut self, path: &Path) -> Result<&mut SearchConfig, &'static str> {
+        let file = std::fs::File::create(path).map_err(|_| INVALID_OUTPUT)?;
+        self.output_id = file
+            .metadata()
+            .ok()
+            .and_then(|metadata| walker::file_id(&metadata));
+        self.writer = std::sync::Mutex::new(Box::new(BufWriter::new(file)));
+        self.output = Some(path.to_path_buf());
+        Ok(self)
+    }
+
+    /// Writes results to `writer` instead of stdout, such as the pipe to a pager.
+    pub fn writer(&mut self, writer: impl Write + Send + 'static) -> &mut SearchConfig {
+        self.writer = std::sync::Mutex::new(Box::new(BufWriter::new(writer)));
+        self
+    }
+
+    pub fn pager(&mut self, yes: bool) -> &mut SearchConfig {
+        self.pager = yes;
+        self
+    }
+
+    /// Applies a `--colors` spec such as `match:fg:red`.
+    pub fn colors(&mut self, spec: &str) -> Result<&mut SearchConfig, &'static str> {
+        self.theme.apply(spec)?;
+        Ok(self)
+    }
+
+    pub fn replacement(&mut self, replacement: Option<&str>) -> &mut SearchConfig {
+        self.replacement = replacement.map(str::to_string);
+        self
+    }
+
+    pub fn write(&mut self, yes: bool) -> &mut SearchConfig {
+        self.write = yes;
+        self
+    }
+
+    pub fn diff(&mut self, yes: bool) -> &mut SearchConfig {
+        self.diff = yes;
+        self
+    }
+
+    /// Whether replacements are shown as a diff instead of printed or written, with
+    /// `--diff` or `--format patch`.
+    pub(crate) fn previews_diff(&self) -> bool {
+        self.diff || self.format == Some(FormatChoice::Patch)
+    }
+
+    pub fn backup(&mut self, suffix: Option<&str>) -> &mut SearchConfig {
+        self.backup = suffix.map(str::to_string);
+        self
+    }
+
+    pub fn text(&mut self, yes: bool) -> &mut SearchConfig {
+        self.text = yes;
+        self
+    }
+
+    pub fn binary(&mut self, yes: bool) -> &mut SearchConfig {
+        self.binary = yes;
+        self
+    }
+
+    pub fn search_zip(&mut self, yes: bool) -> &mut SearchConfig {
+        self.search_zip = yes;
+        self
+    }
+
+    pub fn archive(&mut self, yes: bool) -> &mut SearchConfig {
+        self.archive = yes;
+        self
+    }
+
+    pub fn preprocessor(&mut self, command: Option<&str>) -> &mut SearchConfig {
+        self.preprocessor = command.map(str::to_string);
+        self
+    }
+
+    /// Limits the preprocessor to files matching `glob`; see `--pre-glob`.
+    pub fn pre_glob(&mut self, glob: &str) -> Result<&mut SearchConfig, &'static str> {
+        let pattern = glob.strip_prefix('/').unwrap_or(glob);
+        let regex = format!("(?:^|/){}$", glob::to_regex(pattern));
+        let regex = regex::Regex::new(&regex).map_err(|_| INVALID_GLOB)?;
+        self.pre_globs.push(regex);
+        Ok(self)
+    }
+
+    /// Sets an `--encoding` label; `auto` sniffs a byte order mark.
+    pub fn encoding(&mut self, label: &str) -> Result<&mut SearchConfig, &'static str> {
+        self.encoding = match label {
+            "auto" => None,
+            label => Some(encoding::Encoding::from_label(label).ok_or(INVALID_ENCODING)?),
+        };
+        Ok(self)
+    }
+
+    pub fn mmap(&mut self, mmap: Option<bool>) -> &mut SearchConfig {
+        self.mmap = mmap;
+        self
+    }
+
+    /// Whether a directory search may skip reading files its `.yagrep-index` rules out;
+    /// see `index`.
+    pub fn index(&mut self, yes: bool) -> &mut SearchConfig {
+        self.index = yes;
+        self
+    }
+
+    pub fn hidden(&mut self, yes: bool) -> &mut SearchConfig {
+        self.hidden = yes;
+        self
+    }
+
+    /// Honors `.ignore` and `.gitignore` files; off is `--no-ignore`.
+    pub fn respect_ignore(&mut self, yes: bool) -> &mut SearchConfig {
+        self.respect_ignore = yes;
+        self
+    }
+
+    /// Honors `.gitignore` files; off is `--no-ignore-vcs`.
+    pub fn respect_gitignore(&mut self, yes: bool) -> &mut SearchConfig {
+        self.respect_gitignore = yes;
+        self
+    }
+
+    pub fn follow(&mut self, yes: bool) -> &mut SearchConfig {
+        self.follow = yes;
+        self
+    }
+
+    /// Leaves out directories on another file system than the one they're listed from,
+    /// such as mount points, so a walk of `/` stays out of `/proc` and network mounts.
+    /// Only Unix tells file systems apart.
+    pub fn one_file_system(&mut self, yes: bool) -> &mut SearchConfig {
+        self.one_file_system = yes;
+        self
+    }
+
+    /// Searches the FIFOs, sockets and device nodes found or given, which are otherwise
+    /// skipped: reading a named pipe nothing writes to would wait forever.
+    pub fn read_devices(&mut self, yes: bool) -> &mut SearchConfig {
+        self.read_devices = yes;
+        self
+    }
+
+    /// Restricts a directory walk to the files in git's index, listed once when the walk
+    /// starts. Ignore files and filters still apply on top.
+    pub fn git_tracked(&mut self, yes: bool) -> &mut SearchConfig {
+        self.git_tracked = yes;
+        self
+    }
+
+    /// Searches the files of a git revision, such as `HEAD~3`, instead of the working
+    /// tree. The hidden rule, globs, file types and `max_filesize` still apply; ignore
+    /// files don't, since the revision's files are whatever was committed.
+    pub fn revision(&mut self, revision: Option<&str>) -> &mut SearchConfig {
+        self.revision = revision.map(str::to_string);
+        self
+    }
+
+    /// Instead of printing matches, walks the first-parent history of the revision (or
+    /// `HEAD`) and prints, for each file the pattern ever appeared in, the first commit
+    /// it appeared in and the last. The hidden rule, globs, file types and
+    /// `max_filesize` apply as for `revision`.
+    pub fn git_log(&mut self, yes: bool) -> &mut SearchConfig {
+        self.git_log = yes;
+        self
+    }
+
+    /// Prints a line per match from `template`, such as `{path}:{line}: {match}`; see
+    /// `template` for the placeholders.
+    pub fn template(&mut self, template: &str) -> Result<&mut SearchConfig, &'static str> {
+        self.template = Some(template::Template::parse(template).ok_or(INVALID_TEMPLATE)?);
+        self.format = Some(FormatChoice::Template);
+        Ok(self)
+    }
+
+    /// Adds a `-g` include glob, or an exclude glob when it starts with `!`.
+    pub fn glob(&mut self, glob: &str) -> Result<&mut SearchConfig, &'static str> {
+        self.globs.add(glob).ok_or(INVALID_GLOB)?;
+        Ok(self)
+    }
+
+    /// Adds a `name:glob,glob` file type definition. Types must be defined before they
+    /// are selected.
+    pub fn type_add(&mut self, definition: &str) -> Result<&mut SearchConfig, &'static str> {
+        self.types.add(definition).ok_or(INVALID_TYPE_DEFINITION)?;
+        Ok(self)
+    }
+
+    /// Searches only files of type `name`, along with any other selected types.
+    pub fn select_type(&mut self, name: &str) -> Result<&mut SearchConfig, &'static str> {
+        self.types.select(name, false).ok_or(INVALID_TYPE)?;
+        Ok(self)
+    }
+
+    /// Never searches files of type `name`.
+    pub fn negate_type(&mut self, name: &str) -> Result<&mut SearchConfig, &'static str> {
+        self.types.select(name, true).ok_or(INVALID_TYPE)?;
+        Ok(self)
+    }
+
+    pub fn max_depth(&mut self, max_depth: Option<usize>) -> &mut SearchConfig {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn max_filesize(&mut self, bytes: Option<u64>) -> &mut SearchConfig {
+        self.max_filesize = bytes;
+        self
+    }
+
+    pub fn sort(&mut self, sort: SortChoice) -> &mut SearchConfig {
+        self.sort = sort;
+        self
+    }
+
+    /// Reverses the order of [`SortChoice::Path`], `Modified` and `Size`.
+    pub fn sort_reverse(&mut self, yes: bool) -> &mut SearchConfig {
+        self.sort_reverse = yes;
+        self
+    }
+
+    /// Whether problems with individual files are printed on stderr. They're counted
+    /// either way; see [`crate::Searcher::had_errors`].
+    pub fn messages(&mut self, yes: bool) -> &mut SearchConfig {
+        self.messages = yes;
+        self
+    }
+
+    /// Prints totals for the run after its results; `--json` reports them in its summary
+    /// event instead.
+    pub fn stats(&mut self, yes: bool) -> &mut SearchConfig {
+        self.stats = yes;
+        self
+    }
+
+    /// Prints a table of the files that matched, ranked by their matches with each one's
+    /// share, in place of the results. Inverted, it counts the selected lines instead.
+    pub fn summary(&mut self, yes: bool) -> &mut SearchConfig {
+        self.summary = yes;
+        self
+    }
+
+    /// Adds a bar chart to the summary.
+    pub fn summary_chart(&mut self, yes: bool) -> &mut SearchConfig {
+        self.summary_chart = yes;
+        self
+    }
+
+    /// Prints each distinct text matched, or captured by the `--group`, with how many times
+    /// it was found, in place of the results. Inverted, whole lines are counted.
+    pub fn tally(&mut self, yes: bool) -> &mut SearchConfig {
+        self.tally = yes;
+        self
+    }
+
+    /// Number of files searched concurrently; 0 picks one per CPU.
+    pub fn threads(&mut self, threads: usize) -> &mut SearchConfig {
+        self.threads = threads;
+        self
+    }
+}
+
+impl SearchConfig {
+    /// Writes one file's complete output. Once the output can't be written, as when its
+    /// reader has gone, the search is over and nothing more is written.
+    pub(crate) fn emit(&self, output: &[u8]) {
+        if output.is_empty() || self.is_closed() {
+            return;
+        }
+        let mut writer = self.writer.lock().unwrap();
+        let written = match self.format {
+            // Each line is a result, and the document's array of them needs commas.
+            Some(FormatChoice::Sarif) => output
+                .split(|&byte| byte == b'\n')
+                .filter(|line| !line.is_empty())
+                .try_for_each(|result| {
+                    let emitted = self
+                        .results_emitted
+                        .swap(true, std::sync::atomic::Ordering::SeqCst);
+                    writer.write_all(if emitted { b",\n" } else { b"\n" })?;
+                    writer.write_all(result)
+                }),
+            _ => writer.write_all(output),
+        };
+        if let Err(err) = written.and_then(|_| writer.flush()) {
+            self.closed.store(true, std::sync::atomic::Ordering::SeqCst);
+            // A closed pipe is how `head` says it has seen enough, and no problem.
+            if err.kind() != std::io::ErrorKind::BrokenPipe {
+                self.error(&format!("Failed to write output: {}", err));
+            }
+        }
+    }
+
+    /// Whether the output was closed, so the search should stop.
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Whether the search should stop short: its output was closed, or Ctrl-C pressed.
+    pub(crate) fn should_stop(&self) -> bool {
+        self.is_closed() || interrupt::interrupted()
+    }
+
+    /// Reports a file that couldn't be searched, or only partly, and counts it against
+    /// the run. The search goes on with the next file.
+    pub(crate) fn error(&self, message: &str) {
+        if self.muted.load(std::sync::atomic::Ordering::SeqCst) {
+            return;
+        }
+        self.errors
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        if self.messages {
+            eprintln!("Error: {}", message);
+        }
+    }
+
+    /// Reports something skipped that doesn't count as a failure, like a broken symlink.
+    pub(crate) fn warning(&self, message: &str) {
+        if self.messages && !self.muted.load(std::sync::atomic::Ordering::SeqCst) {
+            eprintln!("Warning: {}", message);
+        }
+    }
+
+    /// Whether results go to a terminal, rather than a pipe or an `--output` file.
+    fn to_terminal(&self) -> bool {
+        self.output.is_none() && std::io::stdout().is_terminal()
+    }
+
+    /// Whether paths are printed once above their lines rather than prefixed to each line.
+    /// Defaults to grouping on a terminal and prefixing when piped.
+    pub(crate) fn use_heading(&self) -> bool {
+        self.heading.unwrap_or_else(|| self.to_terminal())
+    }
+
+    /// Whether printed lines and counts name their file.
+    pub(crate) fn use_filename(&self) -> bool {
+        self.filename.unwrap_or_else(|| match &self.path {
+            _ if self.archive => true,
+            Some(path) => path.is_dir(),
+            // `--rev` without a path searches the whole tree.
+            None => self.revision.is_some(),
+        })
+    }
+
+    /// Whether printed lines are numbered. Always with `--column`, since a column means
+    /// little without its line; otherwise defaults to numbering on a terminal and not
+    /// when piped.
+    pub(crate) fn use_line_number(&self) -> bool {
+        self.column || self.line_number.unwrap_or_else(|| self.to_terminal())
+    }
+
+    /// Whether a file of `len` bytes is searched through a memory map. Defaults to
+    /// mapping files of at least `mmap::THRESHOLD` bytes.
+    pub(crate) fn use_mmap(&self, len: u64) -> bool {
+        self.mmap.unwrap_or(len >= mmap::THRESHOLD)
+    }
+
+    /// The `--pre` command for `path`, if any applies to it.
+    pub(crate) fn preprocessor_for(&self, path: &Path) -> Option<&str> {
+        let path = path.to_string_lossy();
+        let selected =
+            self.pre_globs.is_empty() || self.pre_globs.iter().any(|glob| glob.is_match(&path));
+        self.preprocessor.as_deref().filter(|_| selected)
+    }
+
+    /// Number of threads for a directory walk.
+    pub(crate) fn thread_count(&self) -> usize {
+        match self.threads {
+            0 => std::thread::available_parallelism().map_or(1, usize::from),
+            threads => threads,
+        }
+    }
+
+    /// Whether output is colored. On Windows, this turns on the console's handling of
+    /// color codes; `auto` goes without colors on a console that can't.
+    pub fn use_color(&self) -> bool {
+        match self.color {
+            ColorChoice::Always => {
+                console::enable_ansi();
+                true
+            }
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                self.to_terminal()
+                    && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                    && console::enable_ansi()
+            }
+        }
+    }
+
+    /// Whether results should go through a pager: they're shown on a terminal, there
+    /// are some to show, and no `--interactive` prompt needs the terminal meanwhile.
+    pub fn use_pager(&self) -> bool {
+        self.pager && !self.quiet && self.to_terminal() && self.prompter.lock().unwrap().is_none()
+    }
+
+    /// What follows a printed path: a NUL with `--null` (for `xargs -0`), otherwise
+    /// `default`.
+    pub(crate) fn path_terminator<'a>(&self, default: &'a str) -> &'a str {
+        match self.null {
+            true => "\0",
+            false => default,
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! Colors on the Windows console, which prints ANSI escapes as text unless virtual
+//! terminal processing is turned on for it. Consoles too old to have it (before
+//! Windows 10) get no colors rather than escape codes.
+
+/// Prepares standard output for ANSI escapes, returning whether they'll render. Output
+/// that isn't a console, such as a terminal emulator's pipe, is assumed to handle them.
+#[cfg(windows)]
+pub(crate) fn enable_ansi() -> bool {
+    // SAFETY: the handle is this process's standard output, whose mode is only read and
+    // extended.
+    unsafe {
+        let handle = sys::GetStdHandle(sys::STD_OUTPUT_HANDLE);
+        let mut mode = 0;
+        if sys::GetConsoleMode(handle, &mut mode) == 0 {
+            return true;
+        }
+        mode & sys::ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+            || sys::SetConsoleMode(handle, mode | sys::ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+    }
+}
+
+/// Everywhere else, terminals render ANSI escapes as they are.
+#[cfg(not(windows))]
+pub(crate) fn enable_ansi() -> bool {
+    true
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::c_void;
+
+    pub const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    pub const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub fn GetStdHandle(std_handle: u32) -> *mut c_void;
+        pub fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+        pub fn SetConsoleMode(console: *mut c_void, mode: u32) -> i32;
+    }
+}
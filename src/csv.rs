@@ -0,0 +1,31 @@
+//! CSV and TSV output for `--format csv` and `--format tsv`: a header row, then one
+//! record per match.
+
+const FIELDS: [&str; 5] = ["path", "line", "column", "match", "text"];
+
+/// The header row, newline included, with a column for each of the pattern's named
+/// `groups` after the usual fields.
+pub fn header<'a>(tabs: bool, groups: impl Iterator<Item = &'a str>) -> String {
+    let fields = FIELDS.into_iter().chain(groups).collect::<Vec<_>>();
+    format!("{}\n", record(&fields, tabs))
+}
+
+/// One record, without its newline. CSV fields holding a comma, quote or line break are
+/// quoted as RFC 4180 describes; TSV can't quote, so tabs, line breaks and backslashes
+/// are written as `\t`, `\n`, `\r` and `\\`.
+pub fn record(fields: &[&str], tabs: bool) -> String {
+    let fields = fields.iter().map(|field| match tabs {
+        true => field
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r"),
+        false if field.contains([',', '"', '\n', '\r']) => {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        }
+        false => field.to_string(),
+    });
+    fields
+        .collect::<Vec<_>>()
+        .join(if tabs { "\t" } else { "," })
+}
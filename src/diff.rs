@@ -0,0 +1,82 @@
+//! Unified diffs for `--replace --diff`, previewing what `--write` would change.
+
+use std::borrow::Cow;
+
+/// Unchanged lines shown around each change, as in `diff -u`.
+const CONTEXT: usize = 3;
+
+/// Renders a unified diff from `(original, replaced)` line pairs, each line keeping its
+/// terminator, as raw bytes. A replaced line may contain several lines if the replacement added
+/// newlines. Returns an empty string when nothing changed.
+pub fn unified(old_path: &str, new_path: &str, lines: &[(&[u8], Cow<[u8]>)]) -> String {
+    let changed = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, (old, new))| *old != new.as_ref())
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    if changed.is_empty() {
+        return String::new();
+    }
+
+    let mut diff = format!("--- {}\n+++ {}\n", old_path, new_path);
+    // How far line numbers in the new file have drifted from the old one.
+    let mut offset: isize = 0;
+    let mut hunk_start = 0;
+    while hunk_start < changed.len() {
+        // Merge changes whose context would overlap into one hunk.
+        let mut hunk_end = hunk_start;
+        while hunk_end + 1 < changed.len()
+            && changed[hunk_end + 1] - changed[hunk_end] <= 2 * CONTEXT
+        {
+            hunk_end += 1;
+        }
+        let start = changed[hunk_start].saturating_sub(CONTEXT);
+        let end = (changed[hunk_end] + CONTEXT + 1).min(lines.len());
+
+        let mut body = String::new();
+        let mut new_count = 0;
+        for (old, new) in &lines[start..end] {
+            if *old == new.as_ref() {
+                push_line(&mut body, ' ', old);
+                new_count += 1;
+                continue;
+            }
+            push_line(&mut body, '-', old);
+            for new_line in new.split_inclusive(|&byte| byte == b'\n') {
+                push_line(&mut body, '+', new_line);
+                new_count += 1;
+            }
+        }
+
+        let old_count = end - start;
+        let new_start = (start as isize + offset) as usize;
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(start, old_count),
+            range(new_start, new_count)
+        ));
+        diff.push_str(&body);
+        offset += new_count as isize - old_count as isize;
+        hunk_start = hunk_end + 1;
+    }
+    diff
+}
+
+/// Formats a hunk range; an empty range points at the line before it, as `diff` does.
+fn range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+/// Bytes that aren't valid UTF-8 are shown as U+FFFD.
+fn push_line(body: &mut String, marker: char, line: &[u8]) {
+    body.push(marker);
+    body.push_str(&String::from_utf8_lossy(line));
+    if !line.ends_with(b"\n") {
+        body.push_str("\n\\ No newline at end of file\n");
+    }
+}
@@ -0,0 +1,159 @@
+//! Transcoding of non-UTF-8 input (`--encoding`), so files saved as UTF-16 — common on
+//! Windows — or Latin-1 are searched as text. Without `--encoding`, a byte order mark
+//! decides, and input without one is searched as UTF-8.
+
+use std::io::Read;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// ISO-8859-1: every byte is the code point of the same value.
+    Latin1,
+}
+
+const BOMS: [(&[u8], Encoding); 3] = [
+    (b"\xEF\xBB\xBF", Encoding::Utf8),
+    (b"\xFF\xFE", Encoding::Utf16Le),
+    (b"\xFE\xFF", Encoding::Utf16Be),
+];
+
+impl Encoding {
+    /// Parses an `--encoding` label, ignoring case and punctuation (`UTF-16LE`,
+    /// `utf16le`).
+    pub fn from_label(label: &str) -> Option<Encoding> {
+        let label = label
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .collect::<String>()
+            .to_ascii_lowercase();
+        match label.as_str() {
+            "utf8" => Some(Encoding::Utf8),
+            "utf16le" => Some(Encoding::Utf16Le),
+            "utf16be" => Some(Encoding::Utf16Be),
+            "latin1" | "iso88591" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+}
+
+/// Picks the encoding for input starting with `start`, returning it with the length of
+/// the byte order mark to skip. An explicit `requested` encoding always wins, but its
+/// own mark is still skipped.
+pub fn detect(start: &[u8], requested: Option<Encoding>) -> (Encoding, usize) {
+    let sniffed = BOMS.iter().find(|(bom, _)| start.starts_with(bom));
+    match (requested, sniffed) {
+        (Some(requested), Some(&(bom, sniffed))) if requested == sniffed => (requested, bom.len()),
+        (Some(requested), _) => (requested, 0),
+        (None, Some(&(bom, sniffed))) => (sniffed, bom.len()),
+        (None, None) => (Encoding::Utf8, 0),
+    }
+}
+
+/// Reads `inner` as `encoding` and produces UTF-8. Malformed input becomes U+FFFD.
+pub struct Decoder<R> {
+    inner: R,
+    encoding: Encoding,
+    /// Bytes read but not decoded yet, such as half of a UTF-16 code unit.
+    input: Vec<u8>,
+    output: Vec<u8>,
+    position: usize,
+    done: bool,
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(inner: R, encoding: Encoding) -> Decoder<R> {
+        Decoder {
+            inner,
+            encoding,
+            input: Vec::new(),
+            output: Vec::new(),
+            position: 0,
+            done: false,
+        }
+    }
+
+    fn fill(&mut self) -> std::io::Result<()> {
+        self.output.clear();
+        self.position = 0;
+        let mut chunk = [0; 8192];
+        let read = self.inner.read(&mut chunk)?;
+        if read == 0 {
+            self.done = true;
+            if !self.input.is_empty() {
+                self.output.extend_from_slice("\u{FFFD}".as_bytes());
+                self.input.clear();
+            }
+            return Ok(());
+        }
+        self.input.extend_from_slice(&chunk[..read]);
+        let consumed = decode(self.encoding, &self.input, &mut self.output);
+        self.input.drain(..consumed);
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.output.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.fill()?;
+        }
+        let length = buf.len().min(self.output.len() - self.position);
+        buf[..length].copy_from_slice(&self.output[self.position..self.position + length]);
+        self.position += length;
+        Ok(length)
+    }
+}
+
+/// Appends the UTF-8 form of the longest decodable prefix of `input` to `output`,
+/// returning how many bytes of `input` it used.
+fn decode(encoding: Encoding, input: &[u8], output: &mut Vec<u8>) -> usize {
+    let unit = |index: usize| {
+        let bytes = [input[index], input[index + 1]];
+        match encoding {
+            Encoding::Utf16Be => u16::from_be_bytes(bytes),
+            _ => u16::from_le_bytes(bytes),
+        }
+    };
+    let mut push = |c: char| output.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+
+    match encoding {
+        Encoding::Utf8 => {
+            output.extend_from_slice(input);
+            input.len()
+        }
+        Encoding::Latin1 => {
+            input.iter().for_each(|&byte| push(char::from(byte)));
+            input.len()
+        }
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let mut index = 0;
+            while index + 2 <= input.len() {
+                let high = unit(index);
+                if !(0xD800..0xDC00).contains(&high) {
+                    push(char::from_u32(u32::from(high)).unwrap_or('\u{FFFD}'));
+                    index += 2;
+                    continue;
+                }
+                // A surrogate pair may be split across reads.
+                if index + 4 > input.len() {
+                    break;
+                }
+                let low = unit(index + 2);
+                if !(0xDC00..0xE000).contains(&low) {
+                    push('\u{FFFD}');
+                    index += 2;
+                    continue;
+                }
+                let code = 0x10000 + ((u32::from(high) - 0xD800) << 10) + (u32::from(low) - 0xDC00);
+                push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                index += 4;
+            }
+            index
+        }
+    }
+}
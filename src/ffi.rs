@@ -0,0 +1,234 @@
+//! The C interface, behind the `ffi` feature: `yagrep_search` runs a search and calls
+//! back once per selected line. The build writes `include/yagrep.h` from this file, and
+//! `cargo rustc --lib --release --features ffi --crate-type cdylib` (or `staticlib`)
+//! builds the library to link against; an ordinary build doesn't.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+
+use crate::config::SearchConfig;
+use crate::searcher::Searcher;
+
+/// Something selected a line.
+pub const YAGREP_OK: c_int = 0;
+/// Nothing was selected.
+pub const YAGREP_NO_MATCH: c_int = 1;
+/// A required pointer was null, or a string wasn't UTF-8.
+pub const YAGREP_ERROR_ARGUMENT: c_int = -1;
+/// The pattern didn't compile.
+pub const YAGREP_ERROR_PATTERN: c_int = -2;
+/// The path doesn't exist, or an option was rejected.
+pub const YAGREP_ERROR_CONFIG: c_int = -3;
+/// The search failed unexpectedly: a bug in yagrep, which the message describes.
+pub const YAGREP_ERROR_INTERNAL: c_int = -4;
+
+/// Search options; a null pointer means all of them off.
+#[repr(C)]
+pub struct YagrepOptions {
+    pub case_insensitive: bool,
+    pub fixed_strings: bool,
+    pub word_regexp: bool,
+    pub line_regexp: bool,
+    pub invert_match: bool,
+    pub hidden: bool,
+    /// Search files that ignore files exclude.
+    pub no_ignore: bool,
+    pub follow: bool,
+    /// Lines selected per file at most, 0 for no limit.
+    pub max_count: usize,
+    /// How deep to walk directories, 0 for no limit.
+    pub max_depth: usize,
+    /// `--glob` patterns, `glob_count` of them.
+    pub globs: *const *const c_char,
+    pub glob_count: usize,
+}
+
+/// One selected line, valid only for the duration of the callback.
+#[repr(C)]
+pub struct YagrepMatch {
+    /// The file's path, or null for standard input; not NUL-terminated.
+    pub path: *const u8,
+    pub path_len: usize,
+    pub line_number: usize,
+    pub offset: usize,
+    /// The line without its terminator; not NUL-terminated.
+    pub line: *const u8,
+    pub line_len: usize,
+    /// `span_count` pairs of start and end offsets within `line`.
+    pub spans: *const usize,
+    pub span_count: usize,
+}
+
+/// Receives each match with the caller's `user_data`; returning nonzero stops the
+/// search.
+pub type YagrepCallback =
+    Option<unsafe extern "C" fn(found: *const YagrepMatch, user_data: *mut c_void) -> c_int>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Searches `path` (standard input when null) for `pattern`, calling `callback` once for
+/// every selected line in walk order. Returns `YAGREP_OK` or `YAGREP_NO_MATCH`, or a
+/// negative error code with the message left for `yagrep_last_error`.
+///
+/// # Safety
+///
+/// `pattern` and a non-null `path` must be NUL-terminated strings, and a non-null
+/// `options` must point to a valid `YagrepOptions` whose `globs` holds `glob_count`
+/// NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn yagrep_search(
+    pattern: *const c_char,
+    path: *const c_char,
+    options: *const YagrepOptions,
+    callback: YagrepCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    // A panic must not unwind into C, so it becomes an error code.
+    let searched = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        search(pattern, path, options, callback, user_data)
+    }));
+    searched.unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("the search panicked");
+        set_last_error(&format!("Internal error: {}", message));
+        YAGREP_ERROR_INTERNAL
+    })
+}
+
+/// The body of `yagrep_search`.
+unsafe fn search(
+    pattern: *const c_char,
+    path: *const c_char,
+    options: *const YagrepOptions,
+    callback: YagrepCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let result = configure(pattern, path, options).and_then(|config| {
+        Searcher::new(config).map_err(|err| {
+            let message = format!("Failed to compile regex: {}", err);
+            (YAGREP_ERROR_PATTERN, message)
+        })
+    });
+    let searcher = match result {
+        Ok(searcher) => searcher,
+        Err((code, message)) => {
+            set_last_error(&message);
+            return code;
+        }
+    };
+    set_last_error("");
+
+    let mut matched = false;
+    for found in searcher.search() {
+        matched = true;
+        let Some(callback) = callback else {
+            break;
+        };
+        let path = found
+            .path
+            .as_deref()
+            .map(|path| path.as_os_str().as_encoded_bytes());
+        let spans = found
+            .spans
+            .iter()
+            .flat_map(|span| [span.start, span.end])
+            .collect::<Vec<_>>();
+        let found_line = YagrepMatch {
+            path: path.map_or(std::ptr::null(), <[u8]>::as_ptr),
+            path_len: path.map_or(0, <[u8]>::len),
+            line_number: found.line_number,
+            offset: found.offset,
+            line: found.line.as_ptr(),
+            line_len: found.line.len(),
+            spans: spans.as_ptr(),
+            span_count: found.spans.len(),
+        };
+        if callback(&found_line, user_data) != 0 {
+            break;
+        }
+    }
+    match matched {
+        true => YAGREP_OK,
+        false => YAGREP_NO_MATCH,
+    }
+}
+
+/// The message of the last error `yagrep_search` returned on this thread, or an empty
+/// string. Valid until the next call on the same thread.
+#[no_mangle]
+pub extern "C" fn yagrep_last_error() -> *const c_char {
+    let last_error = std::panic::catch_unwind(|| {
+        LAST_ERROR.with(|last| match &*last.borrow() {
+            Some(message) => message.as_ptr(),
+            None => c"".as_ptr(),
+        })
+    });
+    last_error.unwrap_or(c"".as_ptr())
+}
+
+fn set_last_error(message: &str) {
+    let message = CString::new(message).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Builds the configuration from the C arguments, or an error code with its message.
+unsafe fn configure(
+    pattern: *const c_char,
+    path: *const c_char,
+    options: *const YagrepOptions,
+) -> Result<SearchConfig, (c_int, String)> {
+    let argument = |message: &str| (YAGREP_ERROR_ARGUMENT, message.to_string());
+    let string = |pointer: *const c_char| CStr::from_ptr(pointer).to_str();
+
+    let mut config = SearchConfig::new();
+    if pattern.is_null() {
+        return Err(argument("The pattern is null"));
+    }
+    let pattern = string(pattern).map_err(|_| argument("The pattern is not UTF-8"))?;
+    config.pattern(pattern);
+    if !path.is_null() {
+        let path = string(path).map_err(|_| argument("The path is not UTF-8"))?;
+        if !std::path::Path::new(path).exists() {
+            return Err((YAGREP_ERROR_CONFIG, "File not found".to_string()));
+        }
+        config.path(path);
+    }
+
+    let Some(options) = options.as_ref() else {
+        return Ok(config);
+    };
+    config
+        .case_insensitive(options.case_insensitive)
+        .fixed_strings(options.fixed_strings)
+        .word_regexp(options.word_regexp)
+        .line_regexp(options.line_regexp)
+        .invert_match(options.invert_match)
+        .hidden(options.hidden)
+        .respect_ignore(!options.no_ignore)
+        .follow(options.follow)
+        .max_count((options.max_count > 0).then_some(options.max_count))
+        .max_depth((options.max_depth > 0).then_some(options.max_depth));
+    if options.glob_count > 0 {
+        if options.globs.is_null() {
+            return Err(argument("The globs are null"));
+        }
+        for &glob in std::slice::from_raw_parts(options.globs, options.glob_count) {
+            if glob.is_null() {
+                return Err(argument("A glob is null"));
+            }
+            let glob = string(glob).map_err(|_| argument("A glob is not UTF-8"))?;
+            config.glob(glob).map_err(|err| {
+                (
+                    YAGREP_ERROR_CONFIG,
+                    err.trim_start_matches("Error: ").to_string(),
+                )
+            })?;
+        }
+    }
+    Ok(config)
+}
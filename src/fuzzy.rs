@@ -0,0 +1,278 @@
+//! Approximate matching for `--fuzzy`, in the manner of agrep: a pattern, taken
+//! literally, matches any text within `distance` edits of it, an edit being a character
+//! inserted, deleted or substituted, or two adjacent characters swapped. Matches are
+//! found a line at a time with Sellers' dynamic program, which tracks where each
+//! candidate began so the whole span can be reported.
+
+use std::ops::Range;
+
+/// A candidate match: the edits it takes, and the character it starts at.
+type Cell = (usize, usize);
+
+/// Stands in for the cost of a start the options rule out.
+const UNREACHABLE: usize = usize::MAX / 2;
+
+pub(crate) struct Pattern {
+    patterns: Vec<Vec<char>>,
+    distance: usize,
+    case_insensitive: bool,
+    /// Matches must start and end at word boundaries (`-w`).
+    word: bool,
+    /// Matches must be the whole line (`-x`).
+    line: bool,
+}
+
+impl Pattern {
+    /// Fails for a pattern no longer than `distance`, which every line would match.
+    pub(crate) fn new(
+        patterns: &[String],
+        distance: usize,
+        case_insensitive: bool,
+        word: bool,
+        line: bool,
+    ) -> Result<Pattern, String> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                let chars = pattern
+                    .chars()
+                    .map(|c| fold(c, case_insensitive))
+                    .collect::<Vec<_>>();
+                match chars.len() > distance {
+                    true => Ok(chars),
+                    false => Err(format!(
+                        "the fuzzy pattern {:?} must be longer than the fuzzy distance, {}",
+                        pattern, distance
+                    )),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Pattern {
+            patterns,
+            distance,
+            case_insensitive,
+            word,
+            line,
+        })
+    }
+
+    /// The leftmost match starting at or after byte `start`, with the index of its
+    /// pattern; of matches starting together, the first pattern's.
+    pub(crate) fn find_at(&self, line: &[u8], start: usize) -> Option<(Range<usize>, usize)> {
+        let chars = chars(line);
+        // Where the search begins, in `text` positions.
+        let from = chars
+            .iter()
+            .position(|&(offset, _)| offset >= start)
+            .unwrap_or(chars.len());
+        let text = chars
+            .iter()
+            .map(|&(_, c)| fold(c, self.case_insensitive))
+            .collect::<Vec<_>>();
+        let offset = |at: usize| chars.get(at).map_or(line.len(), |&(offset, _)| offset);
+        // Whether `text` position `at` isn't inside a word, for `-w`.
+        let boundary = |at: usize| {
+            let before = at
+                .checked_sub(1)
+                .and_then(|before| chars.get(before))
+                .is_some_and(|&(_, c)| is_word(c));
+            let after = chars.get(at).is_some_and(|&(_, c)| is_word(c));
+            !(before && after)
+        };
+        let may_start = |at: usize| match (self.line, self.word) {
+            (true, _) => at == 0 && from == 0,
+            (false, true) => boundary(at),
+            (false, false) => true,
+        };
+        let may_end = |at: usize| match (self.line, self.word) {
+            (true, _) => at == text.len(),
+            (false, true) => boundary(at),
+            (false, false) => true,
+        };
+
+        self.patterns
+            .iter()
+            .enumerate()
+            .filter_map(|(index, pattern)| {
+                let span = self.search(pattern, &text, from, &may_start, &may_end)?;
+                Some((span, index))
+            })
+            .min_by_key(|(span, _)| (span.start, std::cmp::Reverse(span.end)))
+            .map(|(span, index)| (offset(span.start)..offset(span.end), index))
+    }
+
+    /// The first match of `pattern` in `text` to end after position `from`, extended for
+    /// as long as that lowers its cost, as a range of `text` positions.
+    fn search(
+        &self,
+        pattern: &[char],
+        text: &[char],
+        from: usize,
+        may_start: &dyn Fn(usize) -> bool,
+        may_end: &dyn Fn(usize) -> bool,
+    ) -> Option<Range<usize>> {
+        let first = |at: usize| -> Vec<Cell> {
+            let start_cost = match may_start(at) {
+                true => 0,
+                false => UNREACHABLE,
+            };
+            (0..=pattern.len()).map(|i| (start_cost + i, at)).collect()
+        };
+        let mut before = first(from);
+        let mut previous = before.clone();
+        let mut best: Option<(usize, Range<usize>)> = None;
+        for (j, &c) in text.iter().enumerate().skip(from) {
+            let mut column = first(j + 1);
+            for i in 1..=pattern.len() {
+                let substituted = previous[i - 1].0 + usize::from(pattern[i - 1] != c);
+                let mut cell = (substituted, previous[i - 1].1)
+                    .min((previous[i].0 + 1, previous[i].1))
+                    .min((column[i - 1].0 + 1, column[i - 1].1));
+                let swapped =
+                    i >= 2 && j > from && pattern[i - 1] == text[j - 1] && pattern[i - 2] == c;
+                if swapped {
+                    cell = cell.min((before[i - 2].0 + 1, before[i - 2].1));
+                }
+                column[i] = cell;
+            }
+            let (cost, origin) = column[pattern.len()];
+            let end = j + 1;
+            match &best {
+                Some((best_cost, _)) if cost >= *best_cost => break,
+                _ if cost <= self.distance && may_end(end) => best = Some((cost, origin..end)),
+                _ => {}
+            }
+            before = std::mem::replace(&mut previous, column);
+        }
+        best.map(|(_, span)| span)
+    }
+}
+
+/// The characters of `line` with their offsets. An invalid UTF-8 byte stands for U+FFFD,
+/// as it would be printed.
+fn chars(line: &[u8]) -> Vec<(usize, char)> {
+    let mut chars = Vec::new();
+    let mut at = 0;
+    while at < line.len() {
+        let (valid, invalid) = match std::str::from_utf8(&line[at..]) {
+            Ok(valid) => (valid, false),
+            Err(err) => (
+                std::str::from_utf8(&line[at..at + err.valid_up_to()]).unwrap(),
+                true,
+            ),
+        };
+        chars.extend(valid.char_indices().map(|(offset, c)| (at + offset, c)));
+        at += valid.len();
+        if invalid {
+            chars.push((at, char::REPLACEMENT_CHARACTER));
+            at += 1;
+        }
+    }
+    chars
+}
+
+fn fold(c: char, case_insensitive: bool) -> char {
+    match case_insensitive {
+        true => c.to_lowercase().next().unwrap_or(c),
+        false => c,
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(pattern: &str, distance: usize) -> Pattern {
+        Pattern::new(&[pattern.to_string()], distance, false, false, false).unwrap()
+    }
+
+    /// The text of the first match in `line`.
+    fn found<'a>(pattern: &Pattern, line: &'a str) -> Option<&'a str> {
+        let (span, _) = pattern.find_at(line.as_bytes(), 0)?;
+        Some(&line[span])
+    }
+
+    #[test]
+    fn one_edit_of_each_kind() {
+        let one = pattern("color", 1);
+        assert_eq!(found(&one, "the color red"), Some("color"));
+        assert_eq!(found(&one, "the coxlor red"), Some("coxlor"));
+        assert_eq!(found(&one, "the colr red"), Some("colr"));
+        assert_eq!(found(&one, "the cilor red"), Some("cilor"));
+        assert_eq!(found(&one, "the oclor red"), Some("oclor"));
+    }
+
+    #[test]
+    fn spans_end_as_soon_as_they_are_close_enough() {
+        // `colo` is already one deletion away, and `colour` is no closer.
+        assert_eq!(found(&pattern("color", 1), "the colour red"), Some("colo"));
+        // `colo` is one away, but `color` is exact.
+        assert_eq!(found(&pattern("color", 1), "the color red"), Some("color"));
+    }
+
+    #[test]
+    fn two_edits_exceed_a_distance_of_one() {
+        let one = pattern("color", 1);
+        assert_eq!(found(&one, "the cilour red"), None);
+        assert_eq!(found(&one, "the clr red"), None);
+        let two = pattern("color", 2);
+        assert_eq!(found(&two, "the cilour red"), Some("cilo"));
+    }
+
+    #[test]
+    fn distance_zero_is_exact() {
+        let exact = pattern("color", 0);
+        assert_eq!(found(&exact, "colour color"), Some("color"));
+        assert_eq!(found(&exact, "colour"), None);
+    }
+
+    #[test]
+    fn pattern_must_outlast_the_distance() {
+        let new = |pattern: &str, distance| {
+            Pattern::new(&[pattern.to_string()], distance, false, false, false)
+        };
+        assert!(new("a", 1).is_err());
+        assert!(new("ab", 2).is_err());
+        assert!(new("ab", 1).is_ok());
+        assert!(new("a", 0).is_ok());
+    }
+
+    #[test]
+    fn case_folding() {
+        let folded = Pattern::new(&["Color".to_string()], 1, true, false, false).unwrap();
+        assert_eq!(found(&folded, "COLOR"), Some("COLOR"));
+        assert_eq!(found(&pattern("Color", 1), "COLOR"), None);
+    }
+
+    #[test]
+    fn word_and_line_boundaries() {
+        let word = Pattern::new(&["color".to_string()], 1, false, true, false).unwrap();
+        assert_eq!(found(&word, "discolored"), None);
+        assert_eq!(found(&word, "no colr here"), Some("colr"));
+        let line = Pattern::new(&["color".to_string()], 1, false, false, true).unwrap();
+        assert_eq!(found(&line, "colour"), Some("colour"));
+        assert_eq!(found(&line, "a colour"), None);
+    }
+
+    #[test]
+    fn offsets_are_bytes() {
+        let one = pattern("café", 1);
+        let line = "à la cafés";
+        let (span, _) = one.find_at(line.as_bytes(), 0).unwrap();
+        assert_eq!(&line[span], "café");
+        // A start inside the text leaves only what follows it.
+        assert_eq!(one.find_at(line.as_bytes(), line.len() - 1), None);
+    }
+
+    #[test]
+    fn leftmost_of_several_patterns() {
+        let patterns = ["world".to_string(), "hello".to_string()];
+        let both = Pattern::new(&patterns, 1, false, false, false).unwrap();
+        assert_eq!(both.find_at(b"helo wrld", 0), Some((0..4, 1)));
+        assert_eq!(both.find_at(b"helo wrld", 4), Some((5..9, 0)));
+    }
+}
@@ -0,0 +1,228 @@
+//! Reading a repository through the `git` command: for `--git-tracked`, the files of a
+//! past revision (`--rev`) and the history `--git-log` walks.
+
+use std::ffi::OsStr;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+/// Runs `git` in `directory` and returns what it printed, or why it failed.
+pub(crate) fn output<I, S>(directory: &Path, args: I) -> Result<Vec<u8>, String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|err| format!("can't run git: {}", err))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(stderr.trim().trim_start_matches("fatal: ").to_string());
+    }
+    Ok(output.stdout)
+}
+
+/// Where `directory` is in its repository's working tree, relative to the top: `d/`
+/// for a subdirectory `d`, and empty at the top.
+pub(crate) fn prefix(directory: &Path) -> Result<PathBuf, String> {
+    let output = output(directory, ["rev-parse", "--show-prefix"])?;
+    let line = output.strip_suffix(b"\n").unwrap_or(&output);
+    Ok(path_from_bytes(line))
+}
+
+/// A file of a revision's tree.
+pub(crate) struct Blob {
+    /// Relative to the directory the tree was listed from.
+    pub(crate) path: PathBuf,
+    /// The blob's id, which is the same for the same contents.
+    pub(crate) object: String,
+}
+
+/// A commit of the history `--git-log` walks, with the files it changed.
+pub(crate) struct Commit {
+    pub(crate) hash: String,
+    pub(crate) subject: String,
+    /// The changed files' new versions; `None` for a file the commit deleted.
+    pub(crate) changes: Vec<(PathBuf, Option<Blob>)>,
+}
+
+/// The first-parent history of `revision` that touches `directory`, or only `file` in
+/// it when given, oldest commit first. Paths are relative to `directory`.
+pub(crate) fn history(
+    directory: &Path,
+    revision: &str,
+    file: Option<&OsStr>,
+) -> Result<Vec<Commit>, String> {
+    let pathspec = file.unwrap_or(OsStr::new("."));
+    let args = [
+        OsStr::new("log"),
+        OsStr::new("--first-parent"),
+        OsStr::new("--reverse"),
+        OsStr::new("--format=%H %P%x09%s"),
+        OsStr::new(revision),
+        OsStr::new("--"),
+        pathspec,
+    ];
+    let log = output(directory, args)?;
+    let mut commits = Vec::new();
+    for line in String::from_utf8_lossy(&log).lines() {
+        let (ids, subject) = line.split_once('\t').unwrap_or((line, ""));
+        let mut ids = ids.split(' ');
+        let hash = ids.next().unwrap_or_default().to_string();
+        // `--relative` keeps paths relative to, and within, the directory.
+        let mut args = vec![
+            OsStr::new("diff-tree"),
+            OsStr::new("-r"),
+            OsStr::new("-z"),
+            OsStr::new("--no-commit-id"),
+            OsStr::new("--no-renames"),
+            OsStr::new("--relative"),
+        ];
+        match ids.next().filter(|parent| !parent.is_empty()) {
+            Some(parent) => args.push(OsStr::new(parent)),
+            None => args.push(OsStr::new("--root")),
+        }
+        args.extend([OsStr::new(&hash), OsStr::new("--"), pathspec]);
+        let diff = output(directory, args)?;
+        commits.push(Commit {
+            changes: changes(&diff),
+            hash,
+            subject: subject.to_string(),
+        });
+    }
+    Ok(commits)
+}
+
+/// Parses `diff-tree -z` output: `:<mode> <mode> <object> <object> <status>` then the
+/// path, each ended by a NUL.
+fn changes(diff: &[u8]) -> Vec<(PathBuf, Option<Blob>)> {
+    let mut fields = diff.split(|&byte| byte == 0);
+    let mut changes = Vec::new();
+    while let (Some(header), Some(name)) = (fields.next(), fields.next()) {
+        let header = String::from_utf8_lossy(header);
+        let [_, mode, _, object, status] = header.split(' ').collect::<Vec<_>>()[..] else {
+            continue;
+        };
+        let path = path_from_bytes(name);
+        let blob = match (status, mode) {
+            ("D", _) => None,
+            (_, "100644" | "100755") => Some(Blob {
+                path: path.clone(),
+                object: object.to_string(),
+            }),
+            // A file that became a symlink or submodule is gone as far as the walk is
+            // concerned.
+            _ => None,
+        };
+        changes.push((path, blob));
+    }
+    changes
+}
+
+/// Every regular file of `revision` under `directory`, or only `file` in it when given.
+/// Symlinks and submodules are left out, as the walk leaves out what isn't a file.
+pub(crate) fn tree(
+    directory: &Path,
+    revision: &str,
+    file: Option<&OsStr>,
+) -> Result<Vec<Blob>, String> {
+    let pathspec = file.unwrap_or(OsStr::new("."));
+    let args = [
+        OsStr::new("ls-tree"),
+        OsStr::new("-r"),
+        OsStr::new("-z"),
+        OsStr::new(revision),
+        OsStr::new("--"),
+        pathspec,
+    ];
+    let listing = output(directory, args)?;
+    // Each entry is `<mode> <type> <object>\t<path>`.
+    let blobs = listing
+        .split(|&byte| byte == 0)
+        .filter_map(|entry| {
+            let tab = entry.iter().position(|&byte| byte == b'\t')?;
+            let fields = std::str::from_utf8(&entry[..tab]).ok()?;
+            match fields.split(' ').collect::<Vec<_>>()[..] {
+                ["100644" | "100755", "blob", object] => Some(Blob {
+                    path: path_from_bytes(&entry[tab + 1..]),
+                    object: object.to_string(),
+                }),
+                _ => None,
+            }
+        })
+        .collect();
+    Ok(blobs)
+}
+
+/// Reads the contents of blobs through one `git cat-file --batch`.
+pub(crate) struct Contents {
+    child: Child,
+    requests: ChildStdin,
+    responses: BufReader<ChildStdout>,
+}
+
+impl Contents {
+    pub(crate) fn open(directory: &Path) -> std::io::Result<Contents> {
+        let mut child = Command::new("git")
+            .arg("-C")
+            .arg(directory)
+            .args(["cat-file", "--batch"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let requests = child.stdin.take().unwrap();
+        let responses = BufReader::new(child.stdout.take().unwrap());
+        Ok(Contents {
+            child,
+            requests,
+            responses,
+        })
+    }
+
+    pub(crate) fn read(&mut self, blob: &Blob) -> std::io::Result<Vec<u8>> {
+        writeln!(self.requests, "{}", blob.object)?;
+        self.requests.flush()?;
+        // The reply is `<object> blob <size>\n`, the contents, then a newline.
+        let mut header = String::new();
+        self.responses.read_line(&mut header)?;
+        let size = header
+            .split(' ')
+            .nth(2)
+            .and_then(|size| size.trim().parse::<usize>().ok())
+            .ok_or_else(|| {
+                let message = format!("unexpected reply from git: {}", header.trim());
+                std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+            })?;
+        let mut contents = vec![0; size + 1];
+        self.responses.read_exact(&mut contents)?;
+        contents.pop();
+        Ok(contents)
+    }
+}
+
+impl Drop for Contents {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// git prints paths as raw bytes.
+#[cfg(unix)]
+pub(crate) fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+/// Elsewhere they're taken as UTF-8, with git's `/` swapped for the native separator.
+#[cfg(not(unix))]
+pub(crate) fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    let path = String::from_utf8_lossy(bytes);
+    PathBuf::from(path.replace('/', std::path::MAIN_SEPARATOR_STR))
+}
@@ -0,0 +1,37 @@
+//! GitHub Actions workflow commands for `--format github`: a `::warning` per match, which
+//! the Actions runner shows as an annotation on the matched line of a pull request.
+
+/// A `::warning` annotation for a match of `pattern` at a 1-based line and column of
+/// `path`, which GitHub expects relative to the checkout.
+pub fn annotation(
+    path: &str,
+    line: usize,
+    column: usize,
+    end_column: Option<usize>,
+    pattern: &str,
+    matched: &str,
+) -> String {
+    let end_column = end_column.map_or(String::new(), |end| format!(",endColumn={}", end));
+    format!(
+        "::warning file={},line={},col={}{},title={}::{}",
+        property(path),
+        line,
+        column,
+        end_column,
+        property(pattern),
+        data(&format!("Found {}", matched))
+    )
+}
+
+/// Escapes a command's message, which can't hold line breaks.
+fn data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes a property value, which can't hold the `:` and `,` that delimit properties
+/// either.
+fn property(text: &str) -> String {
+    data(text).replace(':', "%3A").replace(',', "%2C")
+}
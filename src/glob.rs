@@ -0,0 +1,245 @@
+//! Gitignore-style globs, shared by ignore files and the `-g`/`--pre-glob` filters.
+
+use std::path::Path;
+
+use regex::Regex;
+
+/// One compiled glob, matched against `/`-separated paths relative to some base.
+pub struct Glob {
+    regex: Regex,
+    /// `!pattern`: the glob's meaning is inverted (re-included, or excluded for `-g`).
+    pub negated: bool,
+    /// `pattern/`: only matches directories.
+    dir_only: bool,
+}
+
+impl Glob {
+    /// Parses one gitignore-style line; `#` comments are left to the caller.
+    pub fn parse(line: &str) -> Option<Glob> {
+        if line.is_empty() {
+            return None;
+        }
+
+        // Trailing spaces are insignificant unless escaped with a backslash.
+        let mut pattern = line.trim_end_matches(' ');
+        if pattern.ends_with('\\') && pattern.len() < line.len() {
+            pattern = &line[..pattern.len() + 1];
+        }
+        // A leading `\!` or `\#` is an escape, which `to_regex` handles like any other.
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        if pattern.is_empty() {
+            return None;
+        }
+
+        // A slash anywhere but the end anchors the pattern to the base directory;
+        // otherwise it may match at any depth.
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        let prefix = match anchored {
+            true => "^",
+            false => "^(?:.*/)?",
+        };
+        let regex = Regex::new(&format!("{}{}$", prefix, to_regex(pattern))).ok()?;
+        Some(Glob {
+            regex,
+            negated,
+            dir_only,
+        })
+    }
+
+    pub fn matches(&self, relative: &str, is_dir: bool) -> bool {
+        (is_dir || !self.dir_only) && self.regex.is_match(relative)
+    }
+}
+
+/// `-g` filters, relative to the search root. The last matching glob decides: plain
+/// globs include what they match and `!` globs exclude it. Once any plain glob is given,
+/// files no glob matches are excluded as well.
+#[derive(Default)]
+pub struct Globs {
+    globs: Vec<Glob>,
+}
+
+impl Globs {
+    /// Adds a glob, or returns `None` if it is invalid.
+    pub fn add(&mut self, glob: &str) -> Option<()> {
+        self.globs.push(Glob::parse(glob)?);
+        Some(())
+    }
+
+    /// `Some(true)` if the path is explicitly included, `Some(false)` if it's excluded,
+    /// and `None` if the globs leave it to the ignore rules.
+    pub fn matched(&self, base: &Path, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = relative(base, path)?;
+        let last = self
+            .globs
+            .iter()
+            .rev()
+            .find(|glob| glob.matches(&relative, is_dir));
+        match last {
+            Some(glob) => Some(!glob.negated),
+            // Directories are always entered, or included files inside them couldn't be.
+            None if !is_dir && self.globs.iter().any(|glob| !glob.negated) => Some(false),
+            None => None,
+        }
+    }
+}
+
+/// `path` relative to `base`, with components joined by `/` on every platform.
+pub fn relative(base: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(base).ok()?;
+    let components = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>();
+    Some(components.join("/"))
+}
+
+/// Translates gitignore glob syntax into an equivalent regex: `*` and `?` never cross a
+/// `/`, `**` spans directories when it forms a whole path component, and `[...]`
+/// classes pass through with `!` negation.
+pub fn to_regex(glob: &str) -> String {
+    let mut regex = String::new();
+    let chars = glob.chars().collect::<Vec<_>>();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let starts_component = i == 0 || chars[i - 1] == '/';
+                let next = chars.get(i + 2);
+                if starts_component && next == Some(&'/') {
+                    // `**/`: zero or more leading directories.
+                    regex.push_str("(?:.*/)?");
+                    i += 3;
+                } else if starts_component && next.is_none() {
+                    // Trailing `/**`: everything inside.
+                    regex.push_str(".*");
+                    i += 2;
+                } else {
+                    regex.push_str("[^/]*");
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => match chars[i + 1..].iter().position(|&c| c == ']') {
+                Some(length) if length > 0 => {
+                    let class = chars[i + 1..i + 1 + length].iter().collect::<String>();
+                    let class = match class.strip_prefix('!') {
+                        Some(negated) => format!("^{}", negated),
+                        None => class,
+                    };
+                    regex.push('[');
+                    regex.push_str(&class.replace('\\', "\\\\").replace('[', "\\["));
+                    regex.push(']');
+                    i += length + 2;
+                    continue;
+                }
+                _ => regex.push_str("\\["),
+            },
+            '\\' if i + 1 < chars.len() => {
+                regex.push_str(&regex::escape(&chars[i + 1].to_string()));
+                i += 2;
+                continue;
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(glob: &str, relative: &str) -> bool {
+        Glob::parse(glob).unwrap().matches(relative, false)
+    }
+
+    #[test]
+    fn stars_stay_within_a_component() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(matches("*.rs", "src/main.rs"));
+        assert!(!matches("src/*.rs", "src/bin/main.rs"));
+        assert!(matches("a?c", "abc"));
+        assert!(!matches("a?c", "a/c"));
+    }
+
+    #[test]
+    fn double_stars_span_directories() {
+        assert!(matches("**/test.rs", "test.rs"));
+        assert!(matches("**/test.rs", "a/b/test.rs"));
+        assert!(matches("src/**", "src/a/b.rs"));
+        assert!(matches("a/**/z", "a/z"));
+        assert!(matches("a/**/z", "a/b/c/z"));
+        // Inside a component, `**` is an ordinary star.
+        assert!(!matches("a**z", "a/z"));
+    }
+
+    #[test]
+    fn leading_slash_anchors() {
+        assert!(matches("/Cargo.toml", "Cargo.toml"));
+        assert!(!matches("/Cargo.toml", "sub/Cargo.toml"));
+    }
+
+    #[test]
+    fn classes_and_escapes() {
+        assert!(matches("[ab].txt", "a.txt"));
+        assert!(!matches("[ab].txt", "c.txt"));
+        assert!(matches("[!ab].txt", "c.txt"));
+        assert!(!matches("[!ab].txt", "a.txt"));
+        assert!(matches("\\*.txt", "*.txt"));
+        assert!(!matches("\\*.txt", "a.txt"));
+        assert!(matches("\\#notes", "#notes"));
+        // An unclosed bracket is literal.
+        assert!(matches("[a", "[a"));
+    }
+
+    #[test]
+    fn trailing_spaces() {
+        assert!(matches("a.txt  ", "a.txt"));
+        assert!(matches("a\\ ", "a "));
+    }
+
+    #[test]
+    fn empty_patterns_are_rejected() {
+        assert!(Glob::parse("").is_none());
+        assert!(Glob::parse("!").is_none());
+        assert!(Glob::parse("/").is_none());
+    }
+
+    #[test]
+    fn last_glob_decides() {
+        let mut globs = Globs::default();
+        globs.add("*.rs").unwrap();
+        globs.add("!generated.rs").unwrap();
+        let base = Path::new("/root");
+        let matched = |path: &str, is_dir| globs.matched(base, &base.join(path), is_dir);
+        assert_eq!(matched("src/main.rs", false), Some(true));
+        assert_eq!(matched("src/generated.rs", false), Some(false));
+        // With an inclusion given, unmatched files are left out but directories entered.
+        assert_eq!(matched("README.md", false), Some(false));
+        assert_eq!(matched("src", true), None);
+    }
+
+    #[test]
+    fn exclusions_alone_leave_the_rest() {
+        let mut globs = Globs::default();
+        globs.add("!*.min.js").unwrap();
+        let base = Path::new("/root");
+        assert_eq!(
+            globs.matched(base, &base.join("app.min.js"), false),
+            Some(false)
+        );
+        assert_eq!(globs.matched(base, &base.join("app.js"), false), None);
+    }
+}
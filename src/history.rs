@@ -0,0 +1,136 @@
+//! `--git-log`: walking a file's history to find when the pattern arrived in it and
+//! when it was last there, like a pickaxe search that reports per file.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config::SearchConfig;
+use crate::git;
+use crate::matcher::Matcher;
+use crate::theme::Element;
+
+/// Where the pattern has been found in one file.
+struct Span<'a> {
+    first: &'a git::Commit,
+    /// The last commit the file matched in, once it has stopped matching.
+    last: Option<&'a git::Commit>,
+    matching: bool,
+}
+
+/// Prints, for every file of `path`'s history the pattern ever matched in, the first
+/// commit it matched in and the last. Walks the first-parent history of `--rev`, or of
+/// `HEAD`; every version of a file is searched once, however many commits share it.
+/// Returns whether any file matched.
+pub(crate) fn match_history(
+    matcher: &Matcher,
+    path: &Path,
+    app: &SearchConfig,
+    writer: &mut Vec<u8>,
+) -> bool {
+    let (directory, file) = match path.is_dir() {
+        true => (path, None),
+        false => (path.parent().unwrap_or(path), path.file_name()),
+    };
+    let revision = app.revision.as_deref().unwrap_or("HEAD");
+    let commits = match git::history(directory, revision, file) {
+        Ok(commits) => commits,
+        Err(err) => {
+            app.error(&format!(
+                "Failed to read the history of {}: {}",
+                revision, err
+            ));
+            return false;
+        }
+    };
+    let mut contents = match git::Contents::open(directory) {
+        Ok(contents) => contents,
+        Err(err) => {
+            app.error(&format!("Failed to run git: {}", err));
+            return false;
+        }
+    };
+
+    // Whether each version matches, by blob id.
+    let mut versions: HashMap<String, bool> = HashMap::new();
+    let mut spans: Vec<(PathBuf, Span)> = Vec::new();
+    let mut previous: Option<&git::Commit> = None;
+    for commit in &commits {
+        for (name, blob) in &commit.changes {
+            if crate::searcher::committed_skip(directory, name, app).is_some() {
+                continue;
+            }
+            let matches = match blob {
+                Some(blob) => match versions.get(&blob.object) {
+                    Some(&matches) => matches,
+                    None => {
+                        let matches = match contents.read(blob) {
+                            // Too large to search, so as if the commit hadn't changed it.
+                            Ok(data)
+                                if app.max_filesize.is_some_and(|max| data.len() as u64 > max) =>
+                            {
+                                continue;
+                            }
+                            Ok(data) => data
+                                .split(|&byte| byte == b'\n')
+                                .any(|line| matcher.is_match(line)),
+                            Err(err) => {
+                                let label = format!("{}:{}", commit.hash, name.display());
+                                app.error(&format!("Failed to read {}: {}", label, err));
+                                false
+                            }
+                        };
+                        versions.insert(blob.object.clone(), matches);
+                        matches
+                    }
+                },
+                None => false,
+            };
+            match spans.iter_mut().find(|(path, _)| path == name) {
+                Some((_, span)) if matches && !span.matching => {
+                    span.matching = true;
+                    span.last = None;
+                }
+                Some((_, span)) if !matches && span.matching => {
+                    span.matching = false;
+                    span.last = previous;
+                }
+                Some(_) => {}
+                None if matches => spans.push((
+                    name.clone(),
+                    Span {
+                        first: commit,
+                        last: None,
+                        matching: true,
+                    },
+                )),
+                None => {}
+            }
+        }
+        previous = Some(commit);
+    }
+
+    for (name, span) in &spans {
+        let path = directory.join(name).display().to_string();
+        let last = match span.last {
+            Some(last) => describe(last),
+            None => format!("{} (still present)", describe(previous.unwrap())),
+        };
+        let path = app.theme.paint(Element::Path, &path);
+        writeln!(
+            writer,
+            "{}: first {}; last {}",
+            path,
+            describe(span.first),
+            last
+        )
+        .unwrap();
+    }
+    !spans.is_empty()
+}
+
+/// A commit's short hash and subject.
+fn describe(commit: &git::Commit) -> String {
+    let short = &commit.hash[..commit.hash.len().min(7)];
+    format!("{} {}", short, commit.subject)
+}
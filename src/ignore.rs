@@ -0,0 +1,393 @@
+//! Native ignore file matching, so traversal never has to ask `git` about each path.
+//!
+//! Every ignore file is compiled into a list of rules scoped to the directory holding
+//! it. While walking, an [`Ignore`] carries the files that apply to the current
+//! directory. Sources are consulted by precedence: `.yagrepignore`, then `.ignore`,
+//! then `.gitignore`, then the repository's `info/exclude`, then the user's global
+//! `core.excludesFile`. Within a source the deepest file with a matching rule decides,
+//! and within a file the last matching rule wins, as in git.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::glob::Glob;
+use crate::tracked::Tracked;
+
+/// The rules of one ignore file, matched against paths relative to `base`.
+pub struct Rules {
+    base: PathBuf,
+    rules: Vec<Glob>,
+}
+
+impl Rules {
+    /// Reads and compiles `path`, or returns `None` if it can't be read.
+    pub fn from_file(base: &Path, path: &Path) -> Option<Rules> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        Some(Rules::parse(base, &contents))
+    }
+
+    pub fn parse(base: &Path, contents: &str) -> Rules {
+        let rules = contents
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(Glob::parse)
+            .collect();
+        Rules {
+            base: base.to_path_buf(),
+            rules,
+        }
+    }
+
+    /// `Some(true)` if the last matching rule ignores the path, `Some(false)` if it
+    /// re-includes it, and `None` if no rule matches.
+    fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = crate::glob::relative(&self.base, path)?;
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(&relative, is_dir))
+            .map(|rule| !rule.negated)
+    }
+}
+
+/// Where a set of rules came from, from highest precedence to lowest.
+#[derive(Clone, Copy, PartialEq)]
+enum Source {
+    /// `.yagrepignore`: rules specific to this tool.
+    Yagrepignore,
+    /// `.ignore`: rules shared with other search tools, honored outside git too.
+    Ignore,
+    Gitignore,
+    /// `$GIT_DIR/info/exclude`, scoped to the repository root.
+    GitExclude,
+    /// The user's `core.excludesFile`, scoped to the repository root.
+    GitGlobal,
+}
+
+const SOURCES: [Source; 5] = [
+    Source::Yagrepignore,
+    Source::Ignore,
+    Source::Gitignore,
+    Source::GitExclude,
+    Source::GitGlobal,
+];
+
+/// The ignore files in effect for one directory of the walk.
+#[derive(Clone)]
+pub struct Ignore {
+    /// Honor `.gitignore` files.
+    git: bool,
+    /// Honor `.ignore` and `.yagrepignore` files.
+    dot: bool,
+    /// Whether the directory is inside a git repository; `.gitignore` files only apply
+    /// there.
+    in_repo: bool,
+    /// Rules from every source, shallowest directory first.
+    rules: Vec<(Source, Arc<Rules>)>,
+    /// With `--git-tracked`, the only files the walk may visit.
+    tracked: Option<Arc<Tracked>>,
+}
+
+impl Ignore {
+    /// Builds the rules for a search rooted at `directory`, including the ignore files of
+    /// its parents: `.gitignore` up to the repository root, the others up to `/`.
+    pub fn new(directory: &Path, git: bool, dot: bool) -> Ignore {
+        let repo_root = directory.ancestors().find(|dir| dir.join(".git").exists());
+        let mut ignore = Ignore {
+            git,
+            dot,
+            in_repo: repo_root.is_some(),
+            rules: Vec::new(),
+            tracked: None,
+        };
+        if !git && !dot {
+            return ignore;
+        }
+
+        if let Some(repo_root) = repo_root.filter(|_| git) {
+            ignore.add_repo_excludes(repo_root);
+            let global =
+                global_excludes_file(repo_root).and_then(|path| Rules::from_file(repo_root, &path));
+            if let Some(rules) = global {
+                ignore.rules.push((Source::GitGlobal, Arc::new(rules)));
+            }
+        }
+        let parents = directory.ancestors().collect::<Vec<_>>();
+        for dir in parents.into_iter().rev() {
+            let in_repo = repo_root.is_some_and(|root| dir.starts_with(root));
+            ignore.add_files(dir, in_repo, |path| std::fs::read_to_string(path).ok());
+        }
+        ignore
+    }
+
+    /// Returns the rules for `directory`, a child of the directory `self` applies to.
+    pub fn descend(&self, directory: &Path) -> Ignore {
+        let mut ignore = self.clone();
+        if !self.git && !self.dot {
+            return ignore;
+        }
+
+        // A nested repository (e.g. a submodule) doesn't inherit its parent's gitignores.
+        if directory.join(".git").exists() {
+            ignore.in_repo = true;
+            ignore
+                .rules
+                .retain(|(source, _)| !matches!(source, Source::Gitignore | Source::GitExclude));
+            if self.git {
+                ignore.add_repo_excludes(directory);
+            }
+        }
+        ignore.add_files(directory, ignore.in_repo, |path| {
+            std::fs::read_to_string(path).ok()
+        });
+        ignore
+    }
+
+    /// The rules for a walk of files held in memory (see `vfs`): nothing is inherited
+    /// from the disk, and `.gitignore` files apply without a repository.
+    pub fn in_memory(git: bool, dot: bool) -> Ignore {
+        Ignore {
+            git,
+            dot,
+            in_repo: true,
+            rules: Vec::new(),
+            tracked: None,
+        }
+    }
+
+    /// Restricts the walk to `tracked`, on top of the ignore files.
+    pub(crate) fn with_tracked(self, tracked: Tracked) -> Ignore {
+        Ignore {
+            tracked: Some(Arc::new(tracked)),
+            ..self
+        }
+    }
+
+    /// Whether `--git-tracked` rules `path` out. Unlike the ignore rules, `-g` can't
+    /// bring it back.
+    pub(crate) fn is_untracked(&self, path: &Path, is_dir: bool) -> bool {
+        self.tracked
+            .as_ref()
+            .is_some_and(|tracked| !tracked.contains(path, is_dir))
+    }
+
+    /// Like `descend`, reading the directory's ignore files with `read` instead of from
+    /// the disk.
+    pub fn descend_with(&self, directory: &Path, read: impl Fn(&Path) -> Option<String>) -> Ignore {
+        let mut ignore = self.clone();
+        ignore.add_files(directory, ignore.in_repo, read);
+        ignore
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        SOURCES
+            .iter()
+            .find_map(|&wanted| {
+                self.rules
+                    .iter()
+                    .rev()
+                    .filter(|(source, _)| *source == wanted)
+                    .find_map(|(_, rules)| rules.matched(path, is_dir))
+            })
+            .unwrap_or(false)
+    }
+
+    fn add_repo_excludes(&mut self, repo_root: &Path) {
+        let exclude = git_dir(repo_root)
+            .and_then(|git_dir| Rules::from_file(repo_root, &git_dir.join("info").join("exclude")));
+        if let Some(rules) = exclude {
+            self.rules.push((Source::GitExclude, Arc::new(rules)));
+        }
+    }
+
+    fn add_files(
+        &mut self,
+        directory: &Path,
+        in_repo: bool,
+        read: impl Fn(&Path) -> Option<String>,
+    ) {
+        let files = [
+            (Source::Gitignore, ".gitignore", self.git && in_repo),
+            (Source::Ignore, ".ignore", self.dot),
+            (Source::Yagrepignore, ".yagrepignore", self.dot),
+        ];
+        for (source, name, enabled) in files {
+            if !enabled {
+                continue;
+            }
+            if let Some(contents) = read(&directory.join(name)) {
+                let rules = Rules::parse(directory, &contents);
+                self.rules.push((source, Arc::new(rules)));
+            }
+        }
+    }
+}
+
+/// The git directory of a repository: `.git` itself, or the `gitdir:` a `.git` file
+/// points to in worktrees and submodules.
+fn git_dir(repo_root: &Path) -> Option<PathBuf> {
+    let dot_git = repo_root.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let contents = std::fs::read_to_string(&dot_git).ok()?;
+    let git_dir = contents.trim().strip_prefix("gitdir:")?.trim();
+    Some(repo_root.join(git_dir))
+}
+
+/// Finds the user's global ignore file: `core.excludesFile` from the repository,
+/// global or XDG git config (later files win), defaulting to `$XDG_CONFIG_HOME/git/ignore`.
+fn global_excludes_file(repo_root: &Path) -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let xdg_config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| home.as_ref().map(|home| home.join(".config")));
+
+    let configs = [
+        xdg_config
+            .as_ref()
+            .map(|config| config.join("git").join("config")),
+        home.as_ref().map(|home| home.join(".gitconfig")),
+        git_dir(repo_root).map(|git_dir| git_dir.join("config")),
+    ];
+    let configured = configs
+        .iter()
+        .rev()
+        .flatten()
+        .find_map(|config| config_excludes_file(config));
+
+    let path = configured.or_else(|| xdg_config.map(|config| config.join("git").join("ignore")))?;
+    // `~/` is expanded by git itself, so config files commonly rely on it.
+    match (path.strip_prefix("~"), &home) {
+        (Ok(rest), Some(home)) => Some(home.join(rest)),
+        _ => Some(path),
+    }
+}
+
+/// Reads `core.excludesFile` from one git config file. Only the plain `key = value`
+/// form is understood, which is what `git config` writes.
+fn config_excludes_file(config: &Path) -> Option<PathBuf> {
+    let contents = std::fs::read_to_string(config).ok()?;
+    let mut in_core = false;
+    let mut excludes_file = None;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_core = line
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .trim()
+                .eq_ignore_ascii_case("core");
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if in_core && key.trim().eq_ignore_ascii_case("excludesfile") {
+            let value = value.trim().trim_matches('"');
+            excludes_file = Some(PathBuf::from(value));
+        }
+    }
+    excludes_file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Rules {
+        Rules::parse(Path::new("/repo"), contents)
+    }
+
+    fn matched(rules: &Rules, path: &str, is_dir: bool) -> Option<bool> {
+        rules.matched(&Path::new("/repo").join(path), is_dir)
+    }
+
+    #[test]
+    fn last_matching_rule_wins() {
+        let rules = parse("*.log\n!keep.log\n");
+        assert_eq!(matched(&rules, "debug.log", false), Some(true));
+        assert_eq!(matched(&rules, "keep.log", false), Some(false));
+        assert_eq!(matched(&rules, "sub/keep.log", false), Some(false));
+        assert_eq!(matched(&rules, "main.rs", false), None);
+
+        // A later rule takes the file back out again.
+        let rules = parse("*.log\n!keep.log\nkeep.log\n");
+        assert_eq!(matched(&rules, "keep.log", false), Some(true));
+    }
+
+    #[test]
+    fn escaped_bang_is_literal() {
+        let rules = parse("\\!important\n");
+        assert_eq!(matched(&rules, "!important", false), Some(true));
+        assert_eq!(matched(&rules, "important", false), None);
+    }
+
+    #[test]
+    fn slash_anchors_to_the_file_directory() {
+        let rules = parse("/build\ndocs/*.html\n");
+        assert_eq!(matched(&rules, "build", true), Some(true));
+        assert_eq!(matched(&rules, "src/build", true), None);
+        assert_eq!(matched(&rules, "docs/index.html", false), Some(true));
+        assert_eq!(matched(&rules, "site/docs/index.html", false), None);
+        assert_eq!(matched(&rules, "docs/api/index.html", false), None);
+    }
+
+    #[test]
+    fn unanchored_rules_match_at_any_depth() {
+        let rules = parse("target\n");
+        assert_eq!(matched(&rules, "target", true), Some(true));
+        assert_eq!(matched(&rules, "crates/core/target", true), Some(true));
+    }
+
+    #[test]
+    fn trailing_slash_only_matches_directories() {
+        let rules = parse("cache/\n");
+        assert_eq!(matched(&rules, "cache", true), Some(true));
+        assert_eq!(matched(&rules, "cache", false), None);
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let rules = parse("# *.rs\n\n*.tmp\n");
+        assert_eq!(matched(&rules, "main.rs", false), None);
+        assert_eq!(matched(&rules, "a.tmp", false), Some(true));
+    }
+
+    #[test]
+    fn paths_outside_the_base_are_unmatched() {
+        let rules = parse("*\n");
+        assert_eq!(rules.matched(Path::new("/elsewhere/a.txt"), false), None);
+    }
+
+    #[test]
+    fn deeper_files_override_shallower_ones() {
+        let files = |path: &Path| match path.to_str()? {
+            ".gitignore" => Some("*.txt\n".to_string()),
+            "sub/.gitignore" => Some("!notes.txt\n".to_string()),
+            _ => None,
+        };
+        let root = Ignore::in_memory(true, true).descend_with(Path::new(""), files);
+        let sub = root.descend_with(Path::new("sub"), files);
+        assert!(root.is_ignored(Path::new("notes.txt"), false));
+        assert!(sub.is_ignored(Path::new("sub/other.txt"), false));
+        assert!(!sub.is_ignored(Path::new("sub/notes.txt"), false));
+    }
+
+    #[test]
+    fn ignore_files_outrank_gitignore() {
+        let files = |path: &Path| match path.to_str()? {
+            ".gitignore" => Some("*.txt\n".to_string()),
+            ".ignore" => Some("!keep.txt\n".to_string()),
+            _ => None,
+        };
+        let ignore = Ignore::in_memory(true, true).descend_with(Path::new(""), files);
+        assert!(!ignore.is_ignored(Path::new("keep.txt"), false));
+        assert!(ignore.is_ignored(Path::new("other.txt"), false));
+
+        // Without `.ignore` files, only `.gitignore` applies.
+        let ignore = Ignore::in_memory(true, false).descend_with(Path::new(""), files);
+        assert!(ignore.is_ignored(Path::new("keep.txt"), false));
+    }
+}
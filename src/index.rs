@@ -0,0 +1,365 @@
+//! The trigram index `yagrep index build` writes to `.yagrep-index`: for every file the
+//! default walk visits, the three-byte sequences it contains. A search of the directory,
+//! or of one below it, asks the index which files hold every trigram of the prefilter's
+//! literal and searches the rest as if empty, without reading them.
+//!
+//! Each file is recorded with its size and modification time, and is only ruled out
+//! while both are unchanged and older than the index; a file that has changed since, or
+//! that the index doesn't know, is searched as usual. The index is only ever a shortcut,
+//! never the list of files to search. `yagrep index watch` rescans the tree as it
+//! changes, reindexing a file only when the checksum of its contents has changed too.
+//!
+//! The file is little-endian: [`MAGIC`], the file count and, per file, its path's length,
+//! path, size, modification seconds and nanoseconds, and checksum; then the trigram count
+//! and, per trigram in ascending order, the trigram and how many files hold it; then
+//! those files' ids, trigram by trigram.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::config::SearchConfig;
+use crate::matcher::Matcher;
+use crate::{encoding, git, lines, walker};
+
+/// The index's name in the directory it describes.
+pub(crate) const FILE_NAME: &str = ".yagrep-index";
+
+const MAGIC: &[u8; 8] = b"YGINDEX2";
+
+/// When a file was indexed, to tell whether it has changed since.
+#[derive(PartialEq)]
+struct Stamp {
+    size: u64,
+    modified: SystemTime,
+}
+
+impl Stamp {
+    fn of(metadata: &std::fs::Metadata) -> Option<Stamp> {
+        Some(Stamp {
+            size: metadata.len(),
+            modified: metadata.modified().ok()?,
+        })
+    }
+}
+
+/// Every indexed file by path, with its id and stamp.
+type Files = HashMap<PathBuf, (usize, Stamp)>;
+
+/// What the index says about one search: which of the files it knows can't match.
+pub(crate) struct Index {
+    /// The directory being searched, as the walk names it.
+    directory: PathBuf,
+    /// Where that directory is below the one the index describes.
+    offset: PathBuf,
+    files: Files,
+    /// By file id, whether the file holds every trigram of the literal.
+    candidates: Vec<bool>,
+}
+
+impl Index {
+    /// The index for a search of `directory`: the nearest `.yagrep-index` in it or above
+    /// it. `None` without one, with `--no-index`, or when no file can be ruled out: the
+    /// pattern has no literal of three bytes or more, or the options search files that
+    /// don't match (`-v`, `--passthru`) or search other bytes than the file's own
+    /// (`--binary`, `--encoding`).
+    pub(crate) fn open(directory: &Path, matcher: &Matcher, app: &SearchConfig) -> Option<Index> {
+        let transcoded = app
+            .encoding
+            .is_some_and(|encoding| encoding != encoding::Encoding::Utf8);
+        if !app.index || app.binary || app.invert_match || app.passthru || transcoded {
+            return None;
+        }
+        let literal = matcher.literal().filter(|literal| literal.len() >= 3)?;
+        let absolute = directory.canonicalize().ok()?;
+        let root = absolute
+            .ancestors()
+            .find(|ancestor| ancestor.join(FILE_NAME).is_file())?;
+        let path = root.join(FILE_NAME);
+        match read(&path, literal) {
+            Ok((files, candidates)) => Some(Index {
+                directory: directory.to_path_buf(),
+                offset: absolute.strip_prefix(root).ok()?.to_path_buf(),
+                files,
+                candidates,
+            }),
+            Err(err) => {
+                app.warning(&format!("Ignoring the index {}: {}", path.display(), err));
+                None
+            }
+        }
+    }
+
+    /// Whether the file at `path`, reached by the walk, certainly has no match: it is
+    /// unchanged since it was indexed and lacks one of the literal's trigrams.
+    pub(crate) fn excludes(&self, path: &Path, metadata: &std::fs::Metadata) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.directory) else {
+            return false;
+        };
+        match self.files.get(&self.offset.join(relative)) {
+            Some((id, stamp)) => {
+                !self.candidates[*id] && Stamp::of(metadata).as_ref() == Some(stamp)
+            }
+            None => false,
+        }
+    }
+}
+
+/// Reads the file table of the index at `path`, and which files hold every trigram of
+/// `literal`.
+fn read(path: &Path, literal: &[u8]) -> std::io::Result<(Files, Vec<bool>)> {
+    let mut reader = BufReader::new(std::fs::File::open(path)?);
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a yagrep index, or one of another version"));
+    }
+    // A file changed in the same tick the index was written in may have changed after
+    // it was read, without its stamp showing it, so only older stamps are trusted.
+    let written = reader.get_ref().metadata()?.modified()?;
+    let mut files = HashMap::new();
+    let file_count = read_u32(&mut reader)? as usize;
+    for id in 0..file_count {
+        let mut name = vec![0; read_u32(&mut reader)? as usize];
+        reader.read_exact(&mut name)?;
+        let size = read_u64(&mut reader)?;
+        let modified = Duration::new(read_u64(&mut reader)?, read_u32(&mut reader)?);
+        let _checksum = read_u64(&mut reader)?;
+        let stamp = Stamp {
+            size,
+            modified: SystemTime::UNIX_EPOCH + modified,
+        };
+        if stamp.modified < written {
+            files.insert(git::path_from_bytes(&name), (id, stamp));
+        }
+    }
+
+    let mut wanted = trigrams(literal);
+    wanted.sort_unstable();
+    wanted.dedup();
+    // Where each wanted trigram's files are, counted in ids after the trigram table.
+    let mut postings = Vec::new();
+    let mut position = 0;
+    let trigram_count = read_u32(&mut reader)?;
+    for _ in 0..trigram_count {
+        let (trigram, count) = (read_u32(&mut reader)?, read_u32(&mut reader)? as u64);
+        if wanted.binary_search(&trigram).is_ok() {
+            postings.push((position, count));
+        }
+        position += count;
+    }
+    let mut candidates = vec![postings.len() == wanted.len(); file_count];
+    if postings.len() < wanted.len() {
+        // A trigram no file holds rules every file out.
+        return Ok((files, candidates));
+    }
+    let start = reader.stream_position()?;
+    for (position, count) in postings {
+        reader.seek(SeekFrom::Start(start + position * 4))?;
+        let mut holding = vec![false; file_count];
+        for _ in 0..count {
+            if let Some(holds) = holding.get_mut(read_u32(&mut reader)? as usize) {
+                *holds = true;
+            }
+        }
+        for (candidate, holds) in candidates.iter_mut().zip(holding) {
+            *candidate &= holds;
+        }
+    }
+    Ok((files, candidates))
+}
+
+/// What the index holds for one file.
+struct Entry {
+    stamp: Stamp,
+    /// A hash of the contents, so a file whose stamp changed but whose contents didn't
+    /// isn't reindexed.
+    checksum: u64,
+    /// Sorted, without repeats; `None` for a file that isn't plain text, which is left
+    /// out of the index but remembered so it isn't read again until it changes.
+    trigrams: Option<Vec<u32>>,
+}
+
+/// Indexes every file the default walk visits under `directory` into its
+/// `.yagrep-index`, returning how many files were indexed. Files that aren't plain UTF-8
+/// text (binary, or with a UTF-16 byte order mark) are left out, so searches always read
+/// them. Files that can't be read are reported and left out too.
+pub fn build_index(directory: &Path) -> Result<usize, String> {
+    let mut entries = BTreeMap::new();
+    scan(directory, &mut entries)?;
+    write(directory, &entries)
+}
+
+/// Keeps `directory`'s `.yagrep-index` up to date (`yagrep index watch`): indexes it as
+/// [`build_index`] does, then rescans it every `interval`, reindexing the files that
+/// changed and dropping those that are gone. `updated` is called with how many files
+/// were indexed and then, after every rewrite, how many changed. Only returns if the
+/// index can't be written.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn watch_index(
+    directory: &Path,
+    interval: Duration,
+    mut updated: impl FnMut(usize),
+) -> Result<(), String> {
+    let mut entries = BTreeMap::new();
+    scan(directory, &mut entries)?;
+    updated(write(directory, &entries)?);
+    loop {
+        std::thread::sleep(interval);
+        let changed = scan(directory, &mut entries)?;
+        if changed > 0 {
+            write(directory, &entries)?;
+            updated(changed);
+        }
+    }
+}
+
+/// Brings `entries` up to date with the files under `directory`, returning how many
+/// were added, changed or removed. Only a file whose stamp changed is read again.
+fn scan(directory: &Path, entries: &mut BTreeMap<PathBuf, Entry>) -> Result<usize, String> {
+    if !directory.is_dir() {
+        return Err(format!("{} is not a directory", directory.display()));
+    }
+    let mut app = SearchConfig::new();
+    app.sort(crate::config::SortChoice::Path);
+    let mut changed = 0;
+    let mut seen = HashSet::new();
+    let mut jobs = vec![walker::root(directory, &app)];
+    while let Some(job) = jobs.pop() {
+        let path = match job {
+            walker::Job::File(path, _) => path,
+            walker::Job::Directory(path, ignore, ancestors, key) => {
+                let listed =
+                    walker::list_directory(directory, &path, &ignore, &ancestors, &app, &key);
+                jobs.extend(listed.into_iter().rev());
+                continue;
+            }
+        };
+        let name = path.strip_prefix(directory).unwrap_or(&path).to_path_buf();
+        let Some(stamp) = std::fs::metadata(&path).ok().as_ref().and_then(Stamp::of) else {
+            continue;
+        };
+        let previous = entries.get_mut(&name);
+        if previous.as_ref().is_some_and(|entry| entry.stamp == stamp) {
+            seen.insert(name);
+            continue;
+        }
+        let contents = match std::fs::read(&path) {
+            Ok(contents) => contents,
+            Err(err) => {
+                app.error(&format!("Failed to read {}: {}", path.display(), err));
+                continue;
+            }
+        };
+        let checksum = checksum(&contents);
+        changed += 1;
+        match previous {
+            // Touched, or rewritten as it was: the trigrams still hold.
+            Some(entry) if entry.checksum == checksum => entry.stamp = stamp,
+            _ => {
+                let plain = encoding::detect(&contents, None).0 == encoding::Encoding::Utf8;
+                let trigrams = (plain && !lines::is_binary(&contents, b'\n')).then(|| {
+                    let mut trigrams = trigrams(&contents);
+                    trigrams.sort_unstable();
+                    trigrams.dedup();
+                    trigrams
+                });
+                let entry = Entry {
+                    stamp,
+                    checksum,
+                    trigrams,
+                };
+                entries.insert(name.clone(), entry);
+            }
+        }
+        seen.insert(name);
+    }
+    let before = entries.len();
+    entries.retain(|name, _| seen.contains(name));
+    Ok(changed + before - entries.len())
+}
+
+/// Writes `entries` to `directory`'s `.yagrep-index`, replacing it in one step so a
+/// search never reads half of it, and returns how many files it indexes.
+fn write(directory: &Path, entries: &BTreeMap<PathBuf, Entry>) -> Result<usize, String> {
+    let path = directory.join(FILE_NAME);
+    let partial = directory.join(format!("{}.tmp", FILE_NAME));
+    let indexed = entries
+        .iter()
+        .filter_map(|(name, entry)| Some((name, entry, entry.trigrams.as_ref()?)))
+        .collect::<Vec<_>>();
+    let written = write_entries(&partial, &indexed).and_then(|_| std::fs::rename(&partial, &path));
+    if let Err(err) = written {
+        let _ = std::fs::remove_file(&partial);
+        return Err(format!("Failed to write {}: {}", path.display(), err));
+    }
+    Ok(indexed.len())
+}
+
+fn write_entries(path: &Path, indexed: &[(&PathBuf, &Entry, &Vec<u32>)]) -> std::io::Result<()> {
+    // The ids of the files holding each trigram.
+    let mut holders: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+    for (id, (_, _, trigrams)) in indexed.iter().enumerate() {
+        for &trigram in *trigrams {
+            holders.entry(trigram).or_default().push(id as u32);
+        }
+    }
+    let mut writer = BufWriter::new(std::fs::File::create(path)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&(indexed.len() as u32).to_le_bytes())?;
+    for (name, entry, _) in indexed {
+        let name = name.as_os_str().as_encoded_bytes();
+        let modified = entry
+            .stamp
+            .modified
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        writer.write_all(&(name.len() as u32).to_le_bytes())?;
+        writer.write_all(name)?;
+        writer.write_all(&entry.stamp.size.to_le_bytes())?;
+        writer.write_all(&modified.as_secs().to_le_bytes())?;
+        writer.write_all(&modified.subsec_nanos().to_le_bytes())?;
+        writer.write_all(&entry.checksum.to_le_bytes())?;
+    }
+    writer.write_all(&(holders.len() as u32).to_le_bytes())?;
+    for (trigram, ids) in &holders {
+        writer.write_all(&trigram.to_le_bytes())?;
+        writer.write_all(&(ids.len() as u32).to_le_bytes())?;
+    }
+    for id in holders.values().flatten() {
+        writer.write_all(&id.to_le_bytes())?;
+    }
+    writer.flush()
+}
+
+/// The 64-bit FNV-1a hash of `bytes`.
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Every three-byte window of `bytes`, packed into the low 24 bits.
+fn trigrams(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .windows(3)
+        .map(|window| u32::from_be_bytes([0, window[0], window[1], window[2]]))
+        .collect()
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn invalid(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
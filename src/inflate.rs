@@ -0,0 +1,332 @@
+//! A DEFLATE (RFC 1951) decoder for the compressed members of zip archives. It favors
+//! brevity over speed: Huffman codes are decoded a bit at a time, as in zlib's `puff`.
+
+use std::io::{Error, Result};
+
+const MAX_BITS: usize = 15;
+
+/// Length symbols 257..=285: base lengths and extra bits.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Distance symbols 0..=29: base distances and extra bits.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order in which code length code lengths are stored in a dynamic block header.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Decompresses a complete raw DEFLATE stream, failing once the output would pass
+/// `limit` bytes, so a bomb can't exhaust memory.
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut bits = Bits {
+        data,
+        position: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let mut output = Vec::new();
+    loop {
+        let last = bits.take(1)? == 1;
+        match bits.take(2)? {
+            0 => stored(&mut bits, &mut output)?,
+            1 => {
+                let (lengths, distances) = fixed_codes();
+                codes(&mut bits, &mut output, limit, &lengths, &distances)?
+            }
+            2 => {
+                let (lengths, distances) = dynamic_codes(&mut bits)?;
+                codes(&mut bits, &mut output, limit, &lengths, &distances)?
+            }
+            _ => return Err(invalid("invalid block type")),
+        }
+        if output.len() > limit {
+            return Err(invalid("output exceeds its limit"));
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+/// Reads the stream least significant bit first.
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn take(&mut self, count: u32) -> Result<u32> {
+        while self.count < count {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or_else(|| invalid("unexpected end of stream"))?;
+            self.position += 1;
+            self.buffer |= u32::from(byte) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << count) - 1);
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    /// Decodes one symbol of a canonical Huffman code.
+    fn decode(&mut self, huffman: &Huffman) -> Result<u16> {
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for length in 1..=MAX_BITS {
+            code |= self.take(1)? as i32;
+            let count = i32::from(huffman.counts[length]);
+            if code - count < first {
+                return Ok(huffman.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("invalid Huffman code"))
+    }
+}
+
+/// A canonical Huffman code: how many codes have each length, and the symbols ordered
+/// by code.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; MAX_BITS + 1];
+        for &length in lengths {
+            counts[usize::from(length)] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                let offset = &mut offsets[usize::from(length)];
+                symbols[usize::from(*offset)] = symbol as u16;
+                *offset += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+}
+
+fn stored(bits: &mut Bits, output: &mut Vec<u8>) -> Result<()> {
+    // Stored blocks start at a byte boundary.
+    bits.buffer = 0;
+    bits.count = 0;
+    let header = bits
+        .data
+        .get(bits.position..bits.position + 4)
+        .ok_or_else(|| invalid("unexpected end of stream"))?;
+    let length = usize::from(u16::from_le_bytes([header[0], header[1]]));
+    let complement = u16::from_le_bytes([header[2], header[3]]);
+    if length as u16 != !complement {
+        return Err(invalid("corrupt stored block"));
+    }
+    bits.position += 4;
+    let block = bits
+        .data
+        .get(bits.position..bits.position + length)
+        .ok_or_else(|| invalid("unexpected end of stream"))?;
+    output.extend_from_slice(block);
+    bits.position += length;
+    Ok(())
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman)> {
+    let literal_count = bits.take(5)? as usize + 257;
+    let distance_count = bits.take(5)? as usize + 1;
+    let code_length_count = bits.take(4)? as usize + 4;
+
+    let mut code_lengths = [0; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = bits.take(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let symbol = bits.decode(&code_lengths)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or_else(|| invalid("repeat with no length"))?;
+                (previous, 3 + bits.take(2)?)
+            }
+            17 => (0, 3 + bits.take(3)?),
+            _ => (0, 11 + bits.take(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(invalid("too many code lengths"));
+    }
+    let (literals, distances) = lengths.split_at(literal_count);
+    Ok((Huffman::new(literals), Huffman::new(distances)))
+}
+
+/// Decodes a compressed block, stopping once `output` passes `limit`.
+fn codes(
+    bits: &mut Bits,
+    output: &mut Vec<u8>,
+    limit: usize,
+    lengths: &Huffman,
+    distances: &Huffman,
+) -> Result<()> {
+    loop {
+        if output.len() > limit {
+            return Err(invalid("output exceeds its limit"));
+        }
+        let symbol = usize::from(bits.decode(lengths)?);
+        if symbol < 256 {
+            output.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+        let symbol = symbol - 257;
+        if symbol >= LENGTH_BASE.len() {
+            return Err(invalid("invalid length symbol"));
+        }
+        let length =
+            usize::from(LENGTH_BASE[symbol]) + bits.take(u32::from(LENGTH_EXTRA[symbol]))? as usize;
+        let symbol = usize::from(bits.decode(distances)?);
+        if symbol >= DISTANCE_BASE.len() {
+            return Err(invalid("invalid distance symbol"));
+        }
+        let distance = usize::from(DISTANCE_BASE[symbol])
+            + bits.take(u32::from(DISTANCE_EXTRA[symbol]))? as usize;
+        if distance > output.len() {
+            return Err(invalid("distance too far back"));
+        }
+        // Copies may overlap their own output, so go a byte at a time.
+        let start = output.len() - distance;
+        for index in 0..length {
+            output.push(output[start + index]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `hello hello hello\n` in a fixed Huffman block, from zlib.
+    const FIXED: [u8; 11] = [203, 72, 205, 201, 201, 87, 200, 64, 144, 92, 0];
+    /// `eeeeeeeeeeeeeeeeeeeeeeeetaoin shrdlu ` in a dynamic Huffman block, from zlib.
+    const DYNAMIC: [u8; 31] = [
+        5, 193, 1, 10, 0, 16, 16, 0, 176, 175, 220, 215, 148, 21, 37, 234, 142, 255, 219, 0, 0, 0,
+        110, 59, 115, 71, 141, 236, 235, 197, 7,
+    ];
+    /// `stored` in a stored block, from zlib.
+    const STORED: [u8; 11] = [1, 6, 0, 249, 255, 115, 116, 111, 114, 101, 100];
+
+    #[test]
+    fn decodes_every_block_type() {
+        assert_eq!(inflate(&FIXED, usize::MAX).unwrap(), b"hello hello hello\n");
+        let expected = format!("{}taoin shrdlu ", "e".repeat(24));
+        assert_eq!(inflate(&DYNAMIC, usize::MAX).unwrap(), expected.as_bytes());
+        assert_eq!(inflate(&STORED, usize::MAX).unwrap(), b"stored");
+    }
+
+    #[test]
+    fn continues_past_blocks_that_are_not_last() {
+        // A stored block without the last bit, then an empty last one.
+        let data = [0, 2, 0, 253, 255, b'h', b'i', 1, 0, 0, 255, 255];
+        assert_eq!(inflate(&data, usize::MAX).unwrap(), b"hi");
+    }
+
+    #[test]
+    fn stops_at_the_limit() {
+        assert_eq!(inflate(&FIXED, 18).unwrap(), b"hello hello hello\n");
+        for stream in [&FIXED[..], &DYNAMIC[..], &STORED[..]] {
+            let err = inflate(stream, 5).unwrap_err();
+            assert_eq!(err.to_string(), "output exceeds its limit");
+        }
+    }
+
+    #[test]
+    fn truncated_streams_are_errors() {
+        for stream in [&FIXED[..], &DYNAMIC[..], &STORED[..]] {
+            for end in 0..stream.len() {
+                let err = inflate(&stream[..end], usize::MAX).unwrap_err();
+                assert_eq!(
+                    err.kind(),
+                    std::io::ErrorKind::InvalidData,
+                    "{:?}",
+                    &stream[..end]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_the_reserved_block_type() {
+        let err = inflate(&[0b111], usize::MAX).unwrap_err();
+        assert_eq!(err.to_string(), "invalid block type");
+    }
+
+    #[test]
+    fn rejects_a_stored_length_without_its_complement() {
+        let err = inflate(&[1, 6, 0, 0, 0, b's'], usize::MAX).unwrap_err();
+        assert_eq!(err.to_string(), "corrupt stored block");
+    }
+
+    #[test]
+    fn rejects_a_distance_before_the_start() {
+        // A fixed block whose first symbol copies 3 bytes from 1 back.
+        let err = inflate(&[0x03, 0x02], usize::MAX).unwrap_err();
+        assert_eq!(err.to_string(), "distance too far back");
+    }
+
+    #[test]
+    fn corrupt_streams_never_panic() {
+        for stream in [&FIXED[..], &DYNAMIC[..]] {
+            for bit in 0..stream.len() * 8 {
+                let mut corrupt = stream.to_vec();
+                corrupt[bit / 8] ^= 1 << (bit % 8);
+                let _ = inflate(&corrupt, usize::MAX);
+            }
+        }
+    }
+}
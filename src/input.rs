@@ -0,0 +1,94 @@
+//! Reading files through another program: decompressors for `-z/--search-zip` and the
+//! user's `--pre` command.
+//!
+//! Each supported compression format is an entry in [`DECOMPRESSORS`]: a file extension
+//! and the command that writes the decompressed contents to stdout. Adding a format only
+//! needs a new entry, as long as its tool follows the `gzip -dc` convention.
+
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+/// `(extension, command)` pairs, matched case-insensitively against the file extension.
+const DECOMPRESSORS: [(&str, &[&str]); 5] = [
+    ("gz", &["gzip", "-dc"]),
+    ("tgz", &["gzip", "-dc"]),
+    ("bz2", &["bzip2", "-dc"]),
+    ("xz", &["xz", "-dc"]),
+    ("zst", &["zstd", "-dc"]),
+];
+
+/// The command that decompresses `path`, if its extension is a known format.
+pub fn decompressor(path: &Path) -> Option<&'static [&'static str]> {
+    let extension = path.extension()?.to_str()?;
+    DECOMPRESSORS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(extension))
+        .map(|&(_, command)| command)
+}
+
+/// The output of a program run on a file, read as it is produced.
+pub struct Piped {
+    program: String,
+    child: Child,
+    stdout: ChildStdout,
+}
+
+impl Piped {
+    /// Starts a decompressor `command` with `path` as its stdin.
+    pub fn decompress(command: &[&str], path: &Path) -> std::io::Result<Piped> {
+        let mut decompressor = Command::new(command[0]);
+        decompressor.args(&command[1..]).stderr(Stdio::null());
+        Piped::spawn(decompressor, path)
+    }
+
+    /// Starts the `--pre` `program` with `path` both as its argument and its stdin,
+    /// leaving its stderr on the terminal so its own errors are visible.
+    pub fn preprocess(program: &str, path: &Path) -> std::io::Result<Piped> {
+        let mut preprocessor = Command::new(program);
+        preprocessor.arg(path);
+        Piped::spawn(preprocessor, path)
+    }
+
+    /// Fails if the program isn't installed.
+    fn spawn(mut command: Command, path: &Path) -> std::io::Result<Piped> {
+        let program = command.get_program().to_string_lossy().into_owned();
+        let mut child = command
+            .stdin(std::fs::File::open(path)?)
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| match err.kind() {
+                std::io::ErrorKind::NotFound => {
+                    std::io::Error::other(format!("{} is not installed", program))
+                }
+                _ => err,
+            })?;
+        let stdout = child.stdout.take().unwrap();
+        Ok(Piped {
+            program,
+            child,
+            stdout,
+        })
+    }
+}
+
+impl Read for Piped {
+    /// Reports a program failure, such as a corrupt archive, as an error once its output
+    /// runs out.
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.stdout.read(buf)?;
+        if read == 0 && !buf.is_empty() && !self.child.wait()?.success() {
+            return Err(std::io::Error::other(format!("{} failed", self.program)));
+        }
+        Ok(read)
+    }
+}
+
+impl Drop for Piped {
+    /// Stops the program when the search ends early, e.g. at the first match
+    /// with `-l`.
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
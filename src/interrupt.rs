@@ -0,0 +1,82 @@
+//! Ctrl-C. Once [`catch_interrupts`] is called, the first Ctrl-C asks the search to stop:
+//! the walk queues nothing more and every file being searched is abandoned at its next
+//! line, after which the run prints what it found and its totals as usual. A second
+//! Ctrl-C, for a search stuck waiting on its input, exits at once.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Exit status of a search cut short by Ctrl-C, as a shell reports a process that
+/// SIGINT killed.
+pub const EXIT_INTERRUPTED: u8 = 130;
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Handles Ctrl-C for the rest of the process, in place of being killed by it.
+pub fn catch_interrupts() {
+    sys::install();
+}
+
+/// Whether Ctrl-C has been pressed since [`catch_interrupts`].
+pub fn interrupted() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Records a Ctrl-C, returning whether it was the first; only an atomic swap, so it's
+/// safe to call from a signal handler.
+#[cfg(any(unix, windows))]
+fn request() -> bool {
+    !REQUESTED.swap(true, Ordering::SeqCst)
+}
+
+#[cfg(unix)]
+mod sys {
+    const SIGINT: i32 = 2;
+
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+        fn _exit(status: i32) -> !;
+    }
+
+    extern "C" fn on_interrupt(_signum: i32) {
+        if !super::request() {
+            // SAFETY: `_exit` is async-signal-safe, unlike `std::process::exit`.
+            unsafe { _exit(i32::from(super::EXIT_INTERRUPTED)) }
+        }
+    }
+
+    pub(super) fn install() {
+        // SAFETY: the handler only touches an atomic and calls `_exit`.
+        unsafe {
+            signal(SIGINT, on_interrupt);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    const CTRL_C_EVENT: u32 = 0;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetConsoleCtrlHandler(handler: extern "system" fn(u32) -> i32, add: i32) -> i32;
+    }
+
+    /// Runs on a thread of its own; returning false passes the event on to the default
+    /// handler, which ends the process.
+    extern "system" fn on_control(event: u32) -> i32 {
+        i32::from(event == CTRL_C_EVENT && super::request())
+    }
+
+    pub(super) fn install() {
+        // SAFETY: the handler only touches an atomic.
+        unsafe {
+            SetConsoleCtrlHandler(on_control, 1);
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    /// Without signals, Ctrl-C ends the process as it always would.
+    pub(super) fn install() {}
+}
@@ -0,0 +1,362 @@
+//! JSON Lines output for `--json`. Every event is a single object on its own line:
+//! `begin` and `end` wrap each file with matches, `match` reports a selected line,
+//! and one `summary` closes the run. [`parse`] reads the requests of `yagrep serve`,
+//! and [`selected`] writes the lines it answers with.
+
+/// A match span within a line, in byte offsets relative to the line start.
+pub struct Submatch<'a> {
+    pub text: std::borrow::Cow<'a, str>,
+    pub start: usize,
+    pub end: usize,
+    /// The pattern's named groups, empty when it has none.
+    pub groups: Vec<Group<'a>>,
+}
+
+/// A named group of a submatch, with its span in the same offsets, or `None` when the
+/// group took no part in the match.
+pub struct Group<'a> {
+    pub name: &'a str,
+    pub found: Option<(std::borrow::Cow<'a, str>, usize, usize)>,
+}
+
+pub fn begin(path: &str) -> String {
+    format!(r#"{{"type":"begin","data":{{"path":{}}}}}"#, string(path))
+}
+
+/// A `match` event. In multiline mode the text can span lines, through
+/// `end_line_number`.
+pub fn matched(
+    path: &str,
+    line_number: usize,
+    end_line_number: Option<usize>,
+    absolute_offset: usize,
+    text: &str,
+    submatches: &[Submatch],
+) -> String {
+    let column = submatches.first().map_or(1, |submatch| submatch.start + 1);
+    let submatches = submatches
+        .iter()
+        .map(|submatch| {
+            format!(
+                r#"{{"match":{},"start":{},"end":{}{}}}"#,
+                string(&submatch.text),
+                submatch.start,
+                submatch.end,
+                groups(&submatch.groups)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let end_line_number = end_line_number.map_or(String::new(), |end_line_number| {
+        format!(r#","end_line_number":{}"#, end_line_number)
+    });
+    format!(
+        r#"{{"type":"match","data":{{"path":{},"line_number":{}{},"column":{},"absolute_offset":{},"text":{},"submatches":[{}]}}}}"#,
+        string(path),
+        line_number,
+        end_line_number,
+        column,
+        absolute_offset,
+        string(text),
+        submatches
+    )
+}
+
+/// A submatch's `groups` member, or nothing when the pattern has no named groups.
+fn groups(groups: &[Group]) -> String {
+    if groups.is_empty() {
+        return String::new();
+    }
+    let groups = groups
+        .iter()
+        .map(|group| {
+            let found = match &group.found {
+                Some((text, start, end)) => format!(
+                    r#"{{"text":{},"start":{},"end":{}}}"#,
+                    string(text),
+                    start,
+                    end
+                ),
+                None => "null".to_string(),
+            };
+            format!("{}:{}", string(group.name), found)
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(r#","groups":{{{}}}"#, groups)
+}
+
+pub fn end(path: &str, matched_lines: usize, matches: usize) -> String {
+    format!(
+        r#"{{"type":"end","data":{{"path":{},"matched_lines":{},"matches":{}}}}}"#,
+        string(path),
+        matched_lines,
+        matches
+    )
+}
+
+pub fn summary(summary: &crate::stats::Stats, elapsed: std::time::Duration) -> String {
+    format!(
+        r#"{{"type":"summary","data":{{"files_searched":{},"files_matched":{},"matched_lines":{},"matches":{},"elapsed_secs":{:.6}}}}}"#,
+        summary.files_searched,
+        summary.files_matched,
+        summary.matched_lines,
+        summary.matches,
+        elapsed.as_secs_f64()
+    )
+}
+
+/// A selected line as `yagrep serve` returns it: its `path`, `line_number`, `text` and
+/// `submatches`.
+pub fn selected(found: &crate::matches::Match) -> String {
+    let path = found
+        .path
+        .as_ref()
+        .map_or("null".to_string(), |path| string(&path.to_string_lossy()));
+    let submatches = found
+        .spans
+        .iter()
+        .map(|span| {
+            let text = String::from_utf8_lossy(&found.line[span.clone()]);
+            format!(
+                r#"{{"match":{},"start":{},"end":{}}}"#,
+                string(&text),
+                span.start,
+                span.end
+            )
+        })
+        .collect::<Vec<_>>();
+    format!(
+        r#"{{"path":{},"line_number":{},"text":{},"submatches":[{}]}}"#,
+        path,
+        found.line_number,
+        string(&String::from_utf8_lossy(&found.line)),
+        submatches.join(",")
+    )
+}
+
+/// Quotes `value` as a JSON string, escaping quotes, backslashes and control characters.
+pub fn string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A parsed JSON value. Objects keep their members in order.
+#[derive(Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The member `key` of an object.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The value written back as JSON, for echoing a request's `id`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn to_json(&self) -> String {
+        match self {
+            Value::Null => "null".to_string(),
+            Value::Bool(value) => value.to_string(),
+            Value::Number(value) => value.to_string(),
+            Value::String(value) => string(value),
+            Value::Array(values) => {
+                let values = values.iter().map(Value::to_json).collect::<Vec<_>>();
+                format!("[{}]", values.join(","))
+            }
+            Value::Object(members) => {
+                let members = members
+                    .iter()
+                    .map(|(name, value)| format!("{}:{}", string(name), value.to_json()))
+                    .collect::<Vec<_>>();
+                format!("{{{}}}", members.join(","))
+            }
+        }
+    }
+}
+
+/// Parses one JSON document, or returns `None` if `text` isn't one.
+pub fn parse(text: &str) -> Option<Value> {
+    let mut parser = Parser {
+        text: text.as_bytes(),
+        at: 0,
+    };
+    let value = parser.value()?;
+    parser.whitespace();
+    (parser.at == parser.text.len()).then_some(value)
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn whitespace(&mut self) {
+        while self
+            .text
+            .get(self.at)
+            .is_some_and(|byte| matches!(byte, b' ' | b'\t' | b'\n' | b'\r'))
+        {
+            self.at += 1;
+        }
+    }
+
+    /// Consumes `expected` if it comes next.
+    fn eat(&mut self, expected: &[u8]) -> bool {
+        let found = self.text[self.at..].starts_with(expected);
+        if found {
+            self.at += expected.len();
+        }
+        found
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        self.whitespace();
+        match self.text.get(self.at)? {
+            b'n' if self.eat(b"null") => Some(Value::Null),
+            b't' if self.eat(b"true") => Some(Value::Bool(true)),
+            b'f' if self.eat(b"false") => Some(Value::Bool(false)),
+            b'"' => self.string().map(Value::String),
+            b'[' => {
+                self.at += 1;
+                let mut values = Vec::new();
+                self.whitespace();
+                if self.eat(b"]") {
+                    return Some(Value::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.whitespace();
+                    if self.eat(b"]") {
+                        return Some(Value::Array(values));
+                    }
+                    if !self.eat(b",") {
+                        return None;
+                    }
+                }
+            }
+            b'{' => {
+                self.at += 1;
+                let mut members = Vec::new();
+                self.whitespace();
+                if self.eat(b"}") {
+                    return Some(Value::Object(members));
+                }
+                loop {
+                    self.whitespace();
+                    if self.text.get(self.at) != Some(&b'"') {
+                        return None;
+                    }
+                    let name = self.string()?;
+                    self.whitespace();
+                    if !self.eat(b":") {
+                        return None;
+                    }
+                    members.push((name, self.value()?));
+                    self.whitespace();
+                    if self.eat(b"}") {
+                        return Some(Value::Object(members));
+                    }
+                    if !self.eat(b",") {
+                        return None;
+                    }
+                }
+            }
+            b'-' | b'0'..=b'9' => {
+                let start = self.at;
+                while self.text.get(self.at).is_some_and(|byte| {
+                    matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+                }) {
+                    self.at += 1;
+                }
+                let number = std::str::from_utf8(&self.text[start..self.at]).ok()?;
+                number.parse().ok().map(Value::Number)
+            }
+            _ => None,
+        }
+    }
+
+    /// A string, from its opening quote, with its escapes decoded.
+    fn string(&mut self) -> Option<String> {
+        self.at += 1;
+        let mut decoded = Vec::new();
+        loop {
+            let byte = *self.text.get(self.at)?;
+            self.at += 1;
+            match byte {
+                b'"' => return String::from_utf8(decoded).ok(),
+                b'\\' => {
+                    let escape = *self.text.get(self.at)?;
+                    self.at += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let unit = self.hex()?;
+                            // A surrogate pair spells out a character beyond the BMP.
+                            if (0xD800..0xDC00).contains(&unit) && self.eat(b"\\u") {
+                                let low = self.hex()?;
+                                let c = 0x10000
+                                    + ((unit - 0xD800) << 10)
+                                    + (low.checked_sub(0xDC00)?);
+                                char::from_u32(c)?
+                            } else {
+                                char::from_u32(unit)?
+                            }
+                        }
+                        _ => return None,
+                    };
+                    decoded.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                byte if byte < 0x20 => return None,
+                byte => decoded.push(byte),
+            }
+        }
+    }
+
+    /// The four hex digits of a `\u` escape.
+    fn hex(&mut self) -> Option<u32> {
+        let digits = self.text.get(self.at..self.at + 4)?;
+        self.at += 4;
+        u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+    }
+}
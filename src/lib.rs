@@ -0,0 +1,76 @@
+//! The search behind the `yagrep` binary, for programs that want to run it in-process:
+//! configure a [`SearchConfig`], compile it into a [`Searcher`], and either print its
+//! results with [`Searcher::run`] or iterate over them with [`Searcher::search`]. With
+//! the `async` feature, `AsyncSearcher` awaits them instead. Where there is no file
+//! system to search, [`Searcher::search_in`] searches a [`MemoryFs`]; on wasm32, `wasm`
+//! exports it to JavaScript, in place of the parts that need an operating system: the
+//! server, watching and the C interface.
+
+mod archive;
+mod args;
+pub mod cli;
+mod config;
+mod console;
+mod csv;
+mod diff;
+mod encoding;
+#[cfg(all(feature = "ffi", not(target_arch = "wasm32")))]
+pub mod ffi;
+mod fuzzy;
+mod git;
+mod github;
+mod glob;
+mod history;
+mod ignore;
+mod index;
+mod inflate;
+mod input;
+mod interrupt;
+mod json;
+mod lines;
+mod matcher;
+mod matches;
+mod mmap;
+mod ordered;
+mod parallel;
+#[cfg(feature = "pcre2")]
+mod pcre2;
+mod printer;
+mod replace;
+mod sarif;
+mod searcher;
+#[cfg(not(target_arch = "wasm32"))]
+mod serve;
+mod sink;
+mod stats;
+#[cfg(feature = "async")]
+mod stream;
+mod summary;
+mod tally;
+mod template;
+mod terminal;
+mod theme;
+mod tracked;
+mod types;
+mod vfs;
+mod walker;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+#[cfg(not(target_arch = "wasm32"))]
+mod watch;
+
+pub use config::{ColorChoice, EngineChoice, FormatChoice, SearchConfig, SortChoice};
+pub use index::build_index;
+#[cfg(not(target_arch = "wasm32"))]
+pub use index::watch_index;
+pub use interrupt::{catch_interrupts, interrupted, EXIT_INTERRUPTED};
+pub use matcher::PatternError;
+pub use matches::{Match, Matches};
+pub use searcher::Searcher;
+#[cfg(not(target_arch = "wasm32"))]
+pub use serve::serve;
+#[cfg(feature = "async")]
+pub use stream::{AsyncSearcher, MatchStream, NextMatch};
+pub use vfs::MemoryFs;
+#[cfg(not(target_arch = "wasm32"))]
+pub use watch::{Watcher, POLL_INTERVAL};
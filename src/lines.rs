@@ -0,0 +1,117 @@
+//! Incremental line reading, so a file is searched without holding all of it in memory.
+
+use std::io::BufRead;
+
+/// Like `str::lines` over everything `reader` produces, but yields raw bytes, each line
+/// with the byte offset at which it starts. Lines end at `\n`, also taking the `\r` of
+/// a `\r\n` with [`Lines::crlf`], or at another terminator when one is set (see
+/// [`Lines::terminated_by`]). Iteration stops at the first read error, which is kept
+/// for [`Lines::take_error`].
+pub struct Lines<R> {
+    reader: R,
+    offset: usize,
+    terminator: u8,
+    crlf: bool,
+    escape: bool,
+    error: Option<std::io::Error>,
+}
+
+impl<R: BufRead> Lines<R> {
+    pub fn new(reader: R) -> Lines<R> {
+        Lines {
+            reader,
+            offset: 0,
+            terminator: b'\n',
+            crlf: false,
+            escape: false,
+            error: None,
+        }
+    }
+
+    /// Yields lines with NUL, other control bytes (except tab) and invalid UTF-8
+    /// written as `\xNN`, for searching binary files (`--binary`).
+    pub fn escaped(reader: R) -> Lines<R> {
+        Lines {
+            escape: true,
+            ..Lines::new(reader)
+        }
+    }
+
+    /// Splits the input at `terminator` instead, such as NUL for `find -print0` output
+    /// (`--null-data`).
+    pub fn terminated_by(self, terminator: u8) -> Lines<R> {
+        Lines { terminator, ..self }
+    }
+
+    /// Strips the carriage return ending a line before its `\n`, or ending the input, so
+    /// Windows line endings aren't matched or printed (`--crlf`). Otherwise a `\r` is
+    /// part of the line like any other byte.
+    pub fn crlf(self, crlf: bool) -> Lines<R> {
+        Lines { crlf, ..self }
+    }
+
+    /// How many bytes have been read so far.
+    pub fn bytes_read(&self) -> usize {
+        self.offset
+    }
+
+    /// The read error that ended iteration, if one did.
+    pub fn take_error(&mut self) -> Option<std::io::Error> {
+        self.error.take()
+    }
+}
+
+impl<R: BufRead> Iterator for Lines<R> {
+    type Item = (usize, Vec<u8>);
+
+    fn next(&mut self) -> Option<(usize, Vec<u8>)> {
+        let mut line = Vec::new();
+        let length = match self.reader.read_until(self.terminator, &mut line) {
+            Ok(0) => return None,
+            Err(err) => {
+                self.error = Some(err);
+                return None;
+            }
+            Ok(length) => length,
+        };
+        let start = self.offset;
+        self.offset += length;
+        if line.last() == Some(&self.terminator) {
+            line.pop();
+        }
+        // Also the last line of a CRLF file that doesn't end with a line break.
+        if self.crlf && self.terminator == b'\n' && line.ends_with(b"\r") {
+            line.pop();
+        }
+        if self.escape {
+            line = escape(&line).into_bytes();
+        }
+        Some((start, line))
+    }
+}
+
+fn escape(line: &[u8]) -> String {
+    let mut escaped = String::with_capacity(line.len());
+    for chunk in line.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\t' => escaped.push(c),
+                c if c.is_ascii_control() => escaped.push_str(&format!("\\x{:02X}", c as u8)),
+                c => escaped.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02X}", byte));
+        }
+    }
+    escaped
+}
+
+/// How much of a file is checked for NUL bytes, like grep's first buffer.
+const BINARY_PEEK: usize = 8 * 1024;
+
+/// Whether data starting with `start` looks binary: it has a NUL byte early on. Records
+/// ended by `terminator` NUL are text however many NULs they hold.
+pub fn is_binary(start: &[u8], terminator: u8) -> bool {
+    terminator != 0 && start[..start.len().min(BINARY_PEEK)].contains(&0)
+}
@@ -1,206 +1,145 @@
-use std::io::{BufWriter, Write};
+// The command line needs an operating system; on wasm32 only the library is of use, and
+// this builds as an empty module.
+#![cfg_attr(target_arch = "wasm32", no_main)]
+#![cfg(not(target_arch = "wasm32"))]
 
-use colored::*;
-use regex::{Regex, RegexBuilder};
+use std::io::{IsTerminal, Write};
 
-const USAGE: &str = "Usage: yagrep [options] <pattern> <file>";
+use yagrep::cli::Command;
+use yagrep::Searcher;
 
-#[derive(PartialEq)]
-enum CliOptions {
-    IgnoreCase,
-    IgnoreGitIgnore,
-    IgnoreNoHiddenFiles,
-    Empty,
-}
-
-struct CliApp {
-    options: Vec<CliOptions>,
-    pattern: String,
-    path: std::path::PathBuf,
-    ignored_paths: std::cell::RefCell<Vec<std::path::PathBuf>>,
-    writer: std::cell::RefCell<BufWriter<std::io::Stdout>>,
-}
-
-impl CliApp {
-    fn new(args: Vec<String>) -> Result<CliApp, &'static str> {
-        if args.len() < 3 {
-            return Err(USAGE);
-        }
-
-        let pattern = args[1].clone();
-        let path = get_full_path(&args[2]);
-
-        let options = args
-            .iter()
-            .filter(|&arg| arg.starts_with("-"))
-            .flat_map(|arg| {
-                arg.as_str().chars().map(|c| match c {
-                    'i' => CliOptions::IgnoreCase,
-                    'g' => CliOptions::IgnoreGitIgnore,
-                    'H' => CliOptions::IgnoreNoHiddenFiles,
-                    _ => CliOptions::Empty,
-                })
-            })
-            .collect();
-
-        Ok(CliApp {
-            options,
-            pattern,
-            path,
-            ignored_paths: std::cell::RefCell::new(Vec::new()),
-            writer: std::cell::RefCell::new(BufWriter::new(std::io::stdout())),
-        })
-    }
-
-    fn has_option(&self, option: CliOptions) -> bool {
-        self.options.contains(&option)
-    }
-}
+mod pager;
 
-fn is_git_ignore(git_dir_path: &std::path::Path, path: &std::path::Path) -> Option<bool> {
-    let output = match std::process::Command::new("git")
-        .arg("-C")
-        .arg(git_dir_path)
-        .arg("check-ignore")
-        .arg(path)
-        .output()
-    {
-        Ok(output) => output,
-        Err(_) => return None,
-    };
-    output.status.success().then_some(Some(true))?
-}
+/// Exit status when nothing matched; a match exits with `ExitCode::SUCCESS`.
+const EXIT_NO_MATCH: u8 = 1;
 
-fn git_root(path: &std::path::Path) -> Option<std::path::PathBuf> {
-    let output = match std::process::Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .arg("rev-parse")
-        .arg("--show-toplevel")
-        .output()
-    {
-        Ok(output) => output,
-        Err(_) => return None,
-    };
-    output.status.success().then(|| {
-        std::str::from_utf8(&output.stdout)
-            .ok()
-            .map(|s| s.trim().to_string())
-            .map(std::path::PathBuf::from)
-    })?
-}
+/// Exit status for usage, regex, and file errors, including a file skipped because it
+/// couldn't be read.
+const EXIT_ERROR: u8 = 2;
 
-fn main() {
+fn main() -> std::process::ExitCode {
     let params = std::env::args().collect::<Vec<String>>();
-    let app = match CliApp::new(params) {
-        Ok(app) => app,
+    let (mut config, watch) = match yagrep::cli::parse(params) {
+        Ok(Command::Print(text)) => {
+            // Help piped to `head` may be cut short, which isn't an error.
+            let _ = std::io::stdout().write_all(text.as_bytes());
+            return std::process::ExitCode::SUCCESS;
+        }
+        Ok(Command::BuildIndex(directory)) => return build_index(&directory),
+        Ok(Command::WatchIndex(directory)) => return watch_index(&directory),
+        Ok(Command::Serve(socket)) => {
+            if let Err(err) = yagrep::serve(socket.as_deref()) {
+                eprintln!("Error: {}", err);
+                return std::process::ExitCode::from(EXIT_ERROR);
+            }
+            return std::process::ExitCode::SUCCESS;
+        }
+        Ok(Command::Search { config, watch }) => (*config, watch),
         Err(err) => {
             eprintln!("{}", err);
-            return;
+            return std::process::ExitCode::from(EXIT_ERROR);
         }
     };
 
-    let pattern = &app.pattern;
-    let path = &app.path;
-
-    let mut regex_builder_binding = RegexBuilder::new(pattern);
-    let regex_builder =
-        regex_builder_binding.case_insensitive(app.has_option(CliOptions::IgnoreCase));
+    colored::control::set_override(config.use_color());
+    // A watch redraws the screen itself, so it's never paged.
+    let pager = match watch.is_none() && config.use_pager() {
+        true => pager::Pager::spawn(),
+        false => None,
+    };
+    let pager = pager.map(|(pager, input)| {
+        config.writer(input);
+        pager
+    });
 
-    let re = match regex_builder.build() {
-        Ok(re) => re,
+    let searcher = match Searcher::new(config) {
+        Ok(searcher) => searcher,
         Err(err) => {
             eprintln!("Error: Failed to compile regex: {}", err);
-            return;
+            if let Some(pager) = pager {
+                pager.wait();
+            }
+            return std::process::ExitCode::from(EXIT_ERROR);
         }
     };
 
-    match (path.is_file(), path.is_dir()) {
-        (true, false) => {
-            match_file(&re, path, &app);
-        }
-        (false, true) => {
-            match_directory(&re, path, &app).unwrap();
-        }
-        (false, false) => {
-            eprintln!("Error: File not found");
+    // Ctrl-C stops the search rather than the process, so what was found still prints.
+    yagrep::catch_interrupts();
+
+    if let Some(path) = watch {
+        let mut watcher = yagrep::Watcher::new(&searcher, &path);
+        loop {
+            if std::io::stdout().is_terminal() {
+                // Clear the screen and move the cursor home before each run.
+                print!("\x1b[2J\x1b[H");
+            }
+            let status = search(&searcher);
+            if yagrep::interrupted() {
+                return status;
+            }
+            if !watcher.wait() {
+                return std::process::ExitCode::from(yagrep::EXIT_INTERRUPTED);
+            }
         }
-        _ => {}
     }
+
+    let status = search(&searcher);
+    // Closing the pipe tells the pager the results are complete.
+    drop(searcher);
+    if let Some(pager) = pager {
+        pager.wait();
+    }
+    status
 }
 
-fn match_file(regex: &Regex, path: &std::path::Path, app: &CliApp) {
-    let contents = match std::fs::read_to_string(path) {
-        Ok(contents) => contents,
-        Err(_err) => {
-            return;
+/// Runs one search over the configured input, returning the exit status.
+fn search(searcher: &Searcher) -> std::process::ExitCode {
+    match searcher.run() {
+        Ok(_) if yagrep::interrupted() => std::process::ExitCode::from(yagrep::EXIT_INTERRUPTED),
+        Ok(_) if searcher.had_errors() => std::process::ExitCode::from(EXIT_ERROR),
+        Ok(true) => std::process::ExitCode::SUCCESS,
+        Ok(false) => std::process::ExitCode::from(EXIT_NO_MATCH),
+        // The output's reader went away before the run's last lines were written.
+        Err(err) if is_broken_pipe(err.as_ref()) => std::process::ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::ExitCode::from(EXIT_ERROR)
         }
-    };
-
-    let mut writer = app.writer.borrow_mut();
-    let mut matches = contents
-        .lines()
-        .enumerate()
-        .filter(|(_index, line)| regex.is_match(line))
-        .peekable();
-    if matches.peek().is_some() {
-        writeln!(writer, "{}", path.display().to_string().green()).unwrap();
     }
-    for (index, line) in matches {
-        writeln!(writer, "{}: {}", index + 1, line).unwrap();
-    }
-    writer.flush().unwrap();
 }
 
-fn match_directory(
-    regex: &Regex,
-    directory: &std::path::Path,
-    app: &CliApp,
-) -> Result<(), Box<dyn std::error::Error>> {
-    for entry in std::fs::read_dir(directory)? {
-        let entry = entry?;
-        let path = entry.path();
-        if !app.has_option(CliOptions::IgnoreNoHiddenFiles)
-            && path.file_name().unwrap().to_str().unwrap().starts_with(".")
-        {
-            continue;
-        }
-        if !app.has_option(CliOptions::IgnoreGitIgnore) {
-            if app
-                .ignored_paths
-                .borrow()
-                .iter()
-                .any(|p| p.starts_with(&path))
-            {
-                continue;
-            }
-            let git_root = git_root(&path);
-            if let Some(git_root) = &git_root {
-                if is_git_ignore(git_root, &path) == Some(true) {
-                    app.ignored_paths.borrow_mut().push(path.to_path_buf());
-                    continue;
-                }
-            }
+/// Whether `err` is a write to a pipe whose reader has exited.
+fn is_broken_pipe(err: &(dyn std::error::Error + 'static)) -> bool {
+    err.downcast_ref::<std::io::Error>()
+        .is_some_and(|err| err.kind() == std::io::ErrorKind::BrokenPipe)
+}
+
+/// `yagrep index build`: writes the index, returning the exit status.
+fn build_index(directory: &std::path::Path) -> std::process::ExitCode {
+    match yagrep::build_index(directory) {
+        Ok(files) => {
+            println!("Indexed {} files in {}", files, directory.display());
+            std::process::ExitCode::SUCCESS
         }
-        if path.is_file() {
-            match_file(regex, &path, app);
-        } else if path.is_dir() {
-            match_directory(regex, &path, app)?;
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::ExitCode::from(EXIT_ERROR)
         }
     }
-    Ok(())
 }
 
-fn get_full_path(path: &str) -> std::path::PathBuf {
-    match path
-        .chars()
-        .next()
-        .expect("Failed to get first character of path")
-    {
-        '/' => std::path::PathBuf::from(path),
-        _ => {
-            let current_dir = std::env::current_dir().expect("Failed to get current directory");
-            current_dir.join(path)
+/// `yagrep index watch`: keeps the index up to date until it can't be written.
+fn watch_index(directory: &std::path::Path) -> std::process::ExitCode {
+    let mut indexed = false;
+    let watched = yagrep::watch_index(directory, yagrep::POLL_INTERVAL, |files| {
+        match indexed {
+            false => println!("Indexed {} files in {}", files, directory.display()),
+            true => println!("Updated the index for {} changed files", files),
         }
+        indexed = true;
+    });
+    if let Err(err) = watched {
+        eprintln!("Error: {}", err);
     }
+    std::process::ExitCode::from(EXIT_ERROR)
 }
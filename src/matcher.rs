@@ -0,0 +1,646 @@
+//! Compiling the user's patterns into the regexes lines are matched with. The rest of the
+//! crate sees matches only as byte ranges ([`Found`] and [`Captures`]), so it doesn't
+//! know which engine found them: the `regex` crate, PCRE2 with the `pcre2` feature, or
+//! the approximate matcher of `--fuzzy`.
+
+use std::ops::Range;
+
+use memchr::memmem;
+use regex::bytes::{Regex, RegexBuilder, RegexSet, RegexSetBuilder, SetMatches};
+
+use crate::config::{EngineChoice, SearchConfig};
+
+/// Why the patterns didn't compile, in the words of the engine that rejected them.
+#[derive(Debug)]
+pub struct PatternError(String);
+
+impl std::fmt::Display for PatternError {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+/// Compiled form of every search pattern. Both engines match raw bytes, so input that
+/// isn't valid UTF-8 is still searched.
+pub(crate) struct Matcher {
+    engine: Engine,
+    /// Every named group with its index, for `$name` in replacements.
+    names: Vec<(String, usize)>,
+    prefilter: Option<Prefilter>,
+    /// `--files-matching`: a file is only searched if this matches somewhere in it. `^`
+    /// and `$` match at the start and end of each line.
+    files: Option<Regex>,
+}
+
+/// A literal every match contains, found with `memmem` far faster than the regex engine
+/// can rule a line out.
+struct Prefilter {
+    finder: memmem::Finder<'static>,
+    /// The literal is the whole pattern, so finding it means the line matches.
+    exact: bool,
+}
+
+enum Engine {
+    /// The set answers whether any pattern matches a line in a single pass; the combined
+    /// regex locates match spans.
+    Regex {
+        set: RegexSet,
+        regex: Regex,
+        /// Each pattern compiled alone, to tell which of them a match of `regex` is; empty
+        /// when there is only one.
+        alternatives: Vec<Regex>,
+        condition: Condition,
+    },
+    #[cfg(feature = "pcre2")]
+    Pcre2(crate::pcre2::Pattern),
+    Fuzzy(crate::fuzzy::Pattern),
+}
+
+/// How the regex set's results select a line, for `--all-match` and `--not`: the user's
+/// patterns come first in the set, each `--not` pattern after them.
+struct Condition {
+    patterns: usize,
+    /// Every pattern must match, rather than any.
+    all: bool,
+}
+
+impl Condition {
+    /// Whether the set's answers can't be had from `RegexSet::is_match` alone.
+    fn combines(&self, set: &RegexSet) -> bool {
+        self.all || set.len() > self.patterns
+    }
+
+    fn selects(&self, matched: &SetMatches) -> bool {
+        let found = matched
+            .iter()
+            .filter(|&index| index < self.patterns)
+            .count();
+        let excluded = matched.iter().any(|index| index >= self.patterns);
+        !excluded
+            && match self.all {
+                true => found == self.patterns,
+                false => found > 0,
+            }
+    }
+}
+
+impl Matcher {
+    /// Compiles the user's patterns, applying the matching options that rewrite them.
+    pub(crate) fn new(app: &SearchConfig) -> Result<Matcher, PatternError> {
+        let patterns = app
+            .patterns
+            .iter()
+            .map(|pattern| build_pattern(pattern, app))
+            .collect::<Vec<_>>();
+        let combined = patterns
+            .iter()
+            .map(|pattern| format!("(?:{})", pattern))
+            .collect::<Vec<_>>()
+            .join("|");
+
+        let combines = app.all_match || !app.not_patterns.is_empty();
+        let matcher = match app.engine {
+            _ if combines && app.multiline => Err(PatternError(
+                "--all-match and --not can't be used with --multiline".into(),
+            )),
+            _ if combines && app.fuzzy.is_some() => Err(PatternError(
+                "--all-match and --not can't be used with --fuzzy".into(),
+            )),
+            _ if app.fuzzy.is_some() => compile_fuzzy(app),
+            EngineChoice::Default => compile_regex(&patterns, &combined, app),
+            #[cfg(feature = "pcre2")]
+            EngineChoice::Pcre2 if combines => Err(PatternError(
+                "--all-match and --not need the default regex engine".into(),
+            )),
+            #[cfg(feature = "pcre2")]
+            EngineChoice::Pcre2 => compile_pcre2(&combined, app),
+            #[cfg(feature = "pcre2")]
+            EngineChoice::Auto if combines => compile_regex(&patterns, &combined, app),
+            #[cfg(feature = "pcre2")]
+            EngineChoice::Auto => compile_regex(&patterns, &combined, app)
+                .or_else(|err| compile_pcre2(&combined, app).map_err(|_| err)),
+            #[cfg(not(feature = "pcre2"))]
+            EngineChoice::Pcre2 => Err(PatternError(
+                "PCRE2 is not available: yagrep was built without the pcre2 feature".into(),
+            )),
+            #[cfg(not(feature = "pcre2"))]
+            EngineChoice::Auto => compile_regex(&patterns, &combined, app),
+        };
+        let mut matcher = matcher?;
+        if let Some(group) = &app.group {
+            if !matcher.names.iter().any(|(name, _)| name == group) {
+                return Err(PatternError(format!(
+                    "the pattern has no group named {}",
+                    group
+                )));
+            }
+        }
+        matcher.names.sort_by_key(|&(_, index)| index);
+        let files = app.files_matching.as_ref().map(|pattern| {
+            RegexBuilder::new(&build_pattern(pattern, app))
+                .case_insensitive(case_insensitive(app))
+                .multi_line(true)
+                .crlf(app.crlf)
+                .build()
+                .map_err(|err| PatternError(err.to_string()))
+        });
+        Ok(Matcher {
+            prefilter: prefilter(app),
+            files: files.transpose()?,
+            ..matcher
+        })
+    }
+
+    /// The names of the pattern's named groups, in the order they open.
+    pub(crate) fn group_names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.names.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub(crate) fn is_match(&self, line: &[u8]) -> bool {
+        if let Some(prefilter) = &self.prefilter {
+            if prefilter.finder.find(line).is_none() {
+                return false;
+            }
+            if prefilter.exact {
+                return true;
+            }
+        }
+        match &self.engine {
+            Engine::Regex { set, condition, .. } if condition.combines(set) => {
+                condition.selects(&set.matches(line))
+            }
+            Engine::Regex { set, .. } => set.is_match(line),
+            #[cfg(feature = "pcre2")]
+            Engine::Pcre2(pattern) => pattern.find_at(line, 0).is_some(),
+            Engine::Fuzzy(pattern) => pattern.find_at(line, 0).is_some(),
+        }
+    }
+
+    pub(crate) fn find_iter<'a>(
+        &'a self,
+        line: &'a [u8],
+    ) -> Box<dyn Iterator<Item = Found<'a>> + 'a> {
+        match &self.engine {
+            Engine::Regex { regex, .. } => Box::new(regex.find_iter(line).map(|found| Found {
+                haystack: line,
+                start: found.start(),
+                end: found.end(),
+            })),
+            #[cfg(feature = "pcre2")]
+            Engine::Pcre2(_) => Box::new(
+                self.captures_iter(line)
+                    .map(|captures| captures.get(0).unwrap()),
+            ),
+            Engine::Fuzzy(_) => Box::new(
+                self.captures_iter(line)
+                    .map(|captures| captures.get(0).unwrap()),
+            ),
+        }
+    }
+
+    /// Which pattern a match is of. The combined regex takes the first alternative that
+    /// matches where a match starts, so that's the first pattern to match there, searched
+    /// for in the whole haystack so `^` and `\b` see what precedes it. PCRE2 compiles the
+    /// patterns as one, so its matches are all credited to the first.
+    pub(crate) fn pattern_index(&self, found: &Found) -> usize {
+        match &self.engine {
+            Engine::Regex { alternatives, .. } => alternatives
+                .iter()
+                .position(|regex| {
+                    regex
+                        .find_at(found.haystack, found.start)
+                        .is_some_and(|at| at.start() == found.start)
+                })
+                .unwrap_or(0),
+            #[cfg(feature = "pcre2")]
+            Engine::Pcre2(_) => 0,
+            Engine::Fuzzy(pattern) => pattern
+                .find_at(found.as_bytes(), 0)
+                .map_or(0, |(_, index)| index),
+        }
+    }
+
+    /// Whether files must pass `--files-matching`, which takes all of their contents.
+    pub(crate) fn filters_files(&self) -> bool {
+        self.files.is_some()
+    }
+
+    /// Whether a file with `contents` is searched at all.
+    pub(crate) fn admits(&self, contents: &[u8]) -> bool {
+        self.files
+            .as_ref()
+            .is_none_or(|files| files.is_match(contents))
+    }
+
+    /// The literal every match contains, if the prefilter has found one.
+    pub(crate) fn literal(&self) -> Option<&[u8]> {
+        self.prefilter
+            .as_ref()
+            .map(|prefilter| prefilter.finder.needle())
+    }
+
+    /// Whether `text` certainly has no match, judged by the prefilter alone, so a whole
+    /// buffer can be ruled out before it's split into lines.
+    pub(crate) fn excludes(&self, text: &[u8]) -> bool {
+        self.prefilter
+            .as_ref()
+            .is_some_and(|prefilter| prefilter.finder.find(text).is_none())
+    }
+
+    /// Every match in `line` with its capture groups.
+    pub(crate) fn captures_iter<'a>(
+        &'a self,
+        line: &'a [u8],
+    ) -> Box<dyn Iterator<Item = Captures<'a>> + 'a> {
+        let names = &self.names;
+        match &self.engine {
+            Engine::Regex { regex, .. } => Box::new(regex.captures_iter(line).map(move |found| {
+                let groups = found
+                    .iter()
+                    .map(|group| group.map(|group| group.range()))
+                    .collect();
+                Captures {
+                    haystack: line,
+                    groups,
+                    names,
+                }
+            })),
+            #[cfg(feature = "pcre2")]
+            Engine::Pcre2(pattern) => {
+                let mut start = 0;
+                Box::new(std::iter::from_fn(move || {
+                    let groups = pattern.find_at(line, start)?;
+                    let found = groups[0].clone().unwrap();
+                    // Step past an empty match, to the next character.
+                    start = match found.is_empty() {
+                        true => next_boundary(line, found.end),
+                        false => found.end,
+                    };
+                    Some(Captures {
+                        haystack: line,
+                        groups,
+                        names,
+                    })
+                }))
+            }
+            Engine::Fuzzy(pattern) => {
+                let mut start = 0;
+                Box::new(std::iter::from_fn(move || {
+                    // Fuzzy matches are never empty: a pattern is longer than the edits allowed.
+                    let (found, _) = pattern.find_at(line, start)?;
+                    start = found.end;
+                    Some(Captures {
+                        haystack: line,
+                        groups: vec![Some(found)],
+                        names,
+                    })
+                }))
+            }
+        }
+    }
+}
+
+fn compile_regex(
+    patterns: &[String],
+    combined: &str,
+    app: &SearchConfig,
+) -> Result<Matcher, PatternError> {
+    let case_insensitive = case_insensitive(app);
+    // Across lines, `^` and `$` still mean the start and end of a line.
+    let multi_line = app.multiline;
+    let dot_matches_new_line = app.multiline && app.multiline_dotall;
+    let error = |err: regex::Error| PatternError(err.to_string());
+
+    let not_patterns = app
+        .not_patterns
+        .iter()
+        .map(|pattern| build_pattern(pattern, app));
+    let set = RegexSetBuilder::new(patterns.iter().cloned().chain(not_patterns))
+        .case_insensitive(case_insensitive)
+        .multi_line(multi_line)
+        .crlf(app.crlf)
+        .dot_matches_new_line(dot_matches_new_line)
+        .build()
+        .map_err(error)?;
+    let build = |pattern: &str| {
+        RegexBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .multi_line(multi_line)
+            .crlf(app.crlf)
+            .dot_matches_new_line(dot_matches_new_line)
+            .build()
+            .map_err(error)
+    };
+    let regex = build(combined)?;
+    let alternatives = match patterns.len() {
+        1 => Vec::new(),
+        _ => patterns
+            .iter()
+            .map(|pattern| build(pattern))
+            .collect::<Result<_, _>>()?,
+    };
+
+    let names = regex
+        .capture_names()
+        .enumerate()
+        .filter_map(|(index, name)| Some((name?.to_string(), index)))
+        .collect();
+    let condition = Condition {
+        patterns: patterns.len(),
+        all: app.all_match,
+    };
+    Ok(Matcher {
+        engine: Engine::Regex {
+            set,
+            regex,
+            alternatives,
+            condition,
+        },
+        names,
+        prefilter: None,
+        files: None,
+    })
+}
+
+#[cfg(feature = "pcre2")]
+fn compile_pcre2(combined: &str, app: &SearchConfig) -> Result<Matcher, PatternError> {
+    let pattern = crate::pcre2::Pattern::new(
+        combined,
+        case_insensitive(app),
+        app.multiline,
+        app.multiline && app.multiline_dotall,
+    )
+    .map_err(PatternError)?;
+    let names = pattern.names();
+    Ok(Matcher {
+        engine: Engine::Pcre2(pattern),
+        names,
+        prefilter: None,
+        files: None,
+    })
+}
+
+fn compile_fuzzy(app: &SearchConfig) -> Result<Matcher, PatternError> {
+    let pattern = crate::fuzzy::Pattern::new(
+        &app.patterns,
+        app.fuzzy.unwrap_or_default(),
+        case_insensitive(app),
+        app.word_regexp,
+        app.line_regexp,
+    )
+    .map_err(PatternError)?;
+    Ok(Matcher {
+        engine: Engine::Fuzzy(pattern),
+        names: Vec::new(),
+        prefilter: None,
+        files: None,
+    })
+}
+
+/// The prefilter for a single case-sensitive pattern: all of it if it's a literal,
+/// otherwise the literal it starts with. Alternation anywhere defeats it, since the
+/// prefix may then be only one alternative's.
+fn prefilter(app: &SearchConfig) -> Option<Prefilter> {
+    let [pattern] = app.patterns.as_slice() else {
+        return None;
+    };
+    // A fuzzy match needn't contain any of the pattern's text.
+    if case_insensitive(app) || app.fuzzy.is_some() {
+        return None;
+    }
+    let (literal, whole) = match app.fixed_strings {
+        true => (pattern.clone(), true),
+        false => literal_prefix(pattern)?,
+    };
+    if literal.is_empty() {
+        return None;
+    }
+    Some(Prefilter {
+        finder: memmem::Finder::new(literal.as_bytes()).into_owned(),
+        exact: whole && !app.word_regexp && !app.line_regexp && app.not_patterns.is_empty(),
+    })
+}
+
+/// The literal text a regex starts with, and whether that's the whole regex; `None` if
+/// it alternates. A leading `^` is skipped, and escaped punctuation is literal.
+fn literal_prefix(pattern: &str) -> Option<(String, bool)> {
+    let mut escaped = false;
+    for c in pattern.chars() {
+        if c == '|' && !escaped {
+            return None;
+        }
+        escaped = !escaped && c == '\\';
+    }
+
+    let mut literal = String::new();
+    let mut chars = pattern
+        .strip_prefix('^')
+        .unwrap_or(pattern)
+        .chars()
+        .peekable();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next() {
+                Some(c) if c.is_ascii_punctuation() => c,
+                _ => return Some((literal, false)),
+            },
+            '^' | '$' | '.' | '[' | ']' | '(' | ')' | '{' | '}' | '?' | '*' | '+' => {
+                return Some((literal, false))
+            }
+            c => c,
+        };
+        // A quantifier makes the character before it optional.
+        if matches!(chars.peek(), Some('?' | '*' | '{')) {
+            return Some((literal, false));
+        }
+        literal.push(c);
+    }
+    let whole = !pattern.starts_with('^');
+    Some((literal, whole))
+}
+
+/// The offset of the character after the one at `at`, skipping UTF-8 continuation bytes.
+#[cfg(feature = "pcre2")]
+fn next_boundary(line: &[u8], at: usize) -> usize {
+    let mut next = at + 1;
+    while next < line.len() && line[next] & 0xC0 == 0x80 {
+        next += 1;
+    }
+    next
+}
+
+/// One match: where it is in the text searched.
+#[derive(Clone, Copy)]
+pub(crate) struct Found<'a> {
+    haystack: &'a [u8],
+    start: usize,
+    end: usize,
+}
+
+impl<'a> Found<'a> {
+    pub(crate) fn start(&self) -> usize {
+        self.start
+    }
+
+    pub(crate) fn end(&self) -> usize {
+        self.end
+    }
+
+    pub(crate) fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub(crate) fn as_bytes(&self) -> &'a [u8] {
+        &self.haystack[self.start..self.end]
+    }
+}
+
+/// A match with its capture groups; group 0 is the whole match.
+pub(crate) struct Captures<'a> {
+    haystack: &'a [u8],
+    groups: Vec<Option<Range<usize>>>,
+    names: &'a [(String, usize)],
+}
+
+impl<'a> Captures<'a> {
+    pub(crate) fn get(&self, index: usize) -> Option<Found<'a>> {
+        let range = self.groups.get(index)?.clone()?;
+        Some(Found {
+            haystack: self.haystack,
+            start: range.start,
+            end: range.end,
+        })
+    }
+
+    /// The group called `name`, if there is one and it took part in the match.
+    pub(crate) fn named(&self, name: &str) -> Option<Found<'a>> {
+        let &(_, index) = self.names.iter().find(|(known, _)| known == name)?;
+        self.get(index)
+    }
+
+    /// Every group in pattern order, `None` for one that took no part in the match.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = Option<Found<'a>>> + '_ {
+        (0..self.groups.len()).map(|index| self.get(index))
+    }
+
+    /// Appends `replacement` to `expanded`, with `$1`, `${1}`, `$name` and `${name}`
+    /// replaced by the groups they name (nothing for a group that didn't match) and `$$`
+    /// by a `$`, following the `regex` crate's rules.
+    pub(crate) fn expand(&self, replacement: &[u8], expanded: &mut Vec<u8>) {
+        let is_name = |byte: &u8| byte.is_ascii_alphanumeric() || *byte == b'_';
+        let mut rest = replacement;
+        while let Some(dollar) = rest.iter().position(|&byte| byte == b'$') {
+            expanded.extend_from_slice(&rest[..dollar]);
+            rest = &rest[dollar + 1..];
+            if let Some(after) = rest.strip_prefix(b"$") {
+                expanded.push(b'$');
+                rest = after;
+                continue;
+            }
+            let (name, after) = match rest.strip_prefix(b"{") {
+                Some(braced) => match braced.iter().position(|&byte| byte == b'}') {
+                    Some(close) => (&braced[..close], &braced[close + 1..]),
+                    None => (&rest[..0], rest),
+                },
+                None => {
+                    let length = rest.iter().take_while(|byte| is_name(byte)).count();
+                    (&rest[..length], &rest[length..])
+                }
+            };
+            if name.is_empty() {
+                // Not a group reference, so the `$` stands for itself.
+                expanded.push(b'$');
+                continue;
+            }
+            let name = String::from_utf8_lossy(name);
+            let group = match name.parse::<usize>() {
+                Ok(index) => self.get(index),
+                Err(_) => self.named(&name),
+            };
+            if let Some(group) = group {
+                expanded.extend_from_slice(group.as_bytes());
+            }
+            rest = after;
+        }
+        expanded.extend_from_slice(rest);
+    }
+}
+
+/// Whether to ignore case: with `-i`, or with `-S` when no pattern has an uppercase
+/// letter. In a regex, a letter after a backslash is an escape like `\S` or `\W` rather
+/// than a literal, so it doesn't count.
+fn case_insensitive(app: &SearchConfig) -> bool {
+    let has_uppercase = |pattern: &String| {
+        let mut escaped = false;
+        pattern.chars().any(|c| {
+            let literal = app.fixed_strings || !escaped;
+            escaped = !escaped && c == '\\';
+            literal && c.is_uppercase()
+        })
+    };
+    app.case_insensitive || (app.smart_case && !app.patterns.iter().any(has_uppercase))
+}
+
+fn build_pattern(pattern: &str, app: &SearchConfig) -> String {
+    let pattern = match app.fixed_strings {
+        true => regex::escape(pattern),
+        false => pattern.to_string(),
+    };
+    if app.line_regexp {
+        // A whole-line match already implies word boundaries, so `-x` wins over `-w`.
+        format!("^(?:{})$", pattern)
+    } else if app.word_regexp {
+        // Group the pattern so boundaries apply to every alternative, not just the outer ones.
+        format!(r"\b(?:{})\b", pattern)
+    } else {
+        pattern
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The pattern index of every match of `patterns` in `line`, with its text.
+    fn credited(patterns: &[&str], line: &str) -> Vec<(String, usize)> {
+        let mut app = SearchConfig::new();
+        for pattern in patterns {
+            app.pattern(pattern);
+        }
+        let matcher = Matcher::new(&app).unwrap();
+        matcher
+            .find_iter(line.as_bytes())
+            .map(|found| {
+                let text = String::from_utf8_lossy(found.as_bytes()).into_owned();
+                (text, matcher.pattern_index(&found))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn overlapping_patterns_credit_the_one_that_matched() {
+        // `oo` is inside the match, but `foo` is what matched.
+        assert_eq!(credited(&["oo", "foo"], "foo"), [("foo".to_string(), 1)]);
+        assert_eq!(credited(&["foo", "oo"], "foo"), [("foo".to_string(), 0)]);
+        assert_eq!(
+            credited(&["oo", "foo"], "foo boo"),
+            [("foo".to_string(), 1), ("oo".to_string(), 0)]
+        );
+    }
+
+    #[test]
+    fn earlier_patterns_win_at_the_same_start() {
+        assert_eq!(credited(&["fo", "foo"], "foo"), [("fo".to_string(), 0)]);
+    }
+
+    #[test]
+    fn anchors_keep_their_context() {
+        // The text `bar` alone starts at a word boundary, but inside `foobar` it doesn't.
+        assert_eq!(
+            credited(&["\\bbar", "bar"], "foobar bar"),
+            [("bar".to_string(), 1), ("bar".to_string(), 0)]
+        );
+    }
+}
@@ -0,0 +1,248 @@
+//! [`Searcher::search`](crate::Searcher::search): the selected lines as values instead
+//! of printed text. Files are walked and read only as the iterator is advanced, so
+//! dropping it part way stops the search.
+
+use std::io::{BufRead, BufReader, Read};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::config::SearchConfig;
+use crate::matcher::Matcher;
+use crate::vfs::{self, MemoryFs};
+use crate::walker::{self, Job};
+use crate::{encoding, input, lines};
+
+/// A selected line.
+pub struct Match {
+    /// The file the line is in, or `None` for standard input.
+    pub path: Option<PathBuf>,
+    /// 1-based.
+    pub line_number: usize,
+    /// Where the line starts in the file's (decoded) contents.
+    pub offset: usize,
+    /// The line without its terminator.
+    pub line: Vec<u8>,
+    /// Byte ranges within `line` of every match, empty for lines selected by
+    /// `invert_match`.
+    pub spans: Vec<Range<usize>>,
+    /// For each span, the ranges of its capture groups in pattern order; a group that
+    /// took no part in the match is `None`.
+    pub captures: Vec<Vec<Option<Range<usize>>>>,
+}
+
+/// Iterator over every line a search selects, in walk order.
+pub struct Matches<'a> {
+    matcher: &'a Matcher,
+    app: &'a SearchConfig,
+    /// Where files are read from when not from the disk.
+    fs: Option<&'a MemoryFs>,
+    /// Jobs not started yet, the next one last.
+    jobs: Vec<Job>,
+    current: Option<Input<'a>>,
+}
+
+/// The input being read.
+struct Input<'a> {
+    path: Option<PathBuf>,
+    lines: lines::Lines<Box<dyn BufRead + 'a>>,
+    line_number: usize,
+    /// Lines selected so far, for `max_count`.
+    selected: usize,
+}
+
+impl<'a> Input<'a> {
+    fn new(path: Option<PathBuf>, reader: Box<dyn BufRead + 'a>, app: &SearchConfig) -> Input<'a> {
+        Input {
+            path,
+            lines: lines::Lines::new(reader)
+                .terminated_by(app.record_separator)
+                .crlf(app.crlf),
+            line_number: 0,
+            selected: 0,
+        }
+    }
+}
+
+impl<'a> Matches<'a> {
+    pub(crate) fn new(matcher: &'a Matcher, app: &'a SearchConfig) -> Matches<'a> {
+        let mut matches = Matches {
+            matcher,
+            app,
+            fs: None,
+            jobs: Vec::new(),
+            current: None,
+        };
+        match &app.path {
+            None => {
+                let stdin = Box::new(std::io::stdin().lock());
+                matches.current = decode(stdin, app)
+                    .ok()
+                    .map(|reader| Input::new(None, reader, app));
+            }
+            Some(path) if path.is_dir() => matches.jobs.push(walker::root(path, app)),
+            // As in a walk, a FIFO, socket or device is only read with `--devices read`.
+            Some(path) if path.exists() && !path.is_file() && !app.read_devices => {}
+            Some(path) => matches.jobs.push(Job::File(path.clone(), Vec::new())),
+        }
+        matches
+    }
+
+    /// Searches the files of `fs` instead, below `app.path` or all of them.
+    pub(crate) fn in_memory(
+        matcher: &'a Matcher,
+        app: &'a SearchConfig,
+        fs: &'a MemoryFs,
+    ) -> Matches<'a> {
+        let path = app.path.as_deref().unwrap_or(Path::new(""));
+        let job = match fs.is_dir(path) {
+            true => vfs::root(fs, path, app),
+            false => Job::File(path.to_path_buf(), Vec::new()),
+        };
+        Matches {
+            matcher,
+            app,
+            fs: Some(fs),
+            jobs: vec![job],
+            current: None,
+        }
+    }
+
+    /// Moves on to the next file, listing directories on the way.
+    fn advance(&mut self) -> bool {
+        while let Some(job) = self.jobs.pop() {
+            match job {
+                Job::Directory(path, ignore, ancestors, key) => {
+                    // Directories are only walked when the searched path is one.
+                    let root = self.app.path.as_deref().unwrap_or(&path);
+                    let listed = match self.fs {
+                        Some(fs) => vfs::list_directory(fs, root, &path, &ignore, self.app, &key),
+                        None => {
+                            walker::list_directory(root, &path, &ignore, &ancestors, self.app, &key)
+                        }
+                    };
+                    self.jobs.extend(listed.into_iter().rev());
+                }
+                Job::File(path, _) => {
+                    let opened = match self.fs {
+                        Some(fs) => open_in(fs, &path, self.app),
+                        None => open(&path, self.app),
+                    };
+                    let opened = opened.and_then(|reader| admit(self.matcher, reader));
+                    if let Ok(reader) = opened {
+                        self.current = Some(Input::new(Some(path), reader, self.app));
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+}
+
+impl Iterator for Matches<'_> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        loop {
+            let Some(input) = &mut self.current else {
+                if !self.advance() {
+                    return None;
+                }
+                continue;
+            };
+            let done = self.app.max_count.is_some_and(|max| input.selected >= max);
+            let Some((offset, line)) = input.lines.next().filter(|_| !done) else {
+                self.current = None;
+                continue;
+            };
+            input.line_number += 1;
+            if self.matcher.is_match(&line) == self.app.invert_match {
+                continue;
+            }
+            input.selected += 1;
+
+            let mut spans = Vec::new();
+            let mut captures = Vec::new();
+            if !self.app.invert_match {
+                for found in self.matcher.captures_iter(&line) {
+                    spans.push(found.get(0).unwrap().range());
+                    let groups = found.iter().skip(1);
+                    captures.push(
+                        groups
+                            .map(|group| group.map(|group| group.range()))
+                            .collect(),
+                    );
+                }
+            }
+            return Some(Match {
+                path: input.path.clone(),
+                line_number: input.line_number,
+                offset,
+                line,
+                spans,
+                captures,
+            });
+        }
+    }
+}
+
+/// Opens a file the way the printing search reads it: through the `--pre` command or
+/// a decompressor when one applies. Binary files are skipped unless `text` is set.
+fn open(path: &Path, app: &SearchConfig) -> std::io::Result<Box<dyn BufRead>> {
+    let decompressor = input::decompressor(path).filter(|_| app.search_zip);
+    let reader: Box<dyn Read> = match (app.preprocessor_for(path), decompressor) {
+        (Some(program), _) => Box::new(input::Piped::preprocess(program, path)?),
+        (None, Some(command)) => Box::new(input::Piped::decompress(command, path)?),
+        (None, None) => Box::new(std::fs::File::open(path)?),
+    };
+    let mut reader = decode(Box::new(BufReader::new(reader)), app)?;
+    if !app.text && lines::is_binary(reader.fill_buf()?, app.record_separator) {
+        return Err(std::io::ErrorKind::InvalidData.into());
+    }
+    Ok(reader)
+}
+
+/// Opens a file of `fs`, which is neither preprocessed nor decompressed: there are no
+/// programs to run.
+fn open_in<'a>(
+    fs: &'a MemoryFs,
+    path: &Path,
+    app: &SearchConfig,
+) -> std::io::Result<Box<dyn BufRead + 'a>> {
+    let contents = fs.read(path).ok_or(std::io::ErrorKind::NotFound)?;
+    let mut reader = decode(Box::new(contents), app)?;
+    if !app.text && lines::is_binary(reader.fill_buf()?, app.record_separator) {
+        return Err(std::io::ErrorKind::InvalidData.into());
+    }
+    Ok(reader)
+}
+
+/// Reads all of `reader` when `--files-matching` has to see it, failing if the file
+/// doesn't qualify.
+fn admit<'a>(
+    matcher: &Matcher,
+    mut reader: Box<dyn BufRead + 'a>,
+) -> std::io::Result<Box<dyn BufRead + 'a>> {
+    if !matcher.filters_files() {
+        return Ok(reader);
+    }
+    let mut contents = Vec::new();
+    reader.read_to_end(&mut contents)?;
+    match matcher.admits(&contents) {
+        true => Ok(Box::new(std::io::Cursor::new(contents))),
+        false => Err(std::io::ErrorKind::NotFound.into()),
+    }
+}
+
+/// Transcodes `reader` to UTF-8 as `match_reader` does.
+fn decode<'a>(
+    mut reader: Box<dyn BufRead + 'a>,
+    app: &SearchConfig,
+) -> std::io::Result<Box<dyn BufRead + 'a>> {
+    let (encoding, bom) = encoding::detect(reader.fill_buf()?, app.encoding);
+    reader.consume(bom);
+    Ok(match encoding {
+        encoding::Encoding::Utf8 => reader,
+        encoding => Box::new(BufReader::new(encoding::Decoder::new(reader, encoding))),
+    })
+}
@@ -0,0 +1,91 @@
+//! Read-only memory maps, so large files are searched in place instead of being copied
+//! through a read buffer.
+//!
+//! Mapping a file that another process truncates while it's being searched raises
+//! `SIGBUS`, as with every mmap-based tool; `--no-mmap` avoids that on volatile trees.
+
+/// Files at least this large are mapped unless `--no-mmap` is given. Below it, setting
+/// up and tearing down the mapping costs more than reading the file.
+pub const THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// The contents of a file mapped into memory, unmapped on drop.
+pub struct Mmap {
+    ptr: *mut std::ffi::c_void,
+    len: usize,
+}
+
+impl Mmap {
+    /// Maps all of `file`, or returns `None` where mapping isn't supported or fails, in
+    /// which case the caller should read the file instead.
+    #[cfg(unix)]
+    pub fn map(file: &std::fs::File) -> Option<Mmap> {
+        use std::os::unix::io::AsRawFd;
+
+        let len = usize::try_from(file.metadata().ok()?.len()).ok()?;
+        // A zero-length mapping is an error; there's nothing to search anyway.
+        if len == 0 {
+            return None;
+        }
+        // SAFETY: a fresh private, read-only mapping of a file we hold open; it aliases
+        // no Rust memory and is only read through `Deref`.
+        let ptr = unsafe {
+            sys::mmap(
+                std::ptr::null_mut(),
+                len,
+                sys::PROT_READ,
+                sys::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == sys::MAP_FAILED {
+            return None;
+        }
+        Some(Mmap { ptr, len })
+    }
+
+    #[cfg(not(unix))]
+    pub fn map(_file: &std::fs::File) -> Option<Mmap> {
+        None
+    }
+}
+
+impl std::ops::Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: `ptr` points to `len` readable bytes until `drop` unmaps them.
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: unmaps exactly the region `map` created, which is no longer borrowed.
+        #[cfg(unix)]
+        unsafe {
+            sys::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::ffi::{c_int, c_long, c_void};
+
+    pub const PROT_READ: c_int = 1;
+    pub const MAP_PRIVATE: c_int = 2;
+    pub const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+    extern "C" {
+        pub fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: c_long,
+        ) -> *mut c_void;
+        pub fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+}
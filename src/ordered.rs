@@ -0,0 +1,101 @@
+//! Prints the results of a parallel walk in the order a sequential walk would have.
+//!
+//! Every job is identified by a key: the index of each directory entry on the way from
+//! the root to it, so the entries of a directory extend the directory's key. Finished
+//! results wait in a tree mirroring the walk until everything before them is printed.
+
+use std::sync::Mutex;
+
+enum Node {
+    /// Not searched or listed yet.
+    Pending,
+    /// A searched file's output.
+    Output(Vec<u8>),
+    /// A listed directory; the entries before `next` have been printed.
+    Directory { entries: Vec<Node>, next: usize },
+    /// Printed, along with everything inside it.
+    Done,
+}
+
+pub struct Ordered<F: Fn(&[u8])> {
+    root: Mutex<Node>,
+    emit: F,
+}
+
+impl<F: Fn(&[u8])> Ordered<F> {
+    /// Passes output to `emit` in walk order, one file at a time.
+    pub fn new(emit: F) -> Ordered<F> {
+        Ordered {
+            root: Mutex::new(Node::Pending),
+            emit,
+        }
+    }
+
+    /// Records that the directory at `key` was listed and queued `entries` jobs.
+    pub fn directory(&self, key: &[usize], entries: usize) {
+        self.finish(
+            key,
+            Node::Directory {
+                entries: (0..entries).map(|_| Node::Pending).collect(),
+                next: 0,
+            },
+        );
+    }
+
+    /// Records the output of the file at `key`.
+    pub fn file(&self, key: &[usize], output: Vec<u8>) {
+        self.finish(key, Node::Output(output));
+    }
+
+    /// Prints the results still waiting, in walk order, passing over the jobs that will
+    /// never finish because the walk was stopped.
+    pub fn abandon(&self) {
+        flush(&mut self.root.lock().unwrap(), &self.emit, true);
+    }
+
+    fn finish(&self, key: &[usize], finished: Node) {
+        let mut root = self.root.lock().unwrap();
+        *at(&mut root, key) = finished;
+        flush(&mut root, &self.emit, false);
+    }
+}
+
+/// The node at `key` below `root`.
+fn at<'a>(root: &'a mut Node, key: &[usize]) -> &'a mut Node {
+    let mut node = root;
+    for &index in key {
+        match node {
+            Node::Directory { entries, .. } => node = &mut entries[index],
+            _ => unreachable!("entries are only queued once their directory is listed"),
+        }
+    }
+    node
+}
+
+/// Prints every finished result at the front of the tree. The first unprinted node is
+/// found again from the root after each one, rather than by recursion, so a deep tree
+/// can't overflow the stack. With `abandoned`, pending nodes are passed over as printed.
+fn flush(root: &mut Node, emit: &impl Fn(&[u8]), abandoned: bool) {
+    // The key of the first node that hasn't been printed.
+    let mut key = Vec::new();
+    loop {
+        let node = at(root, &key);
+        match node {
+            Node::Pending if abandoned => {}
+            Node::Pending | Node::Done => return,
+            Node::Output(output) => emit(output),
+            Node::Directory { entries, next } if *next < entries.len() => {
+                key.push(*next);
+                continue;
+            }
+            Node::Directory { .. } => {}
+        }
+        *node = Node::Done;
+        if key.pop().is_none() {
+            return;
+        }
+        if let Node::Directory { next, .. } = at(root, &key) {
+            *next += 1;
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Paging results on a terminal, as git does: they're piped into `$PAGER`, or `less -R`
+//! when it's unset. Unless `$LESS` says otherwise, less is started with `FRX`, so results
+//! that fit on one screen are printed as they are and it quits without waiting for a key.
+
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+/// The pager used when `$PAGER` is unset.
+const DEFAULT_PAGER: &str = "less -R";
+
+pub struct Pager(Child);
+
+impl Pager {
+    /// Starts the pager, returning it with the pipe to write results into. `None` when
+    /// paging is turned off with an empty `$PAGER` or `cat`, or the pager can't be run,
+    /// in which case results are printed directly.
+    pub fn spawn() -> Option<(Pager, ChildStdin)> {
+        let pager = std::env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_string());
+        let mut words = pager.split_whitespace();
+        let program = words.next().filter(|&program| program != "cat")?;
+        let mut command = Command::new(program);
+        command.args(words).stdin(Stdio::piped());
+        if std::env::var_os("LESS").is_none() {
+            command.env("LESS", "FRX");
+        }
+        let mut child = command.spawn().ok()?;
+        let stdin = child.stdin.take()?;
+        Some((Pager(child), stdin))
+    }
+
+    /// Waits for the reader to quit the pager. Its pipe must be closed first, or the
+    /// pager would wait for more results forever.
+    pub fn wait(mut self) {
+        let _ = self.0.wait();
+    }
+}
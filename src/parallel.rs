@@ -0,0 +1,92 @@
+//! A small work-stealing pool for the parallel directory walk.
+//!
+//! Each worker owns a deque: it pushes and pops jobs at the back, so walking stays
+//! depth-first and cache-friendly, and an idle worker steals from the front of another
+//! worker's deque, taking the oldest (and usually largest) piece of remaining work.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+struct Pool<T> {
+    queues: Vec<Mutex<VecDeque<T>>>,
+    /// Jobs pushed but not yet finished; the walk is over when this reaches zero.
+    pending: AtomicUsize,
+    quit: AtomicBool,
+    idle: Mutex<()>,
+    wake: Condvar,
+}
+
+/// A worker's handle for queueing more jobs from inside a job.
+pub struct Worker<'a, T> {
+    pool: &'a Pool<T>,
+    index: usize,
+}
+
+impl<T> Worker<'_, T> {
+    pub fn push(&self, job: T) {
+        self.pool.pending.fetch_add(1, Ordering::SeqCst);
+        self.pool.queues[self.index].lock().unwrap().push_back(job);
+        self.pool.wake.notify_one();
+    }
+
+    /// Stops every worker once their current jobs finish, dropping queued jobs.
+    pub fn quit(&self) {
+        self.pool.quit.store(true, Ordering::SeqCst);
+        self.pool.wake.notify_all();
+    }
+
+    fn next(&self) -> Option<T> {
+        if let Some(job) = self.pool.queues[self.index].lock().unwrap().pop_back() {
+            return Some(job);
+        }
+        let count = self.pool.queues.len();
+        (1..count)
+            .map(|offset| (self.index + offset) % count)
+            .find_map(|victim| self.pool.queues[victim].lock().unwrap().pop_front())
+    }
+}
+
+/// Runs `handle` on every job in `jobs` and on every job handlers push, using
+/// `threads` workers, and returns once all of them are done.
+pub fn run<T: Send>(threads: usize, jobs: Vec<T>, handle: impl Fn(T, &Worker<T>) + Sync) {
+    let threads = threads.max(1);
+    let pool = Pool {
+        queues: (0..threads).map(|_| Mutex::new(VecDeque::new())).collect(),
+        pending: AtomicUsize::new(jobs.len()),
+        quit: AtomicBool::new(false),
+        idle: Mutex::new(()),
+        wake: Condvar::new(),
+    };
+    // Queue in reverse so the first job is popped first.
+    pool.queues[0]
+        .lock()
+        .unwrap()
+        .extend(jobs.into_iter().rev());
+
+    std::thread::scope(|scope| {
+        for index in 0..threads {
+            let pool = &pool;
+            let handle = &handle;
+            scope.spawn(move || {
+                let worker = Worker { pool, index };
+                while !pool.quit.load(Ordering::SeqCst) {
+                    if let Some(job) = worker.next() {
+                        handle(job, &worker);
+                        pool.pending.fetch_sub(1, Ordering::SeqCst);
+                        continue;
+                    }
+                    if pool.pending.load(Ordering::SeqCst) == 0 {
+                        pool.wake.notify_all();
+                        break;
+                    }
+                    // Another worker is still busy and may push more jobs. The timeout
+                    // covers a notification sent between the checks above and the wait.
+                    let idle = pool.idle.lock().unwrap();
+                    let _ = pool.wake.wait_timeout(idle, Duration::from_millis(1));
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,243 @@
+//! PCRE2 patterns, behind the `pcre2` feature, for what the `regex` crate leaves out:
+//! look-around and backreferences. Binds the system's libpcre2-8 directly.
+//!
+//! Patterns are compiled for UTF-8 but match invalid UTF-8 too, like the default engine,
+//! and JIT-compiled where PCRE2 supports it.
+
+use std::ffi::{c_int, c_void};
+use std::ops::Range;
+
+/// A compiled pattern, freed on drop.
+pub(crate) struct Pattern {
+    code: *mut sys::Code,
+    /// Capture groups, not counting the whole match.
+    capture_count: usize,
+}
+
+// SAFETY: compiled code is read-only once JIT compilation is done, and PCRE2 documents it
+// as safe to share between threads; each match gets its own match data.
+unsafe impl Send for Pattern {}
+unsafe impl Sync for Pattern {}
+
+impl Pattern {
+    /// Compiles `pattern`, or returns PCRE2's message for why it doesn't compile.
+    pub(crate) fn new(
+        pattern: &str,
+        case_insensitive: bool,
+        multi_line: bool,
+        dot_matches_new_line: bool,
+    ) -> Result<Pattern, String> {
+        let mut options = sys::UTF | sys::UCP | sys::MATCH_INVALID_UTF;
+        if case_insensitive {
+            options |= sys::CASELESS;
+        }
+        if multi_line {
+            options |= sys::MULTILINE;
+        }
+        if dot_matches_new_line {
+            options |= sys::DOTALL;
+        }
+
+        let mut error = 0;
+        let mut error_offset = 0;
+        // SAFETY: the pattern pointer and length describe a live string; the outputs are
+        // locals and a null context selects the defaults.
+        let code = unsafe {
+            sys::pcre2_compile_8(
+                pattern.as_ptr(),
+                pattern.len(),
+                options,
+                &mut error,
+                &mut error_offset,
+                std::ptr::null_mut(),
+            )
+        };
+        if code.is_null() {
+            return Err(message(error));
+        }
+        // Without JIT support the interpreter matches instead, so failure is ignored.
+        // SAFETY: `code` was just compiled and isn't shared yet.
+        unsafe { sys::pcre2_jit_compile_8(code, sys::JIT_COMPLETE) };
+
+        let mut capture_count: u32 = 0;
+        // SAFETY: CAPTURECOUNT writes a `uint32_t`.
+        unsafe {
+            sys::pcre2_pattern_info_8(
+                code,
+                sys::INFO_CAPTURECOUNT,
+                &mut capture_count as *mut u32 as *mut c_void,
+            )
+        };
+        Ok(Pattern {
+            code,
+            capture_count: capture_count as usize,
+        })
+    }
+
+    /// Every named group with its index.
+    pub(crate) fn names(&self) -> Vec<(String, usize)> {
+        let mut count: u32 = 0;
+        let mut entry_size: u32 = 0;
+        let mut table: *const u8 = std::ptr::null();
+        // SAFETY: NAMECOUNT and NAMEENTRYSIZE write a `uint32_t`, NAMETABLE a pointer.
+        unsafe {
+            sys::pcre2_pattern_info_8(
+                self.code,
+                sys::INFO_NAMECOUNT,
+                &mut count as *mut u32 as *mut c_void,
+            );
+            sys::pcre2_pattern_info_8(
+                self.code,
+                sys::INFO_NAMEENTRYSIZE,
+                &mut entry_size as *mut u32 as *mut c_void,
+            );
+            sys::pcre2_pattern_info_8(
+                self.code,
+                sys::INFO_NAMETABLE,
+                &mut table as *mut *const u8 as *mut c_void,
+            );
+        }
+        if count == 0 || table.is_null() {
+            return Vec::new();
+        }
+        // SAFETY: the table holds `count` entries of `entry_size` bytes and lives as long
+        // as the compiled code.
+        let table =
+            unsafe { std::slice::from_raw_parts(table, count as usize * entry_size as usize) };
+        // Each entry is the group number, two bytes big-endian, then the name and a NUL.
+        table
+            .chunks(entry_size as usize)
+            .map(|entry| {
+                let index = usize::from(entry[0]) << 8 | usize::from(entry[1]);
+                let name = entry[2..].split(|&byte| byte == 0).next().unwrap_or(&[]);
+                (String::from_utf8_lossy(name).into_owned(), index)
+            })
+            .collect()
+    }
+
+    /// The first match in `subject` at or after `start`, as the range of every group
+    /// (group 0 is the whole match), or `None` if there is no match.
+    pub(crate) fn find_at(
+        &self,
+        subject: &[u8],
+        start: usize,
+    ) -> Option<Vec<Option<Range<usize>>>> {
+        if start > subject.len() {
+            return None;
+        }
+        // SAFETY: a null context selects the default allocator.
+        let data =
+            unsafe { sys::pcre2_match_data_create_from_pattern_8(self.code, std::ptr::null_mut()) };
+        if data.is_null() {
+            return None;
+        }
+        // SAFETY: the subject pointer and length describe a live slice, and `data` was
+        // created for this pattern.
+        let found = unsafe {
+            sys::pcre2_match_8(
+                self.code,
+                subject.as_ptr(),
+                subject.len(),
+                start,
+                0,
+                data,
+                std::ptr::null_mut(),
+            )
+        };
+        let groups = (found > 0).then(|| {
+            // SAFETY: the ovector holds a start and end for every group of the pattern,
+            // and stays valid until `data` is freed.
+            let ovector = unsafe {
+                std::slice::from_raw_parts(
+                    sys::pcre2_get_ovector_pointer_8(data),
+                    2 * (self.capture_count + 1),
+                )
+            };
+            ovector
+                .chunks(2)
+                .enumerate()
+                .map(|(index, pair)| {
+                    // Only the first `found` pairs are set; `\K` can start a match after
+                    // its end, which is treated as empty.
+                    (index < found as usize && pair[0] != sys::UNSET)
+                        .then(|| pair[0].min(pair[1])..pair[1])
+                })
+                .collect()
+        });
+        // SAFETY: `data` isn't used after this.
+        unsafe { sys::pcre2_match_data_free_8(data) };
+        groups
+    }
+}
+
+impl Drop for Pattern {
+    fn drop(&mut self) {
+        // SAFETY: the code is no longer borrowed by any match.
+        unsafe { sys::pcre2_code_free_8(self.code) };
+    }
+}
+
+/// PCRE2's text for a compile error code.
+fn message(error: c_int) -> String {
+    let mut buffer = [0u8; 256];
+    // SAFETY: PCRE2 writes at most `buffer.len()` bytes, NUL included.
+    let length =
+        unsafe { sys::pcre2_get_error_message_8(error, buffer.as_mut_ptr(), buffer.len()) };
+    match usize::try_from(length) {
+        Ok(length) => String::from_utf8_lossy(&buffer[..length]).into_owned(),
+        Err(_) => format!("PCRE2 error {}", error),
+    }
+}
+
+mod sys {
+    use std::ffi::{c_int, c_void};
+
+    /// `pcre2_code_8`, opaque.
+    pub enum Code {}
+    /// `pcre2_match_data_8`, opaque.
+    pub enum MatchData {}
+
+    pub const CASELESS: u32 = 0x0000_0008;
+    pub const DOTALL: u32 = 0x0000_0020;
+    pub const MULTILINE: u32 = 0x0000_0400;
+    pub const UCP: u32 = 0x0002_0000;
+    pub const UTF: u32 = 0x0008_0000;
+    pub const MATCH_INVALID_UTF: u32 = 0x0400_0000;
+    pub const JIT_COMPLETE: u32 = 0x0000_0001;
+    pub const INFO_CAPTURECOUNT: u32 = 4;
+    pub const INFO_NAMECOUNT: u32 = 17;
+    pub const INFO_NAMEENTRYSIZE: u32 = 18;
+    pub const INFO_NAMETABLE: u32 = 19;
+    pub const UNSET: usize = usize::MAX;
+
+    #[link(name = "pcre2-8")]
+    extern "C" {
+        pub fn pcre2_compile_8(
+            pattern: *const u8,
+            length: usize,
+            options: u32,
+            error: *mut c_int,
+            error_offset: *mut usize,
+            context: *mut c_void,
+        ) -> *mut Code;
+        pub fn pcre2_get_error_message_8(error: c_int, buffer: *mut u8, length: usize) -> c_int;
+        pub fn pcre2_jit_compile_8(code: *mut Code, options: u32) -> c_int;
+        pub fn pcre2_pattern_info_8(code: *const Code, what: u32, info: *mut c_void) -> c_int;
+        pub fn pcre2_match_data_create_from_pattern_8(
+            code: *const Code,
+            context: *mut c_void,
+        ) -> *mut MatchData;
+        pub fn pcre2_match_8(
+            code: *const Code,
+            subject: *const u8,
+            length: usize,
+            start: usize,
+            options: u32,
+            data: *mut MatchData,
+            context: *mut c_void,
+        ) -> c_int;
+        pub fn pcre2_get_ovector_pointer_8(data: *mut MatchData) -> *const usize;
+        pub fn pcre2_match_data_free_8(data: *mut MatchData);
+        pub fn pcre2_code_free_8(code: *mut Code);
+    }
+}
@@ -0,0 +1,952 @@
+//! Printing the lines a file's search selects, in each output format: the default
+//! grep-style lines with context, `--json`, `--vimgrep`, `-l`/`-L` and counts.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use crate::config::{FormatChoice, SearchConfig};
+use crate::lines::Lines;
+use crate::matcher::{Captures, Matcher};
+use crate::searcher::STDIN_LABEL;
+use crate::sink::{self, Sink, SinkLine};
+use crate::theme::Element;
+use crate::{csv, github, glob, json, sarif, template, theme};
+
+/// Prints `Binary file X matches` if any line is selected, instead of the lines.
+pub(crate) fn binary_contents(
+    matcher: &Matcher,
+    label: &str,
+    lines: Lines<impl BufRead>,
+    app: &SearchConfig,
+    writer: &mut Vec<u8>,
+) -> bool {
+    let mut sink = Binary { writer };
+    let selected = sink::drive(matcher, label, lines, app, &mut sink);
+    sink.matched(selected)
+}
+
+/// Searches the lines and prints the result through the sink of the selected output
+/// mode.
+/// Returns whether anything matched, which decides the exit status.
+pub(crate) fn match_contents(
+    matcher: &Matcher,
+    label: &str,
+    lines: Lines<impl BufRead>,
+    app: &SearchConfig,
+    writer: &mut Vec<u8>,
+) -> bool {
+    let mut sink: Box<dyn Sink + '_> = if app.quiet {
+        Box::new(Quiet)
+    } else if app.summary {
+        Box::new(SummarySink {
+            matcher,
+            app,
+            count: 0,
+        })
+    } else if app.tally {
+        Box::new(TallySink {
+            matcher,
+            app,
+            found: HashMap::new(),
+        })
+    } else if app.json {
+        Box::new(Json::new(matcher, app, writer))
+    } else if let Some(format) = app.format {
+        Box::new(Structured {
+            matcher,
+            app,
+            writer,
+            format,
+            label: String::new(),
+            relative: String::new(),
+            metadata: None,
+        })
+    } else if app.vimgrep {
+        Box::new(Vimgrep {
+            matcher,
+            app,
+            writer,
+            label: String::new(),
+        })
+    } else if app.files_with_matches || app.files_without_match {
+        Box::new(List { app, writer })
+    } else if app.count || app.count_matches {
+        Box::new(Count::new(matcher, app, writer))
+    } else {
+        Box::new(Standard::new(matcher, app, writer))
+    };
+    let selected = sink::drive(matcher, label, lines, app, sink.as_mut());
+    sink.matched(selected)
+}
+
+/// The default output: grep-style lines, with context, headings, `-o` and `--replace`.
+struct Standard<'a> {
+    matcher: &'a Matcher,
+    app: &'a SearchConfig,
+    writer: &'a mut Vec<u8>,
+    label: String,
+    /// Whether lines are attributed to the file, worked out once per file.
+    filename: bool,
+    last_printed: Option<usize>,
+}
+
+impl<'a> Standard<'a> {
+    fn new(matcher: &'a Matcher, app: &'a SearchConfig, writer: &'a mut Vec<u8>) -> Standard<'a> {
+        Standard {
+            matcher,
+            app,
+            writer,
+            label: String::new(),
+            filename: app.use_filename(),
+            last_printed: None,
+        }
+    }
+
+    /// Writes what comes before line `line_number`: the heading above the file's first
+    /// line, or a `--` between groups of context that aren't contiguous.
+    fn start_line(&mut self, line_number: usize) {
+        let app = self.app;
+        if self.filename && app.use_heading() && self.last_printed.is_none() {
+            let terminator = app.path_terminator("\n");
+            write!(
+                self.writer,
+                "{}{}",
+                app.theme.paint(Element::Path, &self.label),
+                terminator
+            )
+            .unwrap();
+        }
+        let (before_context, after_context) = self.context();
+        let has_context = before_context > 0 || after_context > 0;
+        if has_context && self.last_printed.is_some_and(|last| line_number > last + 1) {
+            writeln!(self.writer, "--").unwrap();
+        }
+        self.last_printed = Some(line_number);
+    }
+
+    /// Writes out what has been printed so far when passing stdin through, rather than
+    /// once the input ends, which may be never.
+    fn stream(&mut self) {
+        if self.app.passthru && self.label == STDIN_LABEL {
+            self.app.emit(self.writer);
+            self.writer.clear();
+        }
+    }
+}
+
+impl Sink for Standard<'_> {
+    fn context(&self) -> (usize, usize) {
+        // Context lines only make sense when whole lines are printed.
+        match self.app.only_matching {
+            true => (0, 0),
+            false => (self.app.before_context, self.app.after_context),
+        }
+    }
+
+    fn passthru(&self) -> bool {
+        self.app.passthru && !self.app.only_matching
+    }
+
+    fn on_file_begin(&mut self, label: &str) {
+        self.label = label.to_string();
+        self.last_printed = None;
+    }
+
+    fn on_match(&mut self, line: &SinkLine) -> bool {
+        let (matcher, app) = (self.matcher, self.app);
+        if app.only_matching && app.invert_match {
+            // Inverted lines contain no matches, so there is nothing to print.
+            return false;
+        }
+        self.start_line(line.line_number);
+        // Without a heading, every line carries its path instead.
+        let path = (self.filename && !app.use_heading()).then_some(self.label.as_str());
+        if app.only_matching {
+            for captures in matcher.captures_iter(line.line) {
+                let found = match &app.group {
+                    Some(name) => match captures.named(name) {
+                        Some(found) => found,
+                        None => continue,
+                    },
+                    None => captures.get(0).unwrap(),
+                };
+                let (lines_before, column) = position(line.line, found.start());
+                let record = Record {
+                    path,
+                    line_number: line.line_number + lines_before,
+                    column: app.column.then_some(column + 1),
+                    byte_offset: app.byte_offset.then_some(line.offset + found.start()),
+                    separator: ':',
+                };
+                let text = match app.group {
+                    Some(_) => found.as_bytes().to_vec(),
+                    None => replaced(&captures, app.replacement.as_deref()),
+                };
+                let text = String::from_utf8_lossy(&text);
+                let pattern = matcher.pattern_index(&captures.get(0).unwrap());
+                let text = app
+                    .theme
+                    .paint(Element::Pattern(pattern), &text)
+                    .to_string();
+                let text = match app.pattern_labels {
+                    true => format!("{}{}", labels(app, [pattern]), text),
+                    false => text,
+                };
+                write_record(self.writer, app, &record, &text);
+            }
+        } else {
+            // Inverted lines have no match, so they report the first column.
+            let first_column = app.column.then(|| {
+                matcher
+                    .find_iter(line.line)
+                    .next()
+                    .map_or(1, |found| found.start() + 1)
+            });
+            let replacement = app.replacement.as_deref();
+            // Only in multiline mode can one selection hold several lines; a record of
+            // `--null-data` may hold `\n`s too, but is printed whole.
+            let texts = match app.multiline {
+                true => highlight_lines(matcher, &app.theme, line.line, replacement),
+                false => vec![highlight(matcher, &app.theme, line.line, replacement)],
+            };
+            // The lines of a multiline selection are each printed on their own.
+            let mut sources = line.line.split(|&byte| byte == b'\n');
+            let mut start = 0;
+            for (index, text) in texts.into_iter().enumerate() {
+                let source = sources.next();
+                let record = Record {
+                    path,
+                    line_number: line.line_number + index,
+                    column: first_column.map(|column| match index {
+                        0 => column,
+                        _ => 1,
+                    }),
+                    byte_offset: app.byte_offset.then_some(line.offset + start),
+                    separator: ':',
+                };
+                let source = source.unwrap_or_default();
+                let text = truncate(matcher, app, source, replacement, text);
+                // Only the first line of a selection is labelled.
+                let text = match app.pattern_labels && index == 0 && !app.invert_match {
+                    true => {
+                        let patterns = matcher.find_iter(line.line);
+                        let patterns = patterns.map(|found| matcher.pattern_index(&found));
+                        format!("{}{}", labels(app, patterns), text)
+                    }
+                    false => text,
+                };
+                write_record(self.writer, app, &record, &text);
+                start += source.len() + 1;
+            }
+        }
+        self.last_printed = Some(line.end_line_number());
+        self.stream();
+        true
+    }
+
+    fn on_context(&mut self, line: &SinkLine) {
+        let app = self.app;
+        self.start_line(line.line_number);
+        // Without a heading, every line carries its path instead.
+        let path = (self.filename && !app.use_heading()).then_some(self.label.as_str());
+        let record = Record {
+            path,
+            line_number: line.line_number,
+            column: None,
+            byte_offset: app.byte_offset.then_some(line.offset),
+            separator: '-',
+        };
+        let text = highlight(self.matcher, &app.theme, line.line, None);
+        let text = truncate(self.matcher, app, line.line, None, text);
+        write_record(self.writer, app, &record, &text);
+        self.stream();
+    }
+}
+
+/// `[PATTERN] `, naming the patterns of a line's matches for `--pattern-labels`: each
+/// one once, in the order they're first found, in its matches' color.
+fn labels(app: &SearchConfig, patterns: impl IntoIterator<Item = usize>) -> String {
+    let mut seen = Vec::new();
+    for pattern in patterns {
+        if !seen.contains(&pattern) {
+            seen.push(pattern);
+        }
+    }
+    let labels = seen
+        .into_iter()
+        .map(|index| {
+            let pattern = app.patterns.get(index).map_or("", String::as_str);
+            app.theme
+                .paint(Element::Pattern(index), pattern)
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    format!("[{}] ", labels.join(","))
+}
+
+/// The fields printed before the text of one output line.
+struct Record<'a> {
+    /// Set when lines are prefixed with their path instead of grouped under a heading.
+    path: Option<&'a str>,
+    line_number: usize,
+    /// 1-based column of the first match, with `--column`.
+    column: Option<usize>,
+    /// 0-based byte offset, with `-b`.
+    byte_offset: Option<usize>,
+    /// `:` for selected lines and `-` for context lines.
+    separator: char,
+}
+
+fn write_record(writer: &mut impl Write, app: &SearchConfig, record: &Record, text: &str) {
+    let separator = record.separator;
+    // Whether anything comes before the text, which is then set off by a space.
+    let mut prefixed = false;
+    if let Some(path) = record.path {
+        let mut buffer = [0; 4];
+        let terminator = app.path_terminator(separator.encode_utf8(&mut buffer));
+        write!(
+            writer,
+            "{}{}",
+            app.theme.paint(Element::Path, path),
+            terminator
+        )
+        .unwrap();
+        prefixed = true;
+    }
+    if app.use_line_number() {
+        let line_number = record.line_number.to_string();
+        let line_number = app.theme.paint(Element::Line, &line_number);
+        write!(writer, "{}{}", line_number, separator).unwrap();
+        prefixed = true;
+    }
+    if let Some(column) = record.column {
+        let column = app.theme.paint(Element::Column, &column.to_string());
+        write!(writer, "{}{}", column, separator).unwrap();
+        prefixed = true;
+    }
+    if let Some(byte_offset) = record.byte_offset {
+        write!(writer, "{}{}", byte_offset, separator).unwrap();
+        prefixed = true;
+    }
+    match prefixed {
+        true => write!(writer, " {}", text).unwrap(),
+        false => write!(writer, "{}", text).unwrap(),
+    }
+    writer.write_all(&[app.record_separator]).unwrap();
+}
+
+/// Returns `line` with every match colored, after substituting `replacement` for it
+/// when one is given. Bytes that aren't valid UTF-8 are shown as U+FFFD.
+pub(crate) fn highlight(
+    matcher: &Matcher,
+    theme: &theme::Theme,
+    line: &[u8],
+    replacement: Option<&str>,
+) -> String {
+    let mut highlighted = String::with_capacity(line.len());
+    let mut last_end = 0;
+    for captures in matcher.captures_iter(line) {
+        let found = captures.get(0).unwrap();
+        highlighted.push_str(&String::from_utf8_lossy(&line[last_end..found.start()]));
+        let text = replaced(&captures, replacement);
+        let text = String::from_utf8_lossy(&text);
+        let element = Element::Pattern(matcher.pattern_index(&found));
+        highlighted.push_str(&theme.paint(element, &text).to_string());
+        last_end = found.end();
+    }
+    highlighted.push_str(&String::from_utf8_lossy(&line[last_end..]));
+    highlighted
+}
+
+/// Like `highlight`, for text that can hold several lines joined by `\n`: returns each
+/// line separately, so no color runs across a line break. A replacement is kept whole,
+/// with the lines it replaced.
+fn highlight_lines(
+    matcher: &Matcher,
+    theme: &theme::Theme,
+    text: &[u8],
+    replacement: Option<&str>,
+) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    // The pattern of the match being pushed, or `None` between matches.
+    let mut push = |part: &[u8], pattern: Option<usize>| {
+        let painted = pattern.is_some();
+        let pieces: Vec<&[u8]> = match painted && replacement.is_some() {
+            true => vec![part],
+            false => part.split(|&byte| byte == b'\n').collect(),
+        };
+        for (index, piece) in pieces.into_iter().enumerate() {
+            if index > 0 {
+                lines.push(String::new());
+            }
+            let piece = String::from_utf8_lossy(piece);
+            let line = lines.last_mut().unwrap();
+            match pattern.filter(|_| !piece.is_empty()) {
+                Some(index) => {
+                    line.push_str(&theme.paint(Element::Pattern(index), &piece).to_string())
+                }
+                None => line.push_str(&piece),
+            }
+        }
+    };
+    let mut last_end = 0;
+    for captures in matcher.captures_iter(text) {
+        let found = captures.get(0).unwrap();
+        push(&text[last_end..found.start()], None);
+        let pattern = matcher.pattern_index(&found);
+        push(&replaced(&captures, replacement), Some(pattern));
+        last_end = found.end();
+    }
+    push(&text[last_end..], None);
+    lines
+}
+
+/// What `--max-columns` leaves of a printed line: `text`, the painted form of `source`
+/// with `replacement` substituted, cut to the columns that fit, with `[... omitted]`
+/// where the rest was. A column is a character; color codes take none and are all kept,
+/// so colors still end where they should.
+fn truncate(
+    matcher: &Matcher,
+    app: &SearchConfig,
+    source: &[u8],
+    replacement: Option<&str>,
+    text: String,
+) -> String {
+    const OMITTED: &str = "[... omitted]";
+    let Some(max) = app.max_columns else {
+        return text;
+    };
+    if visible_columns(&text) <= max {
+        return text;
+    }
+    // A preview starts a little before the first match, when the cut would hide it, and
+    // late enough to show as much of the match as fits. Both are measured as `text`
+    // shows them, so a replacement counts at its own length.
+    let (first, width) = match app.max_columns_preview {
+        true => matcher
+            .captures_iter(source)
+            .next()
+            .map_or((0, 0), |captures| {
+                let found = captures.get(0).unwrap();
+                let before = String::from_utf8_lossy(&source[..found.start()]);
+                let shown = replaced(&captures, replacement);
+                (
+                    before.chars().count(),
+                    String::from_utf8_lossy(&shown).chars().count(),
+                )
+            }),
+        false => (0, 0),
+    };
+    let end = first + width;
+    let start = match first > max * 3 / 4 || end > max {
+        true => first
+            .saturating_sub(max / 4)
+            .max(end.saturating_sub(max))
+            .min(first),
+        false => 0,
+    };
+
+    let mut cut = String::with_capacity(text.len());
+    if start > 0 {
+        cut.push_str(OMITTED);
+        cut.push(' ');
+    }
+    let mut column = 0;
+    let mut chars = text.chars();
+    while let Some(char) = chars.next() {
+        if char == '\x1b' {
+            cut.push(char);
+            for char in chars.by_ref() {
+                cut.push(char);
+                if char.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        if (start..start + max).contains(&column) {
+            cut.push(char);
+        }
+        column += 1;
+    }
+    if column > start + max {
+        cut.push(' ');
+        cut.push_str(OMITTED);
+    }
+    cut
+}
+
+/// How many characters `text` has, taking invalid UTF-8 byte by byte.
+fn columns(text: &[u8]) -> usize {
+    text.iter().filter(|&&byte| byte & 0xC0 != 0x80).count()
+}
+
+/// How many characters of `text` show, leaving out color codes.
+fn visible_columns(text: &str) -> usize {
+    let mut count = 0;
+    let mut escape = false;
+    for char in text.chars() {
+        match escape {
+            true => escape = !char.is_ascii_alphabetic(),
+            false if char == '\x1b' => escape = true,
+            false => count += 1,
+        }
+    }
+    count
+}
+
+/// The line of `text` that byte `at` is on, counted from 0, and its column there.
+fn position(text: &[u8], at: usize) -> (usize, usize) {
+    let before = &text[..at];
+    let lines = before.iter().filter(|&&byte| byte == b'\n').count();
+    let line_start = before
+        .iter()
+        .rposition(|&byte| byte == b'\n')
+        .map_or(0, |newline| newline + 1);
+    (lines, at - line_start)
+}
+
+/// The bytes printed for one match: the match itself, or the expanded replacement.
+pub(crate) fn replaced(captures: &Captures, replacement: Option<&str>) -> Vec<u8> {
+    match replacement {
+        Some(replacement) => {
+            let mut expanded = Vec::new();
+            captures.expand(replacement.as_bytes(), &mut expanded);
+            expanded
+        }
+        None => captures.get(0).unwrap().as_bytes().to_vec(),
+    }
+}
+
+/// Emits `--json` events: `begin`, one `match` per selected line and `end`, skipping
+/// files without matches. Inverted lines carry no submatches.
+struct Json<'a> {
+    matcher: &'a Matcher,
+    app: &'a SearchConfig,
+    writer: &'a mut Vec<u8>,
+    label: String,
+    matched_lines: usize,
+    matches: usize,
+}
+
+impl<'a> Json<'a> {
+    fn new(matcher: &'a Matcher, app: &'a SearchConfig, writer: &'a mut Vec<u8>) -> Json<'a> {
+        Json {
+            matcher,
+            app,
+            writer,
+            label: String::new(),
+            matched_lines: 0,
+            matches: 0,
+        }
+    }
+}
+
+impl Sink for Json<'_> {
+    fn on_file_begin(&mut self, label: &str) {
+        self.label = label.to_string();
+        self.matched_lines = 0;
+        self.matches = 0;
+    }
+
+    fn on_match(&mut self, line: &SinkLine) -> bool {
+        if self.matched_lines == 0 {
+            writeln!(self.writer, "{}", json::begin(&self.label)).unwrap();
+        }
+        let submatches = match self.app.invert_match {
+            true => Vec::new(),
+            false => self
+                .matcher
+                .captures_iter(line.line)
+                .map(|captures| {
+                    let found = captures.get(0).unwrap();
+                    let groups = self
+                        .matcher
+                        .group_names()
+                        .map(|name| json::Group {
+                            name,
+                            found: captures.named(name).map(|group| {
+                                let text = String::from_utf8_lossy(group.as_bytes());
+                                (text, group.start(), group.end())
+                            }),
+                        })
+                        .collect();
+                    json::Submatch {
+                        text: String::from_utf8_lossy(found.as_bytes()),
+                        start: found.start(),
+                        end: found.end(),
+                        groups,
+                    }
+                })
+                .collect(),
+        };
+        self.matched_lines += 1;
+        self.matches += submatches.len();
+        let text = String::from_utf8_lossy(line.line);
+        let end_line_number = self.app.multiline.then(|| line.end_line_number());
+        let event = json::matched(
+            &self.label,
+            line.line_number,
+            end_line_number,
+            line.offset,
+            &text,
+            &submatches,
+        );
+        writeln!(self.writer, "{}", event).unwrap();
+        true
+    }
+
+    fn on_file_end(&mut self, label: &str, _selected: usize) {
+        let (matched_lines, matches) = (self.matched_lines, self.matches);
+        if matched_lines > 0 {
+            writeln!(self.writer, "{}", json::end(label, matched_lines, matches)).unwrap();
+        }
+    }
+}
+
+/// Prints `label:line:column:text` once per match, the format vim's `:grep` parses.
+/// Inverted lines have no match and are printed once at column 1.
+struct Vimgrep<'a> {
+    matcher: &'a Matcher,
+    app: &'a SearchConfig,
+    writer: &'a mut Vec<u8>,
+    label: String,
+}
+
+impl Sink for Vimgrep<'_> {
+    fn on_file_begin(&mut self, label: &str) {
+        self.label = label.to_string();
+    }
+
+    fn on_match(&mut self, line: &SinkLine) -> bool {
+        // A match is reported on the line it starts on, which in multiline mode needn't
+        // be the first.
+        let positions = match self.app.invert_match {
+            true => vec![(0, 0)],
+            false => self
+                .matcher
+                .find_iter(line.line)
+                .map(|found| position(line.line, found.start()))
+                .collect(),
+        };
+        for (lines_before, column) in positions {
+            let text = line.line.split(|&byte| byte == b'\n').nth(lines_before);
+            let terminator = self.app.path_terminator(":");
+            writeln!(
+                self.writer,
+                "{}{}{}:{}:{}",
+                self.label,
+                terminator,
+                line.line_number + lines_before,
+                column + 1,
+                String::from_utf8_lossy(text.unwrap_or_default())
+            )
+            .unwrap();
+        }
+        true
+    }
+}
+
+/// Prints a record per match in a structured format (`--format`), one to a line; for
+/// SARIF, `SearchConfig::emit` joins them into the document. Inverted lines have no
+/// match and are reported whole.
+struct Structured<'a> {
+    matcher: &'a Matcher,
+    app: &'a SearchConfig,
+    writer: &'a mut Vec<u8>,
+    format: FormatChoice,
+    label: String,
+    /// The label relative to the working directory when it's under it, for code
+    /// scanning and annotations, which expect paths relative to the checkout.
+    relative: String,
+    /// The file's metadata, when a template needs it.
+    metadata: Option<std::fs::Metadata>,
+}
+
+impl Sink for Structured<'_> {
+    fn on_file_begin(&mut self, label: &str) {
+        self.label = label.to_string();
+        self.relative = std::env::current_dir()
+            .ok()
+            .and_then(|directory| glob::relative(&directory, Path::new(label)))
+            .unwrap_or_else(|| label.to_string());
+        let template = self.app.template.as_ref();
+        self.metadata = template
+            .filter(|template| template.uses_metadata())
+            .and_then(|_| std::fs::metadata(label).ok());
+    }
+
+    fn on_match(&mut self, line: &SinkLine) -> bool {
+        let spans = match self.app.invert_match {
+            true => vec![(0, 0..line.line.len(), None)],
+            false => self
+                .matcher
+                .captures_iter(line.line)
+                .map(|captures| {
+                    let found = captures.get(0).unwrap();
+                    (
+                        self.matcher.pattern_index(&found),
+                        found.range(),
+                        Some(captures),
+                    )
+                })
+                .collect(),
+        };
+        let snippet = String::from_utf8_lossy(line.line);
+        for (rule_index, range, captures) in spans {
+            // Columns count characters from the start of the line a match starts or ends on.
+            let point = |at: usize| {
+                let (lines_before, column) = position(line.line, at);
+                let line_start = at - column;
+                let column = columns(&line.line[line_start..at]);
+                (line.line_number + lines_before, column + 1)
+            };
+            let (start_line, start_column) = point(range.start);
+            let (end_line, end_column) = point(range.end);
+            let matched = String::from_utf8_lossy(&line.line[range]);
+            let record = match self.format {
+                FormatChoice::Sarif => {
+                    let region = sarif::Region {
+                        start_line,
+                        start_column,
+                        end_line,
+                        end_column,
+                    };
+                    sarif::result(
+                        &self.app.patterns,
+                        rule_index,
+                        &self.relative,
+                        &region,
+                        &matched,
+                        &snippet,
+                    )
+                }
+                FormatChoice::Csv | FormatChoice::Tsv => {
+                    let (start_line, start_column) =
+                        (start_line.to_string(), start_column.to_string());
+                    // A group that took no part in the match, or an inverted line's
+                    // groups, are left empty.
+                    let groups = self
+                        .matcher
+                        .group_names()
+                        .map(|name| {
+                            let found = captures.as_ref().and_then(|captures| captures.named(name));
+                            found.map_or(String::new(), |found| {
+                                String::from_utf8_lossy(found.as_bytes()).into_owned()
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    let mut fields = vec![
+                        self.label.as_str(),
+                        &start_line,
+                        &start_column,
+                        &matched,
+                        &snippet,
+                    ];
+                    fields.extend(groups.iter().map(String::as_str));
+                    csv::record(&fields, self.format == FormatChoice::Tsv)
+                }
+                FormatChoice::Github => {
+                    let pattern = self.app.patterns.get(rule_index).map_or("", String::as_str);
+                    // An annotation only spans columns within one line.
+                    let end_column = (end_line == start_line).then_some(end_column);
+                    let path = &self.relative;
+                    github::annotation(
+                        path,
+                        start_line,
+                        start_column,
+                        end_column,
+                        pattern,
+                        &matched,
+                    )
+                }
+                FormatChoice::Patch => unreachable!("patches are written by diff_contents"),
+                FormatChoice::Template => {
+                    let fields = template::Fields {
+                        path: &self.label,
+                        line: start_line,
+                        column: start_column,
+                        matched: &matched,
+                        text: &snippet,
+                        captures: captures.as_ref(),
+                        metadata: self.metadata.as_ref(),
+                    };
+                    self.app.template.as_ref().unwrap().render(&fields)
+                }
+            };
+            writeln!(self.writer, "{}", record).unwrap();
+        }
+        true
+    }
+}
+
+/// Prints the label alone if the contents have a selected line (`-l`) or have none (`-L`),
+/// stopping at the first selected line.
+struct List<'a> {
+    app: &'a SearchConfig,
+    writer: &'a mut Vec<u8>,
+}
+
+impl Sink for List<'_> {
+    fn on_match(&mut self, _line: &SinkLine) -> bool {
+        false
+    }
+
+    fn on_file_end(&mut self, label: &str, selected: usize) {
+        if self.matched(selected) {
+            write!(
+                self.writer,
+                "{}{}",
+                self.app.theme.paint(Element::Path, label),
+                self.app.path_terminator("\n")
+            )
+            .unwrap();
+        }
+    }
+
+    fn matched(&self, selected: usize) -> bool {
+        (selected > 0) == self.app.files_with_matches
+    }
+}
+
+/// Prints `label: N` where N is the number of selected lines, or the total number
+/// of regex matches with `--count-matches` (inverted lines are counted once each).
+struct Count<'a> {
+    matcher: &'a Matcher,
+    app: &'a SearchConfig,
+    writer: &'a mut Vec<u8>,
+    filename: bool,
+    count: usize,
+}
+
+impl<'a> Count<'a> {
+    fn new(matcher: &'a Matcher, app: &'a SearchConfig, writer: &'a mut Vec<u8>) -> Count<'a> {
+        Count {
+            matcher,
+            app,
+            writer,
+            filename: app.use_filename(),
+            count: 0,
+        }
+    }
+}
+
+impl Sink for Count<'_> {
+    fn on_file_begin(&mut self, _label: &str) {
+        self.count = 0;
+    }
+
+    fn on_match(&mut self, line: &SinkLine) -> bool {
+        self.count += match self.app.count_matches && !self.app.invert_match {
+            true => self.matcher.find_iter(line.line).count(),
+            // A selection spanning lines counts each of them.
+            false => line.end_line_number() - line.line_number + 1,
+        };
+        true
+    }
+
+    fn on_file_end(&mut self, label: &str, _selected: usize) {
+        if self.count > 0 && !self.filename {
+            writeln!(self.writer, "{}", self.count).unwrap();
+        } else if self.count > 0 {
+            let terminator = self.app.path_terminator(": ");
+            writeln!(
+                self.writer,
+                "{}{}{}",
+                self.app.theme.paint(Element::Path, label),
+                terminator,
+                self.count
+            )
+            .unwrap();
+        }
+    }
+
+    fn matched(&self, _selected: usize) -> bool {
+        self.count > 0
+    }
+}
+
+/// `--summary`: counts the matches, or the selected lines when inverted, for the report
+/// printed once the search is over.
+struct SummarySink<'a> {
+    matcher: &'a Matcher,
+    app: &'a SearchConfig,
+    count: usize,
+}
+
+impl Sink for SummarySink<'_> {
+    fn on_file_begin(&mut self, _label: &str) {
+        self.count = 0;
+    }
+
+    fn on_match(&mut self, line: &SinkLine) -> bool {
+        self.count += match self.app.invert_match {
+            false => self.matcher.find_iter(line.line).count(),
+            true => line.end_line_number() - line.line_number + 1,
+        };
+        true
+    }
+
+    fn on_file_end(&mut self, label: &str, _selected: usize) {
+        self.app.summaries.lock().unwrap().add(label, self.count);
+    }
+}
+
+/// `--tally`: counts each distinct match, group or inverted line, for the report printed
+/// once the search is over.
+struct TallySink<'a> {
+    matcher: &'a Matcher,
+    app: &'a SearchConfig,
+    found: HashMap<Vec<u8>, usize>,
+}
+
+impl Sink for TallySink<'_> {
+    fn on_match(&mut self, line: &SinkLine) -> bool {
+        if self.app.invert_match {
+            *self.found.entry(line.line.to_vec()).or_default() += 1;
+            return true;
+        }
+        for captures in self.matcher.captures_iter(line.line) {
+            let found = match &self.app.group {
+                Some(name) => captures.named(name),
+                None => captures.get(0),
+            };
+            if let Some(found) = found {
+                *self.found.entry(found.as_bytes().to_vec()).or_default() += 1;
+            }
+        }
+        true
+    }
+
+    fn on_file_end(&mut self, _label: &str, _selected: usize) {
+        let found = std::mem::take(&mut self.found);
+        self.app.tallies.lock().unwrap().add(found);
+    }
+}
+
+/// `-q`: prints nothing and stops at the first selected line.
+struct Quiet;
+
+impl Sink for Quiet {
+    fn on_match(&mut self, _line: &SinkLine) -> bool {
+        false
+    }
+}
+
+/// Prints `Binary file X matches` at the first selected line.
+struct Binary<'a> {
+    writer: &'a mut Vec<u8>,
+}
+
+impl Sink for Binary<'_> {
+    fn on_match(&mut self, _line: &SinkLine) -> bool {
+        false
+    }
+
+    fn on_file_end(&mut self, label: &str, selected: usize) {
+        if selected > 0 {
+            writeln!(self.writer, "Binary file {} matches", label).unwrap();
+        }
+    }
+}
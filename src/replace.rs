@@ -0,0 +1,172 @@
+//! Applying `--replace` to files: rewriting them with `--write`, optionally asking
+//! first with `--interactive`, or previewing the change with `--diff` or as a patch for
+//! `git apply` with `--format patch`.
+
+use std::io::Write;
+use std::path::Path;
+
+use crate::config::{FormatChoice, SearchConfig};
+use crate::matcher::{Found, Matcher};
+use crate::theme::Element;
+use crate::{diff, glob, printer, terminal};
+
+/// Applies `--replace` to the file itself for `--write`, returning whether it changed.
+/// The new contents go to a temporary file in the same directory that is then renamed
+/// over the original, so readers never observe a partially written file.
+pub(crate) fn write_file(
+    matcher: &Matcher,
+    path: &std::path::Path,
+    contents: &[u8],
+    app: &SearchConfig,
+) -> bool {
+    let label = path.display().to_string();
+    let mut prompter = app.prompter.lock().unwrap();
+    let lines = replace_lines(
+        matcher,
+        contents,
+        app,
+        |line_number, line, found, expanded| {
+            let Some(prompter) = prompter.as_mut() else {
+                return true;
+            };
+            let before = String::from_utf8_lossy(&line[..found.start()]);
+            let after = String::from_utf8_lossy(&line[found.end()..]);
+            let path = app.theme.paint(Element::Path, &label);
+            let old = String::from_utf8_lossy(found.as_bytes());
+            let old = app.theme.paint(Element::Match, &old);
+            let new = String::from_utf8_lossy(expanded);
+            let new = app.theme.paint(Element::Match, &new);
+            eprintln!("{}:{}", path, line_number);
+            eprintln!("-{}{}{}", before, old, after);
+            eprintln!("+{}{}{}", before, new, after);
+            matches!(
+                prompter.ask("Apply this change"),
+                terminal::Answer::Yes | terminal::Answer::All
+            )
+        },
+    );
+    if lines.iter().all(|(old, new)| *old == new.as_ref()) {
+        return false;
+    }
+
+    let replaced = lines
+        .into_iter()
+        .map(|(_, new)| new)
+        .collect::<Vec<_>>()
+        .concat();
+    match replace_file(path, &replaced, app.backup.as_deref()) {
+        Ok(()) => true,
+        Err(err) => {
+            app.error(&format!("Failed to write {}: {}", path.display(), err));
+            false
+        }
+    }
+}
+
+/// Prints the unified diff `--write` would apply to the contents (`--diff`).
+pub(crate) fn diff_contents(
+    matcher: &Matcher,
+    label: &str,
+    contents: &[u8],
+    app: &SearchConfig,
+    writer: &mut Vec<u8>,
+) -> bool {
+    let lines = replace_lines(matcher, contents, app, |_, _, _, _| true);
+    let diff = match app.format {
+        Some(FormatChoice::Patch) => {
+            // `git apply` expects `/`-separated paths under the repository, relative to
+            // where it runs.
+            let path = std::env::current_dir()
+                .ok()
+                .and_then(|directory| glob::relative(&directory, Path::new(label)))
+                .unwrap_or_else(|| label.to_string());
+            let (old, new) = (format!("a/{}", path), format!("b/{}", path));
+            match diff::unified(&old, &new, &lines) {
+                diff if diff.is_empty() => diff,
+                diff => format!("diff --git {} {}\n{}", old, new, diff),
+            }
+        }
+        _ => diff::unified(label, label, &lines),
+    };
+    if diff.is_empty() {
+        return false;
+    }
+
+    write!(writer, "{}", diff).unwrap();
+    true
+}
+
+/// Pairs every line of the contents, terminator included, with its `--replace`d form.
+/// `accept` sees each match with its 1-based line number, the line and the expanded
+/// replacement, and can leave that match unchanged by returning false.
+fn replace_lines<'a>(
+    matcher: &Matcher,
+    contents: &'a [u8],
+    app: &SearchConfig,
+    mut accept: impl FnMut(usize, &[u8], Found, &[u8]) -> bool,
+) -> Vec<(&'a [u8], std::borrow::Cow<'a, [u8]>)> {
+    let replacement = app.replacement.as_deref();
+    let separator = app.record_separator;
+    let mut selected = 0;
+    contents
+        .split_inclusive(|&byte| byte == separator)
+        .enumerate()
+        .map(|(index, line)| {
+            let end = line
+                .iter()
+                .rposition(|&byte| {
+                    byte != separator && !(app.crlf && separator == b'\n' && byte == b'\r')
+                })
+                .map_or(0, |last| last + 1);
+            let body = &line[..end];
+            if app.max_count.is_some_and(|max| selected >= max) || !matcher.is_match(body) {
+                return (line, std::borrow::Cow::Borrowed(line));
+            }
+            selected += 1;
+
+            let mut new_line = Vec::with_capacity(line.len());
+            let mut last_end = 0;
+            for captures in matcher.captures_iter(body) {
+                let found = captures.get(0).unwrap();
+                let expanded = printer::replaced(&captures, replacement);
+                new_line.extend_from_slice(&body[last_end..found.start()]);
+                match accept(index + 1, body, found, &expanded) {
+                    true => new_line.extend_from_slice(&expanded),
+                    false => new_line.extend_from_slice(found.as_bytes()),
+                }
+                last_end = found.end();
+            }
+            new_line.extend_from_slice(&line[last_end..]);
+            (line, std::borrow::Cow::Owned(new_line))
+        })
+        .collect()
+}
+
+pub(crate) fn replace_file(
+    path: &std::path::Path,
+    contents: &[u8],
+    backup: Option<&str>,
+) -> std::io::Result<()> {
+    // Built from the name as it is, which needn't be UTF-8.
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(format!(".yagrep-{}", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+    let permissions = std::fs::metadata(path)?.permissions();
+
+    let result = std::fs::write(&temp_path, contents)
+        .and_then(|()| std::fs::set_permissions(&temp_path, permissions))
+        .and_then(|()| match backup {
+            Some(suffix) => {
+                let mut backup_path = path.as_os_str().to_os_string();
+                backup_path.push(suffix);
+                std::fs::copy(path, backup_path).map(|_| ())
+            }
+            None => Ok(()),
+        })
+        .and_then(|()| std::fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
@@ -0,0 +1,81 @@
+//! SARIF 2.1.0 output for `--format sarif`, which code scanning services such as GitHub's
+//! accept: one document whose single run lists every pattern as a rule and every match
+//! as a result. The document is written in three parts, [`begin`], the results and
+//! [`end`], so results can be printed as files are searched.
+
+use crate::json::string;
+
+/// Everything before the first result: the tool, with one rule per pattern. Columns are
+/// counted in characters rather than SARIF's default UTF-16 units.
+pub fn begin(patterns: &[String]) -> String {
+    let rules = patterns
+        .iter()
+        .map(|pattern| {
+            format!(
+                r#"{{"id":{},"shortDescription":{{"text":{}}}}}"#,
+                string(pattern),
+                string(&format!("Matches of {}", pattern))
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(
+        r#"{{"version":"2.1.0","$schema":"https://json.schemastore.org/sarif-2.1.0.json","runs":[{{"tool":{{"driver":{{"name":"yagrep","version":"{}","rules":[{}]}}}},"columnKind":"unicodeCodePoints","results":["#,
+        env!("CARGO_PKG_VERSION"),
+        rules
+    )
+}
+
+/// Where a match is: 1-based lines and columns, the end column exclusive.
+pub struct Region {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+/// One result: a match of pattern `rule_index` in `path`, with the lines it's on as the
+/// snippet. Code scanning expects `path` relative to the checkout.
+pub fn result(
+    patterns: &[String],
+    rule_index: usize,
+    path: &str,
+    region: &Region,
+    matched: &str,
+    snippet: &str,
+) -> String {
+    let rule = patterns.get(rule_index).map_or("", String::as_str);
+    format!(
+        r#"{{"ruleId":{},"ruleIndex":{},"level":"warning","message":{{"text":{}}},"locations":[{{"physicalLocation":{{"artifactLocation":{{"uri":{}}},"region":{{"startLine":{},"startColumn":{},"endLine":{},"endColumn":{},"snippet":{{"text":{}}}}}}}}}]}}"#,
+        string(rule),
+        rule_index,
+        string(&format!("Found {}", matched)),
+        string(&uri(path)),
+        region.start_line,
+        region.start_column,
+        region.end_line,
+        region.end_column,
+        string(snippet)
+    )
+}
+
+/// Closes the results, the run and the document.
+pub fn end() -> &'static str {
+    "\n]}]}\n"
+}
+
+/// `path` as a URI reference, with `/` separators and anything outside the unreserved
+/// characters percent-encoded.
+fn uri(path: &str) -> String {
+    let mut uri = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            b'\\' if std::path::MAIN_SEPARATOR == '\\' => uri.push('/'),
+            byte => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
@@ -0,0 +1,446 @@
+//! Searching one input — standard input, a file, or a directory — with every way a
+//! file's bytes can reach the matcher: preprocessed, decompressed, from an archive,
+//! memory-mapped, or transcoded.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::{FormatChoice, SearchConfig};
+use crate::matcher::{Matcher, PatternError};
+use crate::matches::Matches;
+use crate::stats::{self, Skip};
+use crate::vfs::MemoryFs;
+use crate::{
+    archive, csv, encoding, git, history, index, input, json, lines, mmap, printer, replace, sarif,
+    summary, tally, walker,
+};
+
+pub(crate) const STDIN_LABEL: &str = "(standard input)";
+
+/// A compiled search: the options it runs with and the patterns it looks for.
+pub struct Searcher {
+    config: SearchConfig,
+    matcher: Matcher,
+}
+
+impl Searcher {
+    /// Compiles the patterns of `config`.
+    pub fn new(config: SearchConfig) -> Result<Searcher, PatternError> {
+        let matcher = Matcher::new(&config)?;
+        Ok(Searcher { config, matcher })
+    }
+
+    /// Sends the output to `writer` instead of stdout.
+    pub fn output(mut self, writer: impl Write + Send + 'static) -> Searcher {
+        self.config.writer = std::sync::Mutex::new(Box::new(writer));
+        self
+    }
+
+    /// Every line the search selects, found lazily as the iterator is advanced. Output
+    /// options don't apply, and neither do `--archive`, replacement or `multiline`.
+    pub fn search(&self) -> Matches<'_> {
+        Matches::new(&self.matcher, &self.config)
+    }
+
+    /// Like [`Searcher::search`], over the files of `fs` instead of the disk: the
+    /// configured path, when set, names a file or directory in it.
+    pub fn search_in<'a>(&'a self, fs: &'a MemoryFs) -> Matches<'a> {
+        Matches::in_memory(&self.matcher, &self.config, fs)
+    }
+
+    /// Runs one search over the configured input and prints its results, returning
+    /// whether anything matched. Files and directories that can't be read are reported
+    /// on stderr and skipped (see [`Searcher::had_errors`]); only a missing input is an
+    /// error.
+    pub fn run(&self) -> Result<bool, Box<dyn std::error::Error>> {
+        let (matcher, app) = (&self.matcher, &self.config);
+        let start = std::time::Instant::now();
+        *app.totals.lock().unwrap() = stats::Stats::default();
+        *app.summaries.lock().unwrap() = summary::Summary::default();
+        *app.tallies.lock().unwrap() = tally::Tally::default();
+        app.errors.store(0, std::sync::atomic::Ordering::SeqCst);
+        app.closed.store(false, std::sync::atomic::Ordering::SeqCst);
+        app.results_emitted
+            .store(false, std::sync::atomic::Ordering::SeqCst);
+        // Structured formats start with a header; a SARIF document also wraps the
+        // results, which `emit` separates.
+        // `--summary` and `--tally` print a report in their place.
+        let report = app.summary || app.tally;
+        let sarif = app.format == Some(FormatChoice::Sarif) && !app.quiet && !report;
+        let header = match app.format {
+            _ if app.quiet || report => None,
+            Some(FormatChoice::Sarif) => Some(sarif::begin(&app.patterns)),
+            Some(FormatChoice::Csv) => Some(csv::header(false, matcher.group_names())),
+            Some(FormatChoice::Tsv) => Some(csv::header(true, matcher.group_names())),
+            Some(FormatChoice::Github | FormatChoice::Template | FormatChoice::Patch) | None => {
+                None
+            }
+        };
+        if let Some(header) = header {
+            app.writer.lock().unwrap().write_all(header.as_bytes())?;
+        }
+
+        let mut output = Vec::new();
+        let matched = match (&app.path, &app.revision) {
+            (path, _) if app.git_log => {
+                let path = path.clone().unwrap_or_else(|| PathBuf::from("."));
+                history::match_history(matcher, &path, app, &mut output)
+            }
+            (path, Some(revision)) => {
+                let path = path.clone().unwrap_or_else(|| PathBuf::from("."));
+                match_revision(matcher, &path, revision, app, &mut output)
+            }
+            (None, None) => match_stdin(matcher, app, &mut output),
+            (Some(path), None) => match (path.is_file(), path.is_dir()) {
+                (true, false) => match_file(matcher, path, None, app, &mut output),
+                (false, true) => walker::match_directory(matcher, path, app),
+                (false, false) if !path.exists() => return Err("File not found".into()),
+                (false, false) if app.read_devices => {
+                    match_file(matcher, path, None, app, &mut output)
+                }
+                (false, false) => {
+                    return Err(format!(
+                        "{} is a special file; --devices read searches it",
+                        path.display()
+                    )
+                    .into())
+                }
+                _ => false,
+            },
+        };
+        app.emit(&output);
+        // With no one to read them, the report and totals aren't written either.
+        if app.is_closed() {
+            return Ok(matched);
+        }
+
+        if report && !app.quiet {
+            let report = match app.summary {
+                true => app.summaries.lock().unwrap().report(app.summary_chart),
+                false => app.tallies.lock().unwrap().report(),
+            };
+            let mut writer = app.writer.lock().unwrap();
+            writer.write_all(report.as_bytes())?;
+            writer.flush()?;
+        }
+        let totals = app.totals.lock().unwrap();
+        if app.json && !report {
+            let summary = json::summary(&totals, start.elapsed());
+            app.emit(format!("{}\n", summary).as_bytes());
+        } else if app.stats && app.format.is_none() {
+            app.emit(format!("\n{}", totals.report(start.elapsed())).as_bytes());
+        }
+        if sarif {
+            let mut writer = app.writer.lock().unwrap();
+            writer.write_all(sarif::end().as_bytes())?;
+            writer.flush()?;
+        }
+        Ok(matched)
+    }
+
+    /// Whether the last [`Searcher::run`] skipped a file or directory it couldn't read,
+    /// which the command line reflects in its exit status.
+    pub fn had_errors(&self) -> bool {
+        self.config.errors.load(std::sync::atomic::Ordering::SeqCst) > 0
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn config(&self) -> &SearchConfig {
+        &self.config
+    }
+}
+
+/// Returns whether the file produced a match (see `match_contents`). A file `index`
+/// rules out is searched as if empty.
+pub(crate) fn match_file(
+    matcher: &Matcher,
+    path: &std::path::Path,
+    index: Option<&index::Index>,
+    app: &SearchConfig,
+    writer: &mut Vec<u8>,
+) -> bool {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            app.error(&format!("Failed to open {}: {}", path.display(), err));
+            app.totals.lock().unwrap().skipped(Skip::Unreadable);
+            return false;
+        }
+    };
+
+    // Preprocessed files, archives and compressed files are searched but never rewritten.
+    if let Some(program) = app.preprocessor_for(path) {
+        let label = path.display().to_string();
+        return match input::Piped::preprocess(program, path) {
+            Ok(output) => match_reader(matcher, &label, BufReader::new(output), app, writer),
+            Err(err) => {
+                app.error(&format!("Failed to preprocess {}: {}", label, err));
+                false
+            }
+        };
+    }
+    let archive = archive::kind(path).filter(|_| app.archive);
+    if let Some(kind) = archive {
+        return match_archive(matcher, path, kind, app, writer);
+    }
+    let decompressor = input::decompressor(path).filter(|_| app.search_zip);
+    if let Some(command) = decompressor {
+        let label = path.display().to_string();
+        return match input::Piped::decompress(command, path) {
+            Ok(decompressed) => {
+                match_reader(matcher, &label, BufReader::new(decompressed), app, writer)
+            }
+            Err(err) => {
+                app.error(&format!("Failed to decompress {}: {}", label, err));
+                false
+            }
+        };
+    }
+
+    // Previewing a change takes precedence over making it.
+    if app.write && !app.previews_diff() {
+        let mut contents = Vec::new();
+        if let Err(err) = BufReader::new(file).read_to_end(&mut contents) {
+            app.error(&format!("Failed to read {}: {}", path.display(), err));
+            return false;
+        }
+        // Rewriting lines of a binary file would corrupt it.
+        if lines::is_binary(&contents, app.record_separator) && !app.text {
+            app.totals.lock().unwrap().skipped(Skip::Binary);
+            return false;
+        }
+        if !matcher.admits(&contents) {
+            return false;
+        }
+        return replace::write_file(matcher, path, &contents, app);
+    }
+    let label = path.display().to_string();
+    let metadata = file.metadata().ok();
+    let excluded = index
+        .zip(metadata.as_ref())
+        .is_some_and(|(index, metadata)| index.excludes(path, metadata));
+    if excluded {
+        return match_reader(matcher, &label, &[][..], app, writer);
+    }
+    let len = metadata.map_or(0, |metadata| metadata.len());
+    if app.use_mmap(len) {
+        if let Some(map) = mmap::Mmap::map(&file) {
+            // A mapped file is searched for the prefilter's literal in one pass first; if
+            // it's absent, no line can match and the file is searched as if empty.
+            let plain = encoding::detect(&map, app.encoding).0 == encoding::Encoding::Utf8
+                && !app.binary
+                && !app.invert_match
+                && !app.passthru;
+            let contents = match plain && matcher.excludes(&map) {
+                true => &[][..],
+                false => &map[..],
+            };
+            return match_reader(matcher, &label, contents, app, writer);
+        }
+    }
+    match_reader(matcher, &label, BufReader::new(file), app, writer)
+}
+
+/// Searches every member of an archive (`--archive`), each labelled
+/// `archive!member/path`.
+fn match_archive(
+    matcher: &Matcher,
+    path: &std::path::Path,
+    kind: archive::Kind,
+    app: &SearchConfig,
+    writer: &mut Vec<u8>,
+) -> bool {
+    let label = path.display().to_string();
+    let mut matched = false;
+    let result = archive::members(path, kind, app.max_filesize, |name, contents| {
+        let member = format!("{}!{}", label, name);
+        match contents {
+            Some(contents) => matched |= match_reader(matcher, &member, contents, app, writer),
+            None => app.totals.lock().unwrap().skipped(Skip::TooLarge),
+        }
+    });
+    if let Err(err) = result {
+        app.error(&format!("Failed to read archive {}: {}", label, err));
+    }
+    matched
+}
+
+/// Searches the files of `revision` at `path`, a directory or a file of the revision,
+/// each labelled `revision:path` like `git grep`, with the path relative to the top of
+/// the repository.
+fn match_revision(
+    matcher: &Matcher,
+    path: &Path,
+    revision: &str,
+    app: &SearchConfig,
+    writer: &mut Vec<u8>,
+) -> bool {
+    // A file needn't exist in the working tree, but its directory must.
+    let (directory, file) = match path.is_dir() {
+        true => (path, None),
+        false => (path.parent().unwrap_or(path), path.file_name()),
+    };
+    let blobs = match git::tree(directory, revision, file) {
+        Ok(blobs) => blobs,
+        Err(err) => {
+            app.error(&format!("Failed to read revision {}: {}", revision, err));
+            return false;
+        }
+    };
+    let prefix = match git::prefix(directory) {
+        Ok(prefix) => prefix,
+        Err(err) => {
+            app.error(&format!("Failed to read revision {}: {}", revision, err));
+            return false;
+        }
+    };
+    let mut contents = match git::Contents::open(directory) {
+        Ok(contents) => contents,
+        Err(err) => {
+            app.error(&format!("Failed to run git: {}", err));
+            return false;
+        }
+    };
+
+    let mut matched = false;
+    for blob in blobs {
+        if let Some(reason) = committed_skip(directory, &blob.path, app) {
+            app.totals.lock().unwrap().skipped(reason);
+            continue;
+        }
+        let label = format!("{}:{}", revision, prefix.join(&blob.path).display());
+        match contents.read(&blob) {
+            Ok(data) if app.max_filesize.is_some_and(|max| data.len() as u64 > max) => {
+                app.totals.lock().unwrap().skipped(Skip::TooLarge);
+            }
+            Ok(data) => matched |= match_reader(matcher, &label, &data[..], app, writer),
+            Err(err) => {
+                app.error(&format!("Failed to read {}: {}", label, err));
+                app.totals.lock().unwrap().skipped(Skip::Unreadable);
+            }
+        }
+        if matched && app.quiet {
+            break;
+        }
+    }
+    matched
+}
+
+/// Why a committed file at `path`, relative to `directory`, isn't searched, if it
+/// isn't: the hidden rule, globs and file types apply as in a walk, but ignore files
+/// don't.
+pub(crate) fn committed_skip(directory: &Path, path: &Path, app: &SearchConfig) -> Option<Skip> {
+    let hidden = path
+        .components()
+        .any(|component| component.as_os_str().to_string_lossy().starts_with('.'));
+    if hidden && !app.hidden {
+        return Some(Skip::Hidden);
+    }
+    let path = directory.join(path);
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let included = match app.globs.matched(directory, &path, false) {
+        Some(included) => included,
+        None => app.types.allows(&file_name),
+    };
+    (!included).then_some(Skip::Filtered)
+}
+
+fn match_stdin(matcher: &Matcher, app: &SearchConfig, writer: &mut Vec<u8>) -> bool {
+    match_reader(matcher, STDIN_LABEL, std::io::stdin().lock(), app, writer)
+}
+
+/// Searches everything `reader` produces a line at a time (see `match_contents`),
+/// transcoding it to UTF-8 first when it isn't already (see `encoding`).
+fn match_reader(
+    matcher: &Matcher,
+    label: &str,
+    mut reader: impl BufRead,
+    app: &SearchConfig,
+    writer: &mut Vec<u8>,
+) -> bool {
+    let start = reader.fill_buf().unwrap_or_default();
+    let (encoding, bom) = encoding::detect(start, app.encoding);
+    reader.consume(bom);
+    match encoding {
+        encoding::Encoding::Utf8 => match_admitted(matcher, label, reader, app, writer),
+        encoding => {
+            let reader = BufReader::new(encoding::Decoder::new(reader, encoding));
+            match_admitted(matcher, label, reader, app, writer)
+        }
+    }
+}
+
+/// Searches UTF-8 input from `reader` unless `--files-matching` rules it out, which
+/// takes reading all of it first.
+fn match_admitted(
+    matcher: &Matcher,
+    label: &str,
+    mut reader: impl BufRead,
+    app: &SearchConfig,
+    writer: &mut Vec<u8>,
+) -> bool {
+    if !matcher.filters_files() {
+        return match_decoded(matcher, label, reader, app, writer);
+    }
+    let mut contents = Vec::new();
+    if let Err(err) = reader.read_to_end(&mut contents) {
+        app.error(&format!("Failed to read {}: {}", label, err));
+        return false;
+    }
+    matcher.admits(&contents) && match_decoded(matcher, label, &contents[..], app, writer)
+}
+
+/// Searches UTF-8 input from `reader`. Only `--diff` needs the whole input up front, to
+/// render hunks.
+///
+/// Input with a NUL byte near the start is binary. Unless `-a` or `--binary` is given,
+/// its lines are never printed: modes that would print them report
+/// `Binary file X matches` instead, and `--diff` skips it.
+fn match_decoded(
+    matcher: &Matcher,
+    label: &str,
+    mut reader: impl BufRead,
+    app: &SearchConfig,
+    writer: &mut Vec<u8>,
+) -> bool {
+    let binary = reader
+        .fill_buf()
+        .is_ok_and(|start| lines::is_binary(start, app.record_separator));
+    let text = app.text;
+    let escaped = binary && !text && app.binary;
+    let suppressed = binary && !text && !escaped;
+
+    if app.previews_diff() && !app.quiet {
+        if suppressed {
+            app.totals.lock().unwrap().skipped(Skip::Binary);
+            return false;
+        }
+        let mut contents = Vec::new();
+        if let Err(err) = reader.read_to_end(&mut contents) {
+            app.error(&format!("Failed to read {}: {}", label, err));
+            return false;
+        }
+        return replace::diff_contents(matcher, label, &contents, app, writer);
+    }
+    let prints_lines = !app.quiet
+        && !app.files_with_matches
+        && !app.files_without_match
+        && !app.count
+        && !app.count_matches
+        && !app.summary
+        && !app.tally;
+    // A structured format has no way to say a binary file matched.
+    if suppressed && app.format.is_some() {
+        app.totals.lock().unwrap().skipped(Skip::Binary);
+        return false;
+    }
+    let lines = match escaped {
+        true => lines::Lines::escaped(reader),
+        false => lines::Lines::new(reader),
+    };
+    let lines = lines.terminated_by(app.record_separator).crlf(app.crlf);
+    match suppressed && prints_lines {
+        true => printer::binary_contents(matcher, label, lines, app, writer),
+        false => printer::match_contents(matcher, label, lines, app, writer),
+    }
+}
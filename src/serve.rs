@@ -0,0 +1,202 @@
+//! `yagrep serve`: answers searches over JSON-RPC 2.0 for editor plugins, one request per
+//! line and one response per line, on standard input and output or on every connection
+//! to a Unix socket. A search is compiled once for each distinct set of arguments and
+//! kept, so repeating a query costs only the walk.
+//!
+//! `search` takes `{"args": [...]}`, the arguments of a command line without the program
+//! name; they must name a path, as standard input carries the requests. It returns
+//! `{"matches": [...]}`, an object per selected line with its `path`, `line_number`,
+//! `text` and `submatches`. `shutdown` returns `null`, then stops the server.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::cli::{self, Command};
+use crate::json::{self, Value};
+use crate::searcher::Searcher;
+
+/// Compiled searches kept at most; beyond it they are all dropped and compiled afresh.
+const CACHE_SIZE: usize = 64;
+
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+
+/// Compiled searches by the arguments that configured them.
+#[derive(Default)]
+struct Server {
+    searches: Mutex<HashMap<Vec<String>, Arc<Searcher>>>,
+}
+
+/// Serves requests on standard input and output, or on connections to a Unix socket
+/// created at `socket`, until a `shutdown` request or the end of the input.
+pub fn serve(socket: Option<&Path>) -> std::io::Result<()> {
+    let server = Server::default();
+    match socket {
+        None => {
+            let stdin = std::io::stdin().lock();
+            server.connection(stdin, std::io::stdout().lock())?;
+            Ok(())
+        }
+        Some(socket) => listen(&server, socket),
+    }
+}
+
+#[cfg(unix)]
+fn listen(server: &Server, socket: &Path) -> std::io::Result<()> {
+    use std::io::BufReader;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let listener = UnixListener::bind(socket)?;
+    let stopped = std::sync::atomic::AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        for stream in listener.incoming() {
+            if stopped.load(std::sync::atomic::Ordering::SeqCst) {
+                break;
+            }
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let stopped = &stopped;
+            scope.spawn(move || {
+                let Ok(reader) = stream.try_clone() else {
+                    return;
+                };
+                if let Ok(true) = server.connection(BufReader::new(reader), stream) {
+                    stopped.store(true, std::sync::atomic::Ordering::SeqCst);
+                    // Wake the loop, blocked waiting for a connection, to see it's over.
+                    let _ = UnixStream::connect(socket);
+                }
+            });
+        }
+    });
+    std::fs::remove_file(socket)
+}
+
+#[cfg(not(unix))]
+fn listen(_server: &Server, _socket: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Unix sockets aren't available on this platform",
+    ))
+}
+
+impl Server {
+    /// Answers the requests read from `reader` on `writer`, returning whether one of
+    /// them was `shutdown`.
+    fn connection(&self, reader: impl BufRead, mut writer: impl Write) -> std::io::Result<bool> {
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (response, shutdown) = self.request(&line);
+            if let Some(response) = response {
+                writeln!(writer, "{}", response)?;
+                writer.flush()?;
+            }
+            if shutdown {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// The response to one request, `None` for a notification, and whether it asked the
+    /// server to stop.
+    fn request(&self, line: &str) -> (Option<String>, bool) {
+        let Some(request) = json::parse(line) else {
+            return (
+                Some(failure(&Value::Null, PARSE_ERROR, "Parse error")),
+                false,
+            );
+        };
+        let id = request.get("id");
+        let method = request.get("method").and_then(Value::as_str);
+        let respond = |outcome: Result<String, (i32, String)>| {
+            let id = id?;
+            Some(match outcome {
+                Ok(result) => format!(
+                    r#"{{"jsonrpc":"2.0","id":{},"result":{}}}"#,
+                    id.to_json(),
+                    result
+                ),
+                Err((code, message)) => failure(id, code, &message),
+            })
+        };
+        match method {
+            Some("search") => (respond(self.search(request.get("params"))), false),
+            Some("shutdown") => (respond(Ok("null".to_string())), true),
+            Some(method) => {
+                let message = format!("Method not found: {}", method);
+                (respond(Err((METHOD_NOT_FOUND, message))), false)
+            }
+            None => {
+                let id = id.unwrap_or(&Value::Null);
+                (Some(failure(id, INVALID_REQUEST, "Invalid request")), false)
+            }
+        }
+    }
+
+    /// Runs a `search` request, returning its result or why it couldn't run.
+    fn search(&self, params: Option<&Value>) -> Result<String, (i32, String)> {
+        let invalid = |message: &str| {
+            (
+                INVALID_PARAMS,
+                message.trim_start_matches("Error: ").to_string(),
+            )
+        };
+        let args = match params.and_then(|params| params.get("args")) {
+            Some(Value::Array(args)) => args
+                .iter()
+                .map(|arg| arg.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>(),
+            _ => None,
+        };
+        let args = args.ok_or_else(|| invalid("params.args must be an array of strings"))?;
+        let searcher = self.searcher(&args).map_err(|message| invalid(&message))?;
+
+        let matches = searcher
+            .search()
+            .map(|found| json::selected(&found))
+            .collect::<Vec<_>>();
+        Ok(format!(r#"{{"matches":[{}]}}"#, matches.join(",")))
+    }
+
+    /// The compiled search for `args`, compiling it if it hasn't been yet.
+    fn searcher(&self, args: &[String]) -> Result<Arc<Searcher>, String> {
+        if let Some(searcher) = self.searches.lock().unwrap().get(args) {
+            return Ok(searcher.clone());
+        }
+        let command = std::iter::once("yagrep".to_string()).chain(args.iter().cloned());
+        let config = match cli::parse(command.collect())? {
+            Command::Search { config, .. } => config,
+            _ => return Err("the arguments don't describe a search".to_string()),
+        };
+        match &config.path {
+            Some(path) if path.exists() => {}
+            Some(_) => return Err("File not found".to_string()),
+            None => return Err("a search needs a path".to_string()),
+        }
+        let searcher = Arc::new(Searcher::new(*config).map_err(|err| err.to_string())?);
+        let mut searches = self.searches.lock().unwrap();
+        if searches.len() >= CACHE_SIZE {
+            searches.clear();
+        }
+        searches.insert(args.to_vec(), searcher.clone());
+        Ok(searcher)
+    }
+}
+
+/// An error response.
+fn failure(id: &Value, code: i32, message: &str) -> String {
+    format!(
+        r#"{{"jsonrpc":"2.0","id":{},"error":{{"code":{},"message":{}}}}}"#,
+        id.to_json(),
+        code,
+        json::string(message)
+    )
+}
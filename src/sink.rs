@@ -0,0 +1,232 @@
+//! The search of one input, separated from how its results are shown: [`drive`] selects
+//! lines and hands them to a [`Sink`], and each output mode is a sink (see `printer`).
+
+use std::collections::VecDeque;
+use std::io::BufRead;
+
+use crate::config::SearchConfig;
+use crate::lines::Lines;
+use crate::matcher::Matcher;
+
+/// A line handed to a sink.
+pub(crate) struct SinkLine<'a> {
+    /// 1-based.
+    pub(crate) line_number: usize,
+    /// Where the line starts in the input.
+    pub(crate) offset: usize,
+    /// The line without its terminator. In multiline mode, a selected line can be several
+    /// lines joined by `\n`.
+    pub(crate) line: &'a [u8],
+}
+
+impl SinkLine<'_> {
+    /// The number of the last line this one spans.
+    pub(crate) fn end_line_number(&self) -> usize {
+        self.line_number + self.line.iter().filter(|&&byte| byte == b'\n').count()
+    }
+}
+
+/// Receives the results of searching one input, in order: `on_file_begin`, then every
+/// selected line with the context lines around it, then `on_file_end`.
+pub(crate) trait Sink {
+    /// How many context lines the sink wants before and after each selected line.
+    fn context(&self) -> (usize, usize) {
+        (0, 0)
+    }
+
+    fn on_file_begin(&mut self, _label: &str) {}
+
+    /// A selected line. Returns whether to keep searching the input; sinks that only need
+    /// to know whether anything matched stop at the first line.
+    fn on_match(&mut self, line: &SinkLine) -> bool;
+
+    /// Whether every line that isn't selected should come as a context line, for
+    /// `--passthru`.
+    fn passthru(&self) -> bool {
+        false
+    }
+
+    /// A context line, delivered in line order with the selected lines.
+    fn on_context(&mut self, _line: &SinkLine) {}
+
+    /// Called once the input is exhausted or the search stopped, with how many lines
+    /// were selected.
+    fn on_file_end(&mut self, _label: &str, _selected: usize) {}
+
+    /// Whether the input counts as a match for the exit status.
+    fn matched(&self, selected: usize) -> bool {
+        selected > 0
+    }
+}
+
+/// Searches the lines of one input, honouring `invert_match` and `max_count`, and feeds
+/// the results to `sink`. Returns how many lines were selected; with `multiline`, a
+/// match spanning several lines selects them as one.
+pub(crate) fn drive(
+    matcher: &Matcher,
+    label: &str,
+    mut lines: Lines<impl BufRead>,
+    app: &SearchConfig,
+    sink: &mut dyn Sink,
+) -> usize {
+    let invert = app.invert_match;
+    // Matches are only counted when something reports the totals.
+    let mut matches = 0;
+    let mut count = |line: &[u8]| {
+        if (app.stats || app.json) && !invert {
+            matches += matcher.find_iter(line).count();
+        }
+    };
+    sink.on_file_begin(label);
+    let selected = match app.multiline {
+        true => {
+            let lines = lines.by_ref().collect::<Vec<_>>();
+            feed(regions(matcher, lines, invert), app, sink, &mut count)
+        }
+        false => {
+            let units = lines.by_ref().enumerate().map(|(index, (offset, line))| {
+                let selected = matcher.is_match(&line) != invert;
+                (index + 1, offset, line, selected)
+            });
+            feed(units, app, sink, &mut count)
+        }
+    };
+    // The lines before a read error were still searched.
+    if let Some(error) = lines.take_error() {
+        app.error(&format!("Failed to read {}: {}", label, error));
+    }
+    sink.on_file_end(label, selected);
+    let mut totals = app.totals.lock().unwrap();
+    totals.searched(selected, matches, lines.bytes_read());
+    selected
+}
+
+/// Hands `units` to `sink` as selected or context lines. A unit is a line, or a run of
+/// lines joined by `\n` in multiline mode, with its first line number, offset and whether
+/// it is selected. Every selected unit also goes to `count`.
+fn feed(
+    units: impl Iterator<Item = (usize, usize, Vec<u8>, bool)>,
+    app: &SearchConfig,
+    sink: &mut dyn Sink,
+    count: &mut dyn FnMut(&[u8]),
+) -> usize {
+    let (before_context, after_context) = sink.context();
+    let mut before: VecDeque<(usize, usize, Vec<u8>)> = VecDeque::with_capacity(before_context);
+    let mut after_remaining = 0;
+    let mut selected = 0;
+    let passthru = sink.passthru();
+
+    for (line_number, offset, line, is_selected) in units {
+        if app.should_stop() {
+            break;
+        }
+        let limit_reached = app.max_count.is_some_and(|max| selected >= max);
+        if limit_reached && after_remaining == 0 && !passthru {
+            break;
+        }
+        if !limit_reached && is_selected {
+            selected += 1;
+            for (line_number, offset, line) in before.drain(..) {
+                let line = SinkLine {
+                    line_number,
+                    offset,
+                    line: &line,
+                };
+                sink.on_context(&line);
+            }
+            count(&line);
+            let line = SinkLine {
+                line_number,
+                offset,
+                line: &line,
+            };
+            if !sink.on_match(&line) {
+                break;
+            }
+            after_remaining = after_context;
+        } else if passthru {
+            let line = SinkLine {
+                line_number,
+                offset,
+                line: &line,
+            };
+            sink.on_context(&line);
+        } else if after_remaining > 0 {
+            let line = SinkLine {
+                line_number,
+                offset,
+                line: &line,
+            };
+            sink.on_context(&line);
+            after_remaining -= 1;
+        } else if before_context > 0 {
+            if before.len() == before_context {
+                before.pop_front();
+            }
+            before.push_back((line_number, offset, line));
+        }
+    }
+    selected
+}
+
+/// The units of multiline mode: the lines are joined and searched as one text, and the
+/// lines each match touches are merged into a single unit. Inverted, the lines no
+/// match touches are selected one by one instead.
+fn regions(
+    matcher: &Matcher,
+    lines: Vec<(usize, Vec<u8>)>,
+    invert: bool,
+) -> impl Iterator<Item = (usize, usize, Vec<u8>, bool)> {
+    let mut text = Vec::new();
+    // Where each line starts in `text`.
+    let mut starts = Vec::with_capacity(lines.len());
+    for (index, (_, line)) in lines.iter().enumerate() {
+        if index > 0 {
+            text.push(b'\n');
+        }
+        starts.push(text.len());
+        text.extend_from_slice(line);
+    }
+    let line_at = |position: usize| starts.partition_point(|&start| start <= position) - 1;
+
+    // `(first, last)` line indices of every match, overlapping ones merged.
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    if !lines.is_empty() {
+        for found in matcher.find_iter(&text) {
+            let first = line_at(found.start());
+            // A match ending with a line's `\n` doesn't reach into the next line.
+            let last = line_at(found.end().saturating_sub(1).max(found.start()));
+            match spans.last_mut() {
+                Some((_, previous)) if first <= *previous => *previous = last.max(*previous),
+                _ => spans.push((first, last)),
+            }
+        }
+    }
+
+    let mut units = Vec::new();
+    let mut spans = spans.into_iter().peekable();
+    let mut index = 0;
+    while index < lines.len() {
+        let (offset, _) = lines[index];
+        match spans.peek() {
+            Some(&(first, last)) if first == index => {
+                spans.next();
+                let end = starts[last] + lines[last].1.len();
+                let region = text[starts[first]..end].to_vec();
+                match invert {
+                    true => units.extend((first..=last).map(|index| {
+                        let (offset, line) = &lines[index];
+                        (index + 1, *offset, line.clone(), false)
+                    })),
+                    false => units.push((index + 1, offset, region, true)),
+                }
+                index = last + 1;
+            }
+            _ => {
+                units.push((index + 1, offset, lines[index].1.clone(), invert));
+                index += 1;
+            }
+        }
+    }
+    units.into_iter()
+}
@@ -0,0 +1,88 @@
+//! Totals for a whole run, reported by `--stats` and the `--json` summary event. Every
+//! search thread adds to the one [`Stats`] in `SearchConfig`.
+
+/// Why the walk passed over a file without searching it.
+#[derive(Clone, Copy)]
+pub(crate) enum Skip {
+    Hidden,
+    /// Excluded by an ignore file.
+    Ignored,
+    /// Excluded by `-g` or the file types.
+    Filtered,
+    /// Not tracked by git, with `--git-tracked`.
+    Untracked,
+    /// Larger than `--max-filesize`.
+    TooLarge,
+    /// Binary, where searching it could only mean rewriting it.
+    Binary,
+    Unreadable,
+    /// A FIFO, socket or device node, without `--devices read`.
+    Special,
+}
+
+/// How each [`Skip`] is described, in the same order.
+const SKIP_REASONS: [&str; 8] = [
+    "hidden",
+    "ignored",
+    "excluded by glob or type",
+    "untracked",
+    "larger than --max-filesize",
+    "binary",
+    "unreadable",
+    "special files",
+];
+
+#[derive(Default)]
+pub(crate) struct Stats {
+    pub(crate) files_searched: usize,
+    pub(crate) files_matched: usize,
+    pub(crate) matched_lines: usize,
+    pub(crate) matches: usize,
+    pub(crate) bytes_searched: usize,
+    /// Files skipped, counted by [`Skip`].
+    skipped: [usize; SKIP_REASONS.len()],
+}
+
+impl Stats {
+    /// Adds one searched file, with how many lines were selected and how many matches
+    /// they contain.
+    pub(crate) fn searched(&mut self, matched_lines: usize, matches: usize, bytes: usize) {
+        self.files_searched += 1;
+        self.files_matched += usize::from(matched_lines > 0);
+        self.matched_lines += matched_lines;
+        self.matches += matches;
+        self.bytes_searched += bytes;
+    }
+
+    pub(crate) fn skipped(&mut self, reason: Skip) {
+        self.skipped[reason as usize] += 1;
+    }
+
+    /// The `--stats` report, one total per line.
+    pub(crate) fn report(&self, elapsed: std::time::Duration) -> String {
+        let skipped = self.skipped.iter().sum::<usize>();
+        let reasons = self
+            .skipped
+            .iter()
+            .zip(SKIP_REASONS)
+            .filter(|(&count, _)| count > 0)
+            .map(|(count, reason)| format!("{} {}", count, reason))
+            .collect::<Vec<_>>();
+        let reasons = match reasons.is_empty() {
+            true => String::new(),
+            false => format!(" ({})", reasons.join(", ")),
+        };
+        format!(
+            "{} matched lines\n{} matches\n{} files contained matches\n{} files searched\n\
+             {} files skipped{}\n{} bytes searched\n{:.6} seconds\n",
+            self.matched_lines,
+            self.matches,
+            self.files_matched,
+            self.files_searched,
+            skipped,
+            reasons,
+            self.bytes_searched,
+            elapsed.as_secs_f64()
+        )
+    }
+}
@@ -0,0 +1,152 @@
+//! [`AsyncSearcher`]: the search for async programs, behind the `async` feature. The
+//! search runs on its own thread and hands matches over through a bounded queue, so
+//! awaiting them never blocks the caller's runtime.
+//!
+//! Nothing here depends on a particular runtime: [`MatchStream`] is a
+//! `futures_core::Stream`, so the combinators of `futures` or `tokio-stream` work on it
+//! directly, or each match can simply be awaited with [`MatchStream::next_match`].
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use futures_core::Stream;
+
+use crate::matches::Match;
+use crate::searcher::Searcher;
+
+/// How many matches the search thread gets ahead of the consumer before it waits.
+const QUEUE_CAPACITY: usize = 256;
+
+/// A [`Searcher`] whose results are awaited instead of iterated.
+#[derive(Clone)]
+pub struct AsyncSearcher {
+    searcher: Arc<Searcher>,
+}
+
+impl AsyncSearcher {
+    pub fn new(searcher: Searcher) -> AsyncSearcher {
+        AsyncSearcher {
+            searcher: Arc::new(searcher),
+        }
+    }
+
+    /// Starts a search yielding what [`Searcher::search`] would. Dropping the stream
+    /// stops the search.
+    pub fn search(&self) -> MatchStream {
+        let shared = Arc::new(Shared::default());
+        let searcher = Arc::clone(&self.searcher);
+        let producer = Arc::clone(&shared);
+        std::thread::spawn(move || {
+            for found in searcher.search() {
+                if !producer.push(found) {
+                    return;
+                }
+            }
+            producer.finish();
+        });
+        MatchStream { shared }
+    }
+}
+
+/// The matches of one [`AsyncSearcher::search`], in walk order.
+pub struct MatchStream {
+    shared: Arc<Shared>,
+}
+
+impl MatchStream {
+    /// The next match, or `None` when the search is over.
+    pub fn next_match(&mut self) -> NextMatch<'_> {
+        NextMatch { stream: self }
+    }
+}
+
+impl Stream for MatchStream {
+    type Item = Match;
+
+    /// Returns the next match once the search thread has found it, or `None` when the
+    /// search is over.
+    fn poll_next(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Option<Match>> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        if let Some(found) = queue.matches.pop_front() {
+            self.shared.space.notify_one();
+            return Poll::Ready(Some(found));
+        }
+        if queue.finished {
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(context.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for MatchStream {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().closed = true;
+        self.shared.space.notify_one();
+    }
+}
+
+/// The future returned by [`MatchStream::next_match`].
+pub struct NextMatch<'a> {
+    stream: &'a mut MatchStream,
+}
+
+impl Future for NextMatch<'_> {
+    type Output = Option<Match>;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Option<Match>> {
+        Pin::new(&mut *self.stream).poll_next(context)
+    }
+}
+
+/// The queue between the search thread and the stream.
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Signalled when the queue has room again or the stream is dropped.
+    space: Condvar,
+}
+
+#[derive(Default)]
+struct Queue {
+    matches: VecDeque<Match>,
+    /// The search is over: every match it found is in `matches`.
+    finished: bool,
+    /// The stream was dropped, so the search should stop.
+    closed: bool,
+    /// The task waiting for the next match.
+    waker: Option<Waker>,
+}
+
+impl Shared {
+    /// Queues `found`, waiting while the queue is full. Returns `false` once the stream
+    /// is gone.
+    fn push(&self, found: Match) -> bool {
+        let queue = self.queue.lock().unwrap();
+        let mut queue = self
+            .space
+            .wait_while(queue, |queue| {
+                queue.matches.len() >= QUEUE_CAPACITY && !queue.closed
+            })
+            .unwrap();
+        if queue.closed {
+            return false;
+        }
+        queue.matches.push_back(found);
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+        true
+    }
+
+    fn finish(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.finished = true;
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! The `--summary` report: a table of the files that matched, ranked by how many matches
+//! each has, with its share of the total and, with `--summary-chart`, a bar scaled to the
+//! file with the most. Every search thread adds to the one [`Summary`] in `SearchConfig`.
+
+/// The longest bar, drawn for the file with the most matches.
+const BAR_WIDTH: usize = 40;
+
+#[derive(Default)]
+pub(crate) struct Summary {
+    /// Each file that matched, with its count, in the order they finished.
+    files: Vec<(String, usize)>,
+}
+
+impl Summary {
+    /// Adds one searched file; one without matches isn't listed.
+    pub(crate) fn add(&mut self, label: &str, matches: usize) {
+        if matches > 0 {
+            self.files.push((label.to_string(), matches));
+        }
+    }
+
+    /// The table, most matches first and ties by path, ending with the totals. Empty when
+    /// nothing matched.
+    pub(crate) fn report(&self, chart: bool) -> String {
+        if self.files.is_empty() {
+            return String::new();
+        }
+        let mut files = self.files.iter().collect::<Vec<_>>();
+        files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let total = files.iter().map(|(_, count)| count).sum::<usize>();
+        let most = files[0].1;
+        let width = total.to_string().len().max("matches".len());
+        let bar = |count: usize| match chart {
+            // Every listed file gets at least one mark.
+            true => format!(
+                "{:<BAR_WIDTH$}  ",
+                "#".repeat((count * BAR_WIDTH).div_ceil(most))
+            ),
+            false => String::new(),
+        };
+
+        let mut report = format!("{:>width$}  {:>6}  {}file\n", "matches", "share", bar(0));
+        for (path, count) in &files {
+            let share = *count as f64 * 100.0 / total as f64;
+            report.push_str(&format!(
+                "{:>width$}  {:>5.1}%  {}{}\n",
+                count,
+                share,
+                bar(*count),
+                path
+            ));
+        }
+        report.push_str(&format!(
+            "{:>width$}  {:>5.1}%  {}{} files\n",
+            total,
+            100.0,
+            bar(0),
+            files.len()
+        ));
+        report
+    }
+}
@@ -0,0 +1,33 @@
+//! The `--tally` report: every distinct text matched, or captured by the `--group`, with
+//! how many times it was found across the search, the most frequent first. Every search
+//! thread adds to the one [`Tally`] in `SearchConfig`.
+
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub(crate) struct Tally {
+    counts: HashMap<Vec<u8>, usize>,
+}
+
+impl Tally {
+    /// Adds the texts found in one file.
+    pub(crate) fn add(&mut self, found: HashMap<Vec<u8>, usize>) {
+        for (text, count) in found {
+            *self.counts.entry(text).or_default() += count;
+        }
+    }
+
+    /// One line per text, with its count right-aligned, most found first and ties in
+    /// byte order. Empty when nothing matched.
+    pub(crate) fn report(&self) -> String {
+        let mut counts = self.counts.iter().collect::<Vec<_>>();
+        counts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        let width = counts
+            .first()
+            .map_or(0, |(_, count)| count.to_string().len());
+        counts
+            .iter()
+            .map(|(text, count)| format!("{:>width$}  {}\n", count, String::from_utf8_lossy(text)))
+            .collect()
+    }
+}
@@ -0,0 +1,165 @@
+//! Output templates for `--format TEMPLATE`, such as `{path}\t{line}\t{text}`: one line
+//! is printed per match, with every placeholder filled in.
+//!
+//! The placeholders are `{path}`, `{line}`, `{column}`, `{match}` and `{text}` (the
+//! whole line), capture groups by number (`{1}`) or name (`{word}`), and the file's
+//! `{size}` in bytes and `{modified}` time in seconds since the Unix epoch. `{{` and `}}`
+//! are literal braces, and `\t`, `\n` and `\\` are a tab, a newline and a backslash.
+
+use std::fs::Metadata;
+
+use crate::matcher::Captures;
+
+/// A parsed template.
+pub(crate) struct Template {
+    parts: Vec<Part>,
+}
+
+enum Part {
+    Literal(String),
+    Path,
+    Line,
+    Column,
+    Match,
+    Text,
+    Group(usize),
+    /// A named group, or nothing when the pattern has no group of that name.
+    Name(String),
+    Size,
+    Modified,
+}
+
+/// What one match fills a template in with.
+pub(crate) struct Fields<'a> {
+    pub(crate) path: &'a str,
+    pub(crate) line: usize,
+    /// 1-based, in characters.
+    pub(crate) column: usize,
+    pub(crate) matched: &'a str,
+    pub(crate) text: &'a str,
+    /// `None` for an inverted line, which has no match.
+    pub(crate) captures: Option<&'a Captures<'a>>,
+    /// `None` when the input isn't a file on disk.
+    pub(crate) metadata: Option<&'a Metadata>,
+}
+
+impl Template {
+    /// Parses `template`, or returns `None` for an unclosed or empty placeholder or a
+    /// stray `}`.
+    pub(crate) fn parse(template: &str) -> Option<Template> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return None,
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if !closed || name.is_empty() {
+                        return None;
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(placeholder(name));
+                }
+                '\\' => match chars.next() {
+                    Some('t') => literal.push('\t'),
+                    Some('n') => literal.push('\n'),
+                    Some('\\') => literal.push('\\'),
+                    Some(other) => {
+                        literal.push('\\');
+                        literal.push(other);
+                    }
+                    None => literal.push('\\'),
+                },
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Some(Template { parts })
+    }
+
+    /// Whether filling the template in needs the file's metadata.
+    pub(crate) fn uses_metadata(&self) -> bool {
+        self.parts
+            .iter()
+            .any(|part| matches!(part, Part::Size | Part::Modified))
+    }
+
+    pub(crate) fn render(&self, fields: &Fields) -> String {
+        let mut rendered = String::new();
+        let group = |found: Option<crate::matcher::Found>| {
+            found.map_or(String::new(), |found| {
+                String::from_utf8_lossy(found.as_bytes()).into_owned()
+            })
+        };
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => rendered.push_str(literal),
+                Part::Path => rendered.push_str(fields.path),
+                Part::Line => rendered.push_str(&fields.line.to_string()),
+                Part::Column => rendered.push_str(&fields.column.to_string()),
+                Part::Match => rendered.push_str(fields.matched),
+                Part::Text => rendered.push_str(fields.text),
+                Part::Group(index) => {
+                    let found = fields.captures.and_then(|captures| captures.get(*index));
+                    rendered.push_str(&group(found));
+                }
+                Part::Name(name) => {
+                    let found = fields.captures.and_then(|captures| captures.named(name));
+                    rendered.push_str(&group(found));
+                }
+                Part::Size => {
+                    if let Some(metadata) = fields.metadata {
+                        rendered.push_str(&metadata.len().to_string());
+                    }
+                }
+                Part::Modified => {
+                    let modified = fields
+                        .metadata
+                        .and_then(|metadata| metadata.modified().ok())
+                        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok());
+                    if let Some(modified) = modified {
+                        rendered.push_str(&modified.as_secs().to_string());
+                    }
+                }
+            }
+        }
+        rendered
+    }
+}
+
+fn placeholder(name: String) -> Part {
+    match name.as_str() {
+        "path" => Part::Path,
+        "line" => Part::Line,
+        "column" => Part::Column,
+        "match" => Part::Match,
+        "text" => Part::Text,
+        "size" => Part::Size,
+        "modified" => Part::Modified,
+        _ => match name.parse::<usize>() {
+            Ok(index) => Part::Group(index),
+            Err(_) => Part::Name(name),
+        },
+    }
+}
@@ -0,0 +1,71 @@
+//! Terminal interaction for `--write --interactive`. Questions go to stderr and answers
+//! are read from the controlling terminal, so prompting still works when stdout is
+//! piped or stdin is redirected.
+
+use std::io::{BufRead, BufReader, Write};
+
+/// A decision for one proposed change, modelled on `git add -p`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Answer {
+    /// Apply this change.
+    Yes,
+    /// Skip this change.
+    No,
+    /// Apply this and every remaining change without asking.
+    All,
+    /// Skip this and every remaining change.
+    Quit,
+}
+
+pub struct Prompter {
+    input: Box<dyn BufRead + Send>,
+    /// Set once the user answered `a` or `q`; later questions get it without asking.
+    standing: Option<Answer>,
+}
+
+impl Prompter {
+    pub fn open() -> std::io::Result<Prompter> {
+        #[cfg(unix)]
+        let input: Box<dyn BufRead + Send> =
+            Box::new(BufReader::new(std::fs::File::open("/dev/tty")?));
+        #[cfg(not(unix))]
+        let input: Box<dyn BufRead + Send> = Box::new(BufReader::new(std::io::stdin()));
+
+        Ok(Prompter {
+            input,
+            standing: None,
+        })
+    }
+
+    /// Prints `question` and waits for y/n/a/q, asking again on anything else.
+    /// End of input counts as `q`.
+    pub fn ask(&mut self, question: &str) -> Answer {
+        if let Some(answer) = self.standing {
+            return answer;
+        }
+
+        loop {
+            eprint!("{} [y,n,a,q]? ", question);
+            let _ = std::io::stderr().flush();
+
+            let mut line = String::new();
+            let answer = match self.input.read_line(&mut line) {
+                Ok(0) | Err(_) => Answer::Quit,
+                Ok(_) => match line.trim() {
+                    "y" => Answer::Yes,
+                    "n" => Answer::No,
+                    "a" => Answer::All,
+                    "q" => Answer::Quit,
+                    _ => {
+                        eprintln!("y - apply this change\nn - skip this change\na - apply this and all remaining changes\nq - quit; skip this and all remaining changes");
+                        continue;
+                    }
+                },
+            };
+            if matches!(answer, Answer::All | Answer::Quit) {
+                self.standing = Some(answer);
+            }
+            return answer;
+        }
+    }
+}
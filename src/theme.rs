@@ -0,0 +1,150 @@
+//! Output colors, overridable with `--colors {type}:{attribute}:{value}` where type is
+//! `path`, `line`, `column` or `match`, attribute is `fg`, `bg` or `style`, and
+//! `{type}:none` clears every attribute of that type. With several patterns, each one's
+//! matches have their own color: `match` is the first pattern's, and `match2` to
+//! `match5` the next ones', reused in turn for any more.
+
+use colored::{Color, ColoredString, Colorize};
+
+const INVALID_COLORS: &str = "Error: Invalid --colors specification";
+
+/// The parts of an output line that can be colored.
+#[derive(Clone, Copy)]
+pub enum Element {
+    Path,
+    Line,
+    Column,
+    Match,
+    /// A match of the pattern at this index; the first pattern's are `Match`.
+    Pattern(usize),
+}
+
+#[derive(Clone, Default)]
+pub struct Style {
+    fg: Option<Color>,
+    bg: Option<Color>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+}
+
+pub struct Theme {
+    path: Style,
+    line: Style,
+    column: Style,
+    matched: Style,
+    /// For the matches of the second pattern on.
+    patterns: Vec<Style>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            path: Style {
+                fg: Some(Color::Green),
+                ..Style::default()
+            },
+            line: Style::default(),
+            column: Style::default(),
+            matched: Style {
+                fg: Some(Color::Red),
+                bold: true,
+                ..Style::default()
+            },
+            patterns: [Color::Blue, Color::Magenta, Color::Cyan, Color::Yellow]
+                .into_iter()
+                .map(|color| Style {
+                    fg: Some(color),
+                    bold: true,
+                    ..Style::default()
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Theme {
+    /// Applies one `--colors` specification on top of the current theme.
+    pub fn apply(&mut self, spec: &str) -> Result<(), &'static str> {
+        let mut parts = spec.split(':');
+        let style = match parts.next() {
+            Some("path") => &mut self.path,
+            Some("line") => &mut self.line,
+            Some("column") => &mut self.column,
+            Some("match" | "match1") => &mut self.matched,
+            Some(name) => {
+                let number = name
+                    .strip_prefix("match")
+                    .and_then(|n| n.parse::<usize>().ok());
+                match number {
+                    Some(number @ 2..) if number - 2 < self.patterns.len() => {
+                        &mut self.patterns[number - 2]
+                    }
+                    _ => return Err(INVALID_COLORS),
+                }
+            }
+            None => return Err(INVALID_COLORS),
+        };
+
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some("none"), None, None) => *style = Style::default(),
+            (Some("fg"), Some(value), None) => style.fg = Some(parse_color(value)?),
+            (Some("bg"), Some(value), None) => style.bg = Some(parse_color(value)?),
+            (Some("style"), Some(value), None) => match value {
+                "bold" => style.bold = true,
+                "nobold" => style.bold = false,
+                "italic" => style.italic = true,
+                "noitalic" => style.italic = false,
+                "underline" => style.underline = true,
+                "nounderline" => style.underline = false,
+                _ => return Err(INVALID_COLORS),
+            },
+            _ => return Err(INVALID_COLORS),
+        }
+        Ok(())
+    }
+
+    pub fn paint(&self, element: Element, text: &str) -> ColoredString {
+        let style = match element {
+            Element::Path => &self.path,
+            Element::Line => &self.line,
+            Element::Column => &self.column,
+            Element::Match | Element::Pattern(0) => &self.matched,
+            Element::Pattern(index) => &self.patterns[(index - 1) % self.patterns.len()],
+        };
+
+        let mut painted = text.normal();
+        if let Some(fg) = style.fg {
+            painted = painted.color(fg);
+        }
+        if let Some(bg) = style.bg {
+            painted = painted.on_color(bg);
+        }
+        if style.bold {
+            painted = painted.bold();
+        }
+        if style.italic {
+            painted = painted.italic();
+        }
+        if style.underline {
+            painted = painted.underline();
+        }
+        painted
+    }
+}
+
+/// Accepts a color name (`red`, `bright blue`, ...) or an `R,G,B` triple.
+fn parse_color(value: &str) -> Result<Color, &'static str> {
+    let channels = value
+        .split(',')
+        .map(|channel| channel.trim().parse::<u8>())
+        .collect::<Result<Vec<_>, _>>();
+    match channels.as_deref() {
+        Ok([r, g, b]) => Ok(Color::TrueColor {
+            r: *r,
+            g: *g,
+            b: *b,
+        }),
+        _ => value.parse().map_err(|_| INVALID_COLORS),
+    }
+}
@@ -0,0 +1,48 @@
+//! The files git tracks under a directory (`--git-tracked`), listed by one
+//! `git ls-files -z` when the walk starts, so untracked build output is never visited.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::git;
+
+/// Every tracked file under the searched directory, with the directories that lead to
+/// them, as paths joined onto it the way the walk joins them.
+#[derive(Default)]
+pub(crate) struct Tracked {
+    files: HashSet<PathBuf>,
+    directories: HashSet<PathBuf>,
+}
+
+impl Tracked {
+    /// Asks git for the files tracked under `directory`, or says why it couldn't.
+    pub(crate) fn list(directory: &Path) -> Result<Tracked, String> {
+        let listing = git::output(directory, ["ls-files", "-z"])?;
+        let mut tracked = Tracked {
+            files: HashSet::new(),
+            directories: HashSet::new(),
+        };
+        for name in listing.split(|&byte| byte == 0) {
+            if name.is_empty() {
+                continue;
+            }
+            let path = directory.join(git::path_from_bytes(name));
+            let parents = path.ancestors().skip(1);
+            for parent in parents.take_while(|parent| *parent != directory) {
+                if !tracked.directories.insert(parent.to_path_buf()) {
+                    break;
+                }
+            }
+            tracked.files.insert(path);
+        }
+        Ok(tracked)
+    }
+
+    /// Whether the walk should visit `path`: a tracked file, or a directory holding one.
+    pub(crate) fn contains(&self, path: &Path, is_dir: bool) -> bool {
+        match is_dir {
+            true => self.directories.contains(path),
+            false => self.files.contains(path),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! File types: names for sets of file name globs, so `--type rust` selects `*.rs` files
+//! without spelling out the globs. `--type-add` extends the built-in table.
+
+use crate::glob::Glob;
+
+/// Built-in types, listed by `--type-list`.
+const BUILTIN: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    (
+        "cpp",
+        &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx", "*.h"],
+    ),
+    ("csharp", &["*.cs"]),
+    ("css", &["*.css", "*.scss", "*.sass", "*.less"]),
+    ("docker", &["Dockerfile", "*.dockerfile", "Dockerfile.*"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.mjs", "*.cjs", "*.jsx"]),
+    ("json", &["*.json", "*.jsonl"]),
+    ("kotlin", &["*.kt", "*.kts"]),
+    ("lua", &["*.lua"]),
+    (
+        "make",
+        &["Makefile", "makefile", "GNUmakefile", "*.mk", "*.mak"],
+    ),
+    ("md", &["*.md", "*.markdown"]),
+    ("php", &["*.php"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("ruby", &["*.rb", "Gemfile", "Rakefile", "*.gemspec"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh", ".bashrc", ".zshrc"]),
+    ("sql", &["*.sql"]),
+    ("swift", &["*.swift"]),
+    ("toml", &["*.toml", "Cargo.lock"]),
+    ("ts", &["*.ts", "*.tsx", "*.mts", "*.cts"]),
+    ("txt", &["*.txt"]),
+    ("xml", &["*.xml", "*.xsd", "*.xsl"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+pub struct Types {
+    definitions: Vec<(String, Vec<String>)>,
+    /// Globs of the `--type` selections; when any are given, only matching files are
+    /// searched.
+    selected: Vec<Glob>,
+    /// Globs of the `--type-not` selections, whose files are never searched.
+    negated: Vec<Glob>,
+}
+
+impl Types {
+    pub fn new() -> Types {
+        let definitions = BUILTIN
+            .iter()
+            .map(|(name, globs)| {
+                (
+                    name.to_string(),
+                    globs.iter().map(|glob| glob.to_string()).collect(),
+                )
+            })
+            .collect();
+        Types {
+            definitions,
+            selected: Vec::new(),
+            negated: Vec::new(),
+        }
+    }
+
+    /// Adds globs to a type from a `name:glob,glob` definition, creating the type if it's
+    /// new. Returns `None` if the definition is malformed.
+    pub fn add(&mut self, definition: &str) -> Option<()> {
+        let (name, globs) = definition.split_once(':')?;
+        let globs = globs
+            .split(',')
+            .map(str::trim)
+            .filter(|glob| !glob.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        if name.is_empty() || globs.is_empty() {
+            return None;
+        }
+        match self.definitions.iter_mut().find(|(known, _)| known == name) {
+            Some((_, known)) => known.extend(globs),
+            None => self.definitions.push((name.to_string(), globs)),
+        }
+        Some(())
+    }
+
+    /// Selects the files of type `name` (`--type`), or excludes them when `negated`
+    /// (`--type-not`). Returns `None` for an unknown type.
+    pub fn select(&mut self, name: &str, negated: bool) -> Option<()> {
+        let (_, globs) = self.definitions.iter().find(|(known, _)| known == name)?;
+        let globs = globs.iter().filter_map(|glob| Glob::parse(glob));
+        match negated {
+            true => self.negated.extend(globs),
+            false => self.selected.extend(globs),
+        }
+        Some(())
+    }
+
+    /// Whether the selections let a file named `file_name` be searched.
+    pub fn allows(&self, file_name: &str) -> bool {
+        let matches = |glob: &Glob| glob.matches(file_name, false);
+        (self.selected.is_empty() || self.selected.iter().any(matches))
+            && !self.negated.iter().any(matches)
+    }
+
+    /// Every type with its globs, one `name: glob, glob` line each, sorted by name.
+    pub fn list(&self) -> String {
+        let mut definitions = self.definitions.iter().collect::<Vec<_>>();
+        definitions.sort_by(|(a, _), (b, _)| a.cmp(b));
+        definitions
+            .iter()
+            .map(|(name, globs)| format!("{}: {}\n", name, globs.join(", ")))
+            .collect()
+    }
+}
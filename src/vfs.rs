@@ -0,0 +1,119 @@
+//! Files held in memory instead of on disk, for targets without a usable file system
+//! (wasm32 in a browser, whose exports in `wasm` search one).
+//! [`Searcher::search_in`](crate::Searcher::search_in) walks a [`MemoryFs`] with the
+//! same hidden, ignore, glob, type, size and depth filters as the directory walk.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::SearchConfig;
+use crate::ignore;
+use crate::walker::Job;
+
+/// A tree of files keyed by path; directories exist implicitly as the parents of files.
+#[derive(Clone, Default)]
+pub struct MemoryFs {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryFs {
+    pub fn new() -> MemoryFs {
+        MemoryFs::default()
+    }
+
+    /// Adds a file, replacing any earlier one at `path`.
+    pub fn insert(&mut self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) {
+        self.files.insert(path.into(), contents.into());
+    }
+
+    pub fn read(&self, path: &Path) -> Option<&[u8]> {
+        self.files.get(path).map(Vec::as_slice)
+    }
+
+    /// Whether some file is below `path`. The empty path is the root, which always is.
+    pub fn is_dir(&self, path: &Path) -> bool {
+        path.as_os_str().is_empty()
+            || self
+                .files
+                .keys()
+                .any(|file| file.starts_with(path) && file != path)
+    }
+
+    /// The entries directly inside `directory`, sorted by name, each with whether it is
+    /// a directory.
+    fn read_dir(&self, directory: &Path) -> Vec<(PathBuf, bool)> {
+        let mut entries: Vec<(PathBuf, bool)> = Vec::new();
+        for file in self.files.keys() {
+            let Ok(relative) = file.strip_prefix(directory) else {
+                continue;
+            };
+            let mut components = relative.components();
+            let Some(name) = components.next() else {
+                continue;
+            };
+            let entry = (directory.join(name), components.next().is_some());
+            // Keys are sorted, so a directory's files are adjacent.
+            if entries.last() != Some(&entry) {
+                entries.push(entry);
+            }
+        }
+        entries
+    }
+
+    fn read_to_string(&self, path: &Path) -> Option<String> {
+        self.read(path)
+            .map(|contents| String::from_utf8_lossy(contents).into_owned())
+    }
+}
+
+/// The job that starts a walk of `directory` in `fs`.
+pub(crate) fn root(fs: &MemoryFs, directory: &Path, app: &SearchConfig) -> Job {
+    let git = app.respect_ignore && app.respect_gitignore;
+    let ignore = ignore::Ignore::in_memory(git, app.respect_ignore)
+        .descend_with(directory, |path| fs.read_to_string(path));
+    Job::Directory(directory.to_path_buf(), ignore, Vec::new(), Vec::new())
+}
+
+/// Like `walker::list_directory`, over `fs`. There are no symlinks to follow.
+pub(crate) fn list_directory(
+    fs: &MemoryFs,
+    root: &Path,
+    directory: &Path,
+    ignore: &ignore::Ignore,
+    app: &SearchConfig,
+    key: &[usize],
+) -> Vec<Job> {
+    let mut jobs = Vec::new();
+    if app
+        .max_depth
+        .is_some_and(|max_depth| key.len() >= max_depth)
+    {
+        return jobs;
+    }
+    for (path, is_dir) in fs.read_dir(directory) {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if !app.hidden && file_name.starts_with('.') {
+            continue;
+        }
+        let included = match app.globs.matched(root, &path, is_dir) {
+            Some(included) => included,
+            None => !ignore.is_ignored(&path, is_dir) && (is_dir || app.types.allows(&file_name)),
+        };
+        if !included {
+            continue;
+        }
+        let len = fs.read(&path).map_or(0, <[u8]>::len) as u64;
+        if app.max_filesize.is_some_and(|max| !is_dir && len > max) {
+            continue;
+        }
+        let key = [key, &[jobs.len()]].concat();
+        match is_dir {
+            true => {
+                let ignore = ignore.descend_with(&path, |path| fs.read_to_string(path));
+                jobs.push(Job::Directory(path, ignore, Vec::new(), key));
+            }
+            false => jobs.push(Job::File(path, key)),
+        }
+    }
+    jobs
+}
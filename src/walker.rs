@@ -0,0 +1,283 @@
+//! The directory walk: listing directories on the thread pool, applying the hidden,
+//! ignore, glob, type, size and depth filters, and searching the files that remain.
+
+use crate::config::{SearchConfig, SortChoice};
+use crate::matcher::Matcher;
+use crate::stats::Skip;
+use crate::{ignore, index, ordered, parallel, searcher, tracked};
+
+/// A unit of work for the directory walk. Every job carries its key for
+/// [`ordered::Ordered`]: the indices of the entries leading to it from the root.
+pub(crate) enum Job {
+    /// A directory to list, with the ignore rules in effect for it and, with `--follow`,
+    /// the identities of the directories above it.
+    Directory(
+        std::path::PathBuf,
+        ignore::Ignore,
+        Vec<(u64, u64)>,
+        Vec<usize>,
+    ),
+    File(std::path::PathBuf, Vec<usize>),
+}
+
+/// The job that starts a walk of `directory`.
+pub(crate) fn root(directory: &std::path::Path, app: &SearchConfig) -> Job {
+    // Rules are reloaded on every walk, so `--watch` picks up edited ignore files.
+    let git = app.respect_ignore && app.respect_gitignore;
+    let mut ignore = ignore::Ignore::new(directory, git, app.respect_ignore);
+    if app.git_tracked {
+        // Without the list nothing can be known to be tracked, so nothing is searched.
+        let tracked = tracked::Tracked::list(directory).unwrap_or_else(|err| {
+            let directory = directory.display();
+            app.error(&format!(
+                "Failed to list the files git tracks in {}: {}",
+                directory, err
+            ));
+            tracked::Tracked::default()
+        });
+        ignore = ignore.with_tracked(tracked);
+    }
+    let ancestors = match app.follow {
+        true => directory
+            .metadata()
+            .ok()
+            .and_then(|metadata| file_id(&metadata)),
+        false => None,
+    };
+    Job::Directory(
+        directory.to_path_buf(),
+        ignore,
+        ancestors.into_iter().collect(),
+        Vec::new(),
+    )
+}
+
+/// Searches every file under `directory` on `app.thread_count()` threads, returning
+/// whether any matched. Directories are queued as jobs rather than recursed into, so
+/// the depth of the tree never deepens the stack, and each thread has at most one
+/// directory open, while it lists it.
+pub(crate) fn match_directory(
+    matcher: &Matcher,
+    directory: &std::path::Path,
+    app: &SearchConfig,
+) -> bool {
+    let matched = std::sync::atomic::AtomicBool::new(false);
+    let ordered = match app.sort {
+        SortChoice::Unordered => None,
+        _ => Some(ordered::Ordered::new(|output: &[u8]| app.emit(output))),
+    };
+    let index = index::Index::open(directory, matcher, app);
+
+    parallel::run(
+        app.thread_count(),
+        vec![root(directory, app)],
+        |job, worker| match job {
+            _ if app.should_stop() => worker.quit(),
+            Job::File(path, key) => {
+                let mut output = Vec::new();
+                if searcher::match_file(matcher, &path, index.as_ref(), app, &mut output) {
+                    matched.store(true, std::sync::atomic::Ordering::SeqCst);
+                    if app.quiet {
+                        worker.quit();
+                    }
+                }
+                match &ordered {
+                    Some(ordered) => ordered.file(&key, output),
+                    None => app.emit(&output),
+                }
+            }
+            Job::Directory(path, ignore, ancestors, key) => {
+                let jobs = list_directory(directory, &path, &ignore, &ancestors, app, &key);
+                // The entries must be known before any of them can finish.
+                if let Some(ordered) = &ordered {
+                    ordered.directory(&key, jobs.len());
+                }
+                // Workers pop their newest job first, so queue in reverse to visit entries in order.
+                for job in jobs.into_iter().rev() {
+                    worker.push(job);
+                }
+            }
+        },
+    );
+    if let Some(ordered) = ordered.filter(|_| app.should_stop()) {
+        ordered.abandon();
+    }
+
+    matched.into_inner()
+}
+
+/// Returns a job for every entry of `directory` that isn't hidden or ignored, where
+/// `root` is where the walk started and `key` is the directory's own key. An entry, or
+/// the whole directory, that can't be read is reported and left out.
+pub(crate) fn list_directory(
+    root: &std::path::Path,
+    directory: &std::path::Path,
+    ignore: &ignore::Ignore,
+    ancestors: &[(u64, u64)],
+    app: &SearchConfig,
+    key: &[usize],
+) -> Vec<Job> {
+    let mut jobs = Vec::new();
+    // A key has one index per level below the root, so its length is the directory's
+    // depth and its entries are one deeper.
+    if app
+        .max_depth
+        .is_some_and(|max_depth| key.len() >= max_depth)
+    {
+        return jobs;
+    }
+    // Only files count as skipped; a skipped directory's files are never seen.
+    let skip = |reason| app.totals.lock().unwrap().skipped(reason);
+    // The listing is read whole, closing the directory before any entry is looked at.
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(err) => {
+            app.error(&format!("Failed to read {}: {}", directory.display(), err));
+            return jobs;
+        }
+    };
+    let mut entries = entries
+        .filter_map(
+            |entry| match entry.and_then(|entry| Ok((entry.file_type()?, entry))) {
+                Ok(listed) => Some(listed),
+                Err(err) => {
+                    app.error(&format!("Failed to read {}: {}", directory.display(), err));
+                    None
+                }
+            },
+        )
+        .collect::<Vec<_>>();
+    sort(&mut entries, app);
+    // The directory is on the walk's file system, since no other is entered.
+    let device = match app.one_file_system {
+        true => directory
+            .metadata()
+            .ok()
+            .and_then(|metadata| file_id(&metadata))
+            .map(|(device, _)| device),
+        false => None,
+    };
+    for (file_type, entry) in entries {
+        let path = entry.path();
+        // Names needn't be UTF-8, so they're only ever compared as bytes or shown lossily.
+        let file_name = entry.file_name();
+        if !app.hidden && file_name.as_encoded_bytes().starts_with(b".") {
+            if !file_type.is_dir() {
+                skip(Skip::Hidden);
+            }
+            continue;
+        }
+        let follow = app.follow;
+        let symlink = file_type.is_symlink();
+        let metadata = match path.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) if symlink => {
+                if follow {
+                    app.warning(&format!("Broken symlink {}", path.display()));
+                }
+                skip(Skip::Unreadable);
+                continue;
+            }
+            Err(err) => {
+                app.error(&format!("Failed to read {}: {}", path.display(), err));
+                skip(Skip::Unreadable);
+                continue;
+            }
+        };
+        let is_dir = metadata.is_dir();
+        // Symlinked files are always searched, symlinked directories only with `--follow`.
+        if is_dir && symlink && !follow {
+            continue;
+        }
+        let elsewhere = file_id(&metadata)
+            .zip(device)
+            .is_some_and(|((other, _), device)| other != device);
+        if is_dir && elsewhere {
+            continue;
+        }
+        // An explicit `-g` match overrides the ignore files and file types.
+        let excluded = match app.globs.matched(root, &path, is_dir) {
+            _ if ignore.is_untracked(&path, is_dir) => Some(Skip::Untracked),
+            Some(true) => None,
+            Some(false) => Some(Skip::Filtered),
+            None if ignore.is_ignored(&path, is_dir) => Some(Skip::Ignored),
+            None if !is_dir && !app.types.allows(&file_name.to_string_lossy()) => {
+                Some(Skip::Filtered)
+            }
+            None => None,
+        };
+        if let Some(reason) = excluded {
+            if !is_dir {
+                skip(reason);
+            }
+            continue;
+        }
+        if let Some(max_filesize) = app.max_filesize {
+            if !is_dir && metadata.len() > max_filesize {
+                skip(Skip::TooLarge);
+                continue;
+            }
+        }
+        // Searching the `--output` file would find the results being written to it.
+        if app.output_id.is_some() && file_id(&metadata) == app.output_id {
+            continue;
+        }
+        // Anything else is a FIFO, socket or device, whose reads may never end.
+        let special = !metadata.is_file() && !is_dir;
+        if special && !app.read_devices {
+            skip(Skip::Special);
+            continue;
+        }
+        let key = [key, &[jobs.len()]].concat();
+        if metadata.is_file() || special {
+            jobs.push(Job::File(path, key));
+        } else if is_dir {
+            let mut ancestors = ancestors.to_vec();
+            // A link back to a directory being walked would be followed forever.
+            if let Some(id) = file_id(&metadata).filter(|_| follow) {
+                if ancestors.contains(&id) {
+                    app.warning(&format!("Symlink loop at {}", path.display()));
+                    continue;
+                }
+                ancestors.push(id);
+            }
+            let ignore = ignore.descend(&path);
+            jobs.push(Job::Directory(path, ignore, ancestors, key));
+        }
+    }
+    jobs
+}
+
+/// Orders a directory's entries for `--sort` and `--sortr`; every directory is sorted
+/// alone, so a directory's files are printed together wherever it falls in its parent.
+/// Otherwise they stay in the order the file system lists them.
+fn sort(entries: &mut [(std::fs::FileType, std::fs::DirEntry)], app: &SearchConfig) {
+    let metadata = |entry: &std::fs::DirEntry| entry.path().metadata().ok();
+    match app.sort {
+        SortChoice::Walk | SortChoice::Unordered => return,
+        SortChoice::Path => entries.sort_by_key(|(_, entry)| entry.file_name()),
+        // Entries whose metadata can't be read sort first; the walk reports them.
+        SortChoice::Modified => entries.sort_by_cached_key(|(_, entry)| {
+            metadata(entry).and_then(|metadata| metadata.modified().ok())
+        }),
+        SortChoice::Size => {
+            entries.sort_by_cached_key(|(_, entry)| metadata(entry).map(|metadata| metadata.len()))
+        }
+    }
+    if app.sort_reverse {
+        entries.reverse();
+    }
+}
+
+/// The `(device, inode)` pair that identifies a file or directory however it is reached.
+#[cfg(unix)]
+pub(crate) fn file_id(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// Without inodes there is no loop detection; `--max-depth` still bounds the walk.
+#[cfg(not(unix))]
+pub(crate) fn file_id(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
@@ -0,0 +1,117 @@
+//! The interface for JavaScript on wasm32, which needs no generated bindings: the caller
+//! copies a UTF-8 JSON request into a buffer from `yagrep_alloc`, passes it to
+//! `yagrep_search`, and reads the JSON response at `yagrep_response` for as many bytes as
+//! the search returned. `cargo rustc --lib --release --target wasm32-unknown-unknown
+//! --crate-type cdylib` builds the module.
+//!
+//! A request is `{"args": [...], "files": {"path": "contents", ...}}`: the arguments of a
+//! command line without the program name, and the files to search, held in a
+//! [`MemoryFs`] where the path argument, if any, names a file or directory. The response
+//! is `{"matches": [...]}`, with an object per selected line as `yagrep serve` returns
+//! them, or `{"error": "..."}`.
+//!
+//! ```js
+//! const { memory, yagrep_alloc, yagrep_free, yagrep_search, yagrep_response } =
+//!     instance.exports;
+//! const request = new TextEncoder().encode(JSON.stringify({ args, files }));
+//! const buffer = yagrep_alloc(request.length);
+//! new Uint8Array(memory.buffer, buffer, request.length).set(request);
+//! const length = yagrep_search(buffer, request.length);
+//! yagrep_free(buffer, request.length);
+//! const response = new Uint8Array(memory.buffer, yagrep_response(), length);
+//! const { matches } = JSON.parse(new TextDecoder().decode(response));
+//! ```
+
+use std::cell::RefCell;
+
+use crate::cli::{self, Command};
+use crate::json::{self, Value};
+use crate::searcher::Searcher;
+use crate::vfs::MemoryFs;
+
+thread_local! {
+    static RESPONSE: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A buffer of `len` bytes for a request, to be released with `yagrep_free`.
+#[no_mangle]
+pub extern "C" fn yagrep_alloc(len: usize) -> *mut u8 {
+    let mut buffer = Vec::<u8>::with_capacity(len);
+    let pointer = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    pointer
+}
+
+/// Releases a buffer from `yagrep_alloc`.
+///
+/// # Safety
+///
+/// `buffer` must come from `yagrep_alloc(len)` and not have been released already.
+#[no_mangle]
+pub unsafe extern "C" fn yagrep_free(buffer: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(buffer, 0, len));
+}
+
+/// Runs the search `request` describes, returning the length of its response, which
+/// is kept at `yagrep_response` until the next search.
+///
+/// # Safety
+///
+/// `request` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn yagrep_search(request: *const u8, len: usize) -> usize {
+    let request = std::slice::from_raw_parts(request, len);
+    let response = match search(request) {
+        Ok(matches) => format!(r#"{{"matches":[{}]}}"#, matches.join(",")),
+        Err(message) => {
+            let message = message.trim_start_matches("Error: ");
+            format!(r#"{{"error":{}}}"#, json::string(message))
+        }
+    };
+    RESPONSE.with(|last| {
+        *last.borrow_mut() = response.into_bytes();
+        last.borrow().len()
+    })
+}
+
+/// The response of the last `yagrep_search`.
+#[no_mangle]
+pub extern "C" fn yagrep_response() -> *const u8 {
+    RESPONSE.with(|last| last.borrow().as_ptr())
+}
+
+/// The selected lines of a request, each as JSON, or why it couldn't run.
+fn search(request: &[u8]) -> Result<Vec<String>, String> {
+    let request = std::str::from_utf8(request)
+        .ok()
+        .and_then(json::parse)
+        .ok_or("the request isn't JSON")?;
+    let args = match request.get("args") {
+        Some(Value::Array(args)) => args
+            .iter()
+            .map(|arg| arg.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>(),
+        _ => None,
+    };
+    let args = args.ok_or("args must be an array of strings")?;
+    let mut fs = MemoryFs::new();
+    match request.get("files") {
+        Some(Value::Object(files)) => {
+            for (path, contents) in files {
+                let contents = contents.as_str().ok_or("files must map paths to strings")?;
+                fs.insert(path, contents);
+            }
+        }
+        None => {}
+        Some(_) => return Err("files must be an object".to_string()),
+    }
+
+    let command = std::iter::once("yagrep".to_string()).chain(args);
+    let config = match cli::parse(command.collect())? {
+        Command::Search { config, .. } => config,
+        _ => return Err("the arguments don't describe a search".to_string()),
+    };
+    let searcher = Searcher::new(*config).map_err(|err| err.to_string())?;
+    let matches = searcher.search_in(&fs).map(|found| json::selected(&found));
+    Ok(matches.collect())
+}
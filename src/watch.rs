@@ -0,0 +1,228 @@
+//! `--watch`: re-runs the search whenever a file it would search changes. The tree is
+//! walked with the search's own filters, so ignored, hidden and excluded files, and
+//! directories past `--max-depth`, never trigger a run. On Linux the directories walked
+//! are watched with inotify, and the tree is only walked again when one of them reports
+//! a change; elsewhere it is rescanned every [`POLL_INTERVAL`].
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::config::SearchConfig;
+use crate::interrupt;
+use crate::searcher::Searcher;
+use crate::walker::{self, Job};
+
+/// How often the tree is rescanned where there are no change notifications, and how
+/// often a wait for one checks for Ctrl-C.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The modification time and size of every file a search would read, and the
+/// directories it would list.
+struct Snapshot {
+    files: HashMap<PathBuf, (Option<SystemTime>, u64)>,
+    directories: Vec<PathBuf>,
+}
+
+/// Waits for changes to what a [`Searcher`] searches under `root`.
+pub struct Watcher<'a> {
+    app: &'a SearchConfig,
+    root: PathBuf,
+    snapshot: Snapshot,
+}
+
+impl<'a> Watcher<'a> {
+    /// Records the files under `root` as they are now.
+    pub fn new(searcher: &'a Searcher, root: &Path) -> Watcher<'a> {
+        let app = searcher.config();
+        Watcher {
+            app,
+            root: root.to_path_buf(),
+            snapshot: Snapshot::take(root, app),
+        }
+    }
+
+    /// Blocks until a file has been added, removed or modified since the last call,
+    /// returning true, or until Ctrl-C, returning false.
+    pub fn wait(&mut self) -> bool {
+        let mut notifier = sys::Notifier::new();
+        loop {
+            // What changed during the run, or since the last notification.
+            let current = Snapshot::take(&self.root, self.app);
+            if current.files != self.snapshot.files {
+                self.snapshot = current;
+                return true;
+            }
+            // Directories created since are watched too, before their files change.
+            if let Some(notifier) = &mut notifier {
+                for directory in &current.directories {
+                    notifier.watch(directory);
+                }
+            }
+            self.snapshot = current;
+            loop {
+                if interrupt::interrupted() {
+                    return false;
+                }
+                let notified = match &mut notifier {
+                    Some(notifier) => notifier.wait(POLL_INTERVAL),
+                    None => {
+                        std::thread::sleep(POLL_INTERVAL);
+                        true
+                    }
+                };
+                if notified {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl Snapshot {
+    /// Walks `root` as a search would, one directory at a time, without reporting what
+    /// can't be read: the next run does.
+    fn take(root: &Path, app: &SearchConfig) -> Snapshot {
+        let mut snapshot = Snapshot {
+            files: HashMap::new(),
+            directories: Vec::new(),
+        };
+        app.muted.store(true, std::sync::atomic::Ordering::SeqCst);
+        let mut jobs = match root.is_dir() {
+            true => vec![walker::root(root, app)],
+            false => vec![Job::File(root.to_path_buf(), Vec::new())],
+        };
+        while let Some(job) = jobs.pop() {
+            match job {
+                Job::File(path, _) => {
+                    if let Ok(metadata) = std::fs::metadata(&path) {
+                        let state = (metadata.modified().ok(), metadata.len());
+                        snapshot.files.insert(path, state);
+                    }
+                }
+                Job::Directory(path, ignore, ancestors, key) => {
+                    // Git rewrites its own directory constantly, even when `--hidden`
+                    // would search it; those changes aren't worth a run.
+                    if path.file_name().is_some_and(|name| name == ".git") {
+                        continue;
+                    }
+                    jobs.extend(walker::list_directory(
+                        root, &path, &ignore, &ancestors, app, &key,
+                    ));
+                    snapshot.directories.push(path);
+                }
+            }
+        }
+        app.muted.store(false, std::sync::atomic::Ordering::SeqCst);
+        snapshot
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::ffi::{c_char, c_int, c_ulong, c_void, CString};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::time::Duration;
+
+    const IN_NONBLOCK: c_int = 0o4000;
+    const IN_CLOEXEC: c_int = 0o2000000;
+    /// Modified, attributes changed, closed after writing, moved out or in, created,
+    /// deleted, and the directory itself deleted or moved.
+    const IN_CHANGES: u32 = 0x2 | 0x4 | 0x8 | 0x40 | 0x80 | 0x100 | 0x200 | 0x400 | 0x800;
+    const POLLIN: i16 = 0x1;
+
+    #[repr(C)]
+    struct PollFd {
+        fd: c_int,
+        events: i16,
+        revents: i16,
+    }
+
+    extern "C" {
+        fn inotify_init1(flags: c_int) -> c_int;
+        fn inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int;
+        fn poll(fds: *mut PollFd, count: c_ulong, timeout: c_int) -> c_int;
+        fn read(fd: c_int, buffer: *mut c_void, count: usize) -> isize;
+        fn close(fd: c_int) -> c_int;
+    }
+
+    /// An inotify instance, watching directories for their entries changing.
+    pub(super) struct Notifier(c_int);
+
+    impl Notifier {
+        /// `None` when inotify can't be used, as when the limit on instances is reached.
+        pub(super) fn new() -> Option<Notifier> {
+            // SAFETY: no pointers are passed; a negative result is an error.
+            let fd = unsafe { inotify_init1(IN_NONBLOCK | IN_CLOEXEC) };
+            (fd >= 0).then_some(Notifier(fd))
+        }
+
+        /// Watches `directory`, again harmlessly if it already is. A directory that
+        /// can't be watched, past the limit on watches, is still rescanned on the next
+        /// change elsewhere.
+        pub(super) fn watch(&mut self, directory: &Path) {
+            let Ok(path) = CString::new(directory.as_os_str().as_bytes()) else {
+                return;
+            };
+            // SAFETY: `path` is NUL-terminated and outlives the call.
+            unsafe {
+                inotify_add_watch(self.0, path.as_ptr(), IN_CHANGES);
+            }
+        }
+
+        /// Waits up to `timeout` for a change, returning whether there was one. The
+        /// events are read and dropped: the caller rescans to see what they mean.
+        pub(super) fn wait(&mut self, timeout: Duration) -> bool {
+            let mut fd = PollFd {
+                fd: self.0,
+                events: POLLIN,
+                revents: 0,
+            };
+            // SAFETY: `fd` is one valid `pollfd` for the duration of the call.
+            let ready = unsafe { poll(&mut fd, 1, timeout.as_millis() as c_int) };
+            if ready <= 0 {
+                return false;
+            }
+            // An editor's save is a burst of events; let it finish before rescanning.
+            std::thread::sleep(Duration::from_millis(50));
+            let mut buffer = [0u8; 4096];
+            // SAFETY: reads into `buffer`, at most its length; the descriptor is
+            // nonblocking, so this stops once the queue is empty.
+            while unsafe { read(self.0, buffer.as_mut_ptr().cast(), buffer.len()) } > 0 {}
+            true
+        }
+    }
+
+    impl Drop for Notifier {
+        fn drop(&mut self) {
+            // SAFETY: the descriptor is owned by this notifier and closed once.
+            unsafe {
+                close(self.0);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::path::Path;
+    use std::time::Duration;
+
+    /// Without notifications, the tree is rescanned every [`super::POLL_INTERVAL`].
+    pub(super) enum Notifier {}
+
+    impl Notifier {
+        pub(super) fn new() -> Option<Notifier> {
+            None
+        }
+
+        pub(super) fn watch(&mut self, _directory: &Path) {
+            match *self {}
+        }
+
+        pub(super) fn wait(&mut self, _timeout: Duration) -> bool {
+            match *self {}
+        }
+    }
+}